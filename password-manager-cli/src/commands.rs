@@ -0,0 +1,445 @@
+// Non-interactive subcommands. Results go to stdout, prompts to stderr, so
+// the output can be piped.
+
+use std::fs;
+use std::io::{self, IsTerminal};
+
+use password_manager::{
+    AuditOptions, GpgCommand, KdbxOptions, KdfAlgorithm, KdfParams, PassphrasePolicy, PasswordEntry, PasswordManager, Result, VaultError, Wordlist,
+    DEFAULT_UNLOCK_TIME,
+};
+use password_manager::strength;
+use serde_json::json;
+
+use crate::prompt::{read_hidden, read_new_password, read_password_from_fd};
+use crate::select::select_entry;
+use crate::view::{
+    folder_counts, print_audit, print_code, print_entry, print_folders, print_history, print_import, print_strength, warn_if_weak,
+};
+use crate::{open_manager, read_export, Cli, Command, ExportFormat, Field, FolderCommand, PolicyArgs};
+
+pub fn run(cli: &Cli, command: &Command) -> Result<()> {
+    if let Command::Generate { length, ref rules } = *command {
+        let password = PasswordManager::generate_password(&rules.policy(length))?;
+        if cli.json {
+            println!("{}", json!({ "password": password }));
+        } else {
+            println!("{}", password);
+        }
+        return Ok(());
+    }
+
+    if let Command::Passphrase { words, ref wordlist, ref separator, capitalize, digit, symbol } = *command {
+        let wordlist = match wordlist {
+            Some(path) => Wordlist::from_file(path)?,
+            None => Wordlist::builtin(),
+        };
+        let policy = PassphrasePolicy {
+            words,
+            separator: separator.clone(),
+            capitalize,
+            add_digit: digit,
+            add_symbol: symbol,
+        };
+        let passphrase = PasswordManager::generate_passphrase(&policy, &wordlist)?;
+        if cli.json {
+            println!(
+                "{}",
+                json!({ "passphrase": passphrase.passphrase, "entropy_bits": passphrase.entropy_bits })
+            );
+        } else {
+            println!("{}", passphrase.passphrase);
+            eprintln!("Entropy: {:.1} bits", passphrase.entropy_bits);
+        }
+        return Ok(());
+    }
+
+    if let Command::Strength { ref password } = *command {
+        let password = match password {
+            Some(password) => password.clone(),
+            None => read_hidden("Password: ")?,
+        };
+        let strength = strength::estimate(&password, &[]);
+        if cli.json {
+            let report = json!({
+                "score": strength.score,
+                "guesses_log10": strength.guesses.log10(),
+                "entropy_bits": strength.entropy_bits,
+                "warning": strength.warning,
+                "suggestions": strength.suggestions,
+            });
+            println!("{}", report);
+        } else {
+            print_strength(&strength);
+        }
+        return Ok(());
+    }
+
+    let mut manager = open_manager(cli)?;
+    unlock(&mut manager, &master_password(cli)?)?;
+
+    match command {
+        Command::Add { service, username, password, generate, urls, notes, fields, otp, folder, tags, rules } => {
+            let password = new_password(password, *generate, rules)?;
+            if generate.is_none() {
+                warn_if_weak(&password, &[service, username]);
+            }
+            let mut entry = PasswordEntry::new(service.clone(), username.clone(), password);
+            entry.urls = urls.clone();
+            entry.notes = notes.clone().unwrap_or_default();
+            entry.fields = fields.clone();
+            entry.otp = otp.clone();
+            entry.folder = folder.clone();
+            entry.tags = tags.clone();
+            let id = manager.insert_entry(entry)?;
+            manager.save_to_file()?;
+            print_change(cli, "added", manager.get_entry(id)?);
+        }
+        Command::Get { entry, account, field } => {
+            let id = select_entry(&manager, entry, account.as_deref(), io::stdin().is_terminal())?;
+            manager.use_entry(id)?;
+            manager.save_without_backup()?;
+            let entry = manager.get_entry(id)?;
+            match (field, cli.json) {
+                (Some(field), false) => println!("{}", field_value(entry, *field)),
+                (Some(field), true) => {
+                    let name = match field {
+                        Field::Id => "id",
+                        Field::Service => "service",
+                        Field::Username => "username",
+                        Field::Password => "password",
+                    };
+                    println!("{}", json!({ name: field_value(entry, *field) }));
+                }
+                (None, false) => print_entry(entry),
+                (None, true) => println!("{}", json!(entry)),
+            }
+        }
+        Command::Edit {
+            entry,
+            account,
+            service,
+            username,
+            password,
+            generate,
+            urls,
+            notes,
+            fields,
+            otp,
+            remove_otp,
+            folder,
+            tags,
+            untags,
+            rules,
+        } => {
+            let id = select_entry(&manager, entry, account.as_deref(), io::stdin().is_terminal())?;
+            let mut entry = manager.get_entry(id)?.clone();
+            if let Some(service) = service {
+                entry.service = service.clone();
+            }
+            if let Some(username) = username {
+                entry.username = username.clone();
+            }
+            if password.is_some() || generate.is_some() {
+                entry.password = new_password(password, *generate, rules)?;
+                if generate.is_none() {
+                    warn_if_weak(&entry.password, &[&entry.service, &entry.username]);
+                }
+            }
+            if !urls.is_empty() {
+                entry.urls = urls.clone();
+            }
+            if let Some(notes) = notes {
+                entry.notes = notes.clone();
+            }
+            for field in fields {
+                match entry.fields.iter_mut().find(|existing| existing.name == field.name) {
+                    Some(existing) => *existing = field.clone(),
+                    None => entry.fields.push(field.clone()),
+                }
+            }
+            if otp.is_some() || *remove_otp {
+                entry.otp = otp.clone();
+            }
+            if let Some(folder) = folder {
+                entry.folder = folder.clone();
+            }
+            entry.tags.retain(|tag| !untags.iter().any(|untag| untag.eq_ignore_ascii_case(tag)));
+            entry.tags.extend(tags.iter().cloned());
+            manager.update_entry(entry)?;
+            manager.save_to_file()?;
+            print_change(cli, "updated", manager.get_entry(id)?);
+        }
+        Command::Otp { entry, account } => {
+            let id = select_entry(&manager, entry, account.as_deref(), io::stdin().is_terminal())?;
+            let code = manager.one_time_code(id)?;
+            // Only HOTP changes the secret; TOTP just records the use.
+            if code.remaining.is_none() {
+                manager.save_to_file()?;
+            } else {
+                manager.save_without_backup()?;
+            }
+            if cli.json {
+                println!("{}", json!({ "code": code.code, "remaining": code.remaining }));
+            } else {
+                print_code(&code);
+            }
+        }
+        Command::Rm { entry, account } => {
+            let id = select_entry(&manager, entry, account.as_deref(), io::stdin().is_terminal())?;
+            let entry = manager.delete_entry(id)?;
+            manager.save_to_file()?;
+            print_change(cli, "deleted", &entry);
+        }
+        Command::History { entry, account, restore } => {
+            let id = select_entry(&manager, entry, account.as_deref(), io::stdin().is_terminal())?;
+            if let Some(n) = restore {
+                manager.restore_password(id, usize::from(*n) - 1)?;
+                manager.save_to_file()?;
+                print_change(cli, "restored", manager.get_entry(id)?);
+                return Ok(());
+            }
+            let history = &manager.get_entry(id)?.history;
+            if cli.json {
+                println!("{}", json!(history));
+            } else {
+                print_history(history);
+            }
+        }
+        Command::Audit { max_age, min_score } => {
+            let options = AuditOptions {
+                max_age_days: *max_age,
+                min_score: *min_score,
+            };
+            let report = manager.audit(&options);
+            if cli.json {
+                println!("{}", json!(report));
+            } else {
+                print_audit(&report);
+            }
+        }
+        Command::BreachCheck => {
+            if cli.hibp.is_none() {
+                eprintln!("Pass the breach list with --hibp");
+            }
+            let findings = manager.check_breaches()?;
+            if cli.json {
+                println!("{}", json!(findings));
+            } else if findings.is_empty() {
+                println!("No entry passwords found in the breach list");
+            } else {
+                for finding in findings {
+                    println!("{}: seen {} times", finding.entry.label, finding.count);
+                }
+            }
+        }
+        Command::Import { file, format, dry_run, folder } => {
+            let (format, mut imported) = read_export(file, *format)?;
+            if let Some(folder) = folder {
+                for entry in &mut imported.entries {
+                    entry.folder = format!("{}/{}", folder, entry.folder);
+                }
+                for path in &mut imported.folders {
+                    *path = format!("{}/{}", folder, path);
+                }
+            }
+            let report = manager.import(imported, *dry_run);
+            if !dry_run {
+                manager.save_to_file()?;
+            }
+            if cli.json {
+                println!("{}", json!({ "format": format, "report": report }));
+            } else {
+                print_import(&report, &format);
+            }
+        }
+        Command::Export { file, format, cipher, recipients } => {
+            let count = match format {
+                ExportFormat::Kdbx => {
+                    let password = read_new_password("Password for the KeePass database: ")?;
+                    let options = KdbxOptions {
+                        cipher: cipher.cipher(),
+                        ..KdbxOptions::default()
+                    };
+                    fs::write(file, manager.export_kdbx(&password, &options)?)?;
+                    manager.list_services().len()
+                }
+                ExportFormat::Pass => manager.export_pass(file, recipients, &GpgCommand::default())?,
+            };
+            if cli.json {
+                println!("{}", json!({ "file": file, "entries": count }));
+            } else {
+                println!("Exported {} entries to {}", count, file.display());
+            }
+        }
+        Command::Search { ref query } => {
+            let results = manager.search(&query.join(" "));
+            // No match is an error in both modes, so scripts see the same exit code.
+            if results.is_empty() {
+                return Err(VaultError::NotFound(query.join(" ")));
+            }
+            if cli.json {
+                let list: Vec<_> = results
+                    .iter()
+                    .map(|result| {
+                        let fields: Vec<String> = result.fields.iter().map(|field| field.to_string()).collect();
+                        json!({
+                            "id": result.entry.id,
+                            "service": result.entry.service,
+                            "username": result.entry.username,
+                            "score": result.score,
+                            "matched": fields,
+                        })
+                    })
+                    .collect();
+                println!("{}", json!(list));
+            } else {
+                for result in results {
+                    println!("{} - {}", result.entry.service, result.entry.username);
+                }
+            }
+        }
+        Command::Ls { sort, folder, tags } => {
+            let entries: Vec<&PasswordEntry> = manager
+                .list_sorted(sort.order())
+                .into_iter()
+                .filter(|entry| folder.as_ref().is_none_or(|folder| entry.in_folder(folder)))
+                .filter(|entry| tags.iter().all(|tag| entry.has_tag(tag)))
+                .collect();
+            if cli.json {
+                let list: Vec<_> = entries
+                    .iter()
+                    .map(|entry| {
+                        json!({
+                            "id": entry.id,
+                            "service": entry.service,
+                            "username": entry.username,
+                            "folder": entry.folder,
+                            "tags": entry.tags,
+                        })
+                    })
+                    .collect();
+                println!("{}", json!(list));
+            } else {
+                for entry in entries {
+                    println!("{} - {}", entry.service, entry.username);
+                }
+            }
+        }
+        Command::Mv { entry, folder, account } => {
+            let id = select_entry(&manager, entry, account.as_deref(), io::stdin().is_terminal())?;
+            manager.move_entry(id, folder)?;
+            manager.save_to_file()?;
+            print_change(cli, "moved", manager.get_entry(id)?);
+        }
+        Command::Folder { action } => {
+            let changed = match action {
+                None | Some(FolderCommand::Ls) => false,
+                Some(FolderCommand::Add { path }) => manager.create_folder(path).map(|_| true)?,
+                Some(FolderCommand::Rename { from, to }) => manager.rename_folder(from, to).map(|_| true)?,
+                Some(FolderCommand::Rm { path }) => manager.delete_folder(path).map(|_| true)?,
+            };
+            if changed {
+                manager.save_to_file()?;
+            }
+            let folders = folder_counts(&manager);
+            if cli.json {
+                let list: Vec<_> = folders.iter().map(|(path, count)| json!({ "folder": path, "entries": count })).collect();
+                println!("{}", json!(list));
+            } else {
+                print_folders(&folders);
+            }
+        }
+        Command::Tags => {
+            let tags = manager.tags();
+            if cli.json {
+                let list: Vec<_> = tags.iter().map(|(tag, count)| json!({ "tag": tag, "entries": count })).collect();
+                println!("{}", json!(list));
+            } else {
+                for (tag, count) in tags {
+                    println!("{} ({})", tag, count);
+                }
+            }
+        }
+        Command::Config { backups, history } => {
+            if let Some(count) = backups {
+                manager.set_backup_count(*count);
+            }
+            if let Some(count) = history {
+                manager.set_history_count(*count);
+            }
+            if backups.is_some() || history.is_some() {
+                manager.save_to_file()?;
+            }
+            if cli.json {
+                println!("{}", json!({ "backups": manager.backup_count(), "history": manager.history_count() }));
+            } else {
+                println!("Backups kept: {}", manager.backup_count());
+                println!("Previous passwords kept per entry: {}", manager.history_count());
+            }
+        }
+        Command::Generate { .. } | Command::Passphrase { .. } | Command::Strength { .. } => {
+            unreachable!("handled before unlocking")
+        }
+    }
+    Ok(())
+}
+
+// Explicit flags first; stdin is the last resort.
+fn master_password(cli: &Cli) -> Result<String> {
+    if let Some(ref path) = cli.password_file {
+        let contents = fs::read_to_string(path)?;
+        return Ok(contents.lines().next().unwrap_or("").to_string());
+    }
+    if let Some(fd) = cli.password_fd {
+        return Ok(read_password_from_fd(fd)?);
+    }
+    read_hidden("Master password: ")
+}
+
+fn unlock(manager: &mut PasswordManager, master_password: &str) -> Result<()> {
+    if manager.needs_migration() {
+        eprintln!("Upgrading vault from the old format...");
+        let kdf = KdfParams::benchmark(KdfAlgorithm::Argon2id, DEFAULT_UNLOCK_TIME);
+        manager.migrate_legacy(master_password, kdf)?;
+        return Ok(());
+    }
+    if !manager.vault_exists() {
+        let message = format!(
+            "no vault at {}; run without a subcommand to create one",
+            manager.path().display()
+        );
+        return Err(VaultError::Io(io::Error::new(io::ErrorKind::NotFound, message)));
+    }
+    manager.unlock(master_password)
+}
+
+fn new_password(password: &Option<String>, generate: Option<usize>, rules: &PolicyArgs) -> Result<String> {
+    if let Some(length) = generate {
+        return PasswordManager::generate_password(&rules.policy(length));
+    }
+    if let Some(password) = password {
+        return Ok(password.clone());
+    }
+    read_new_password("Entry password: ")
+}
+
+fn field_value(entry: &PasswordEntry, field: Field) -> String {
+    match field {
+        Field::Id => entry.id.to_string(),
+        Field::Service => entry.service.clone(),
+        Field::Username => entry.username.clone(),
+        Field::Password => entry.password.clone(),
+    }
+}
+
+fn print_change(cli: &Cli, action: &str, entry: &PasswordEntry) {
+    if cli.json {
+        println!(
+            "{}",
+            json!({ "action": action, "id": entry.id, "service": entry.service, "username": entry.username })
+        );
+    } else {
+        println!("Entry {}: {}", action, entry.label());
+    }
+}
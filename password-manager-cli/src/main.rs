@@ -0,0 +1,553 @@
+mod commands;
+mod menu;
+mod prompt;
+mod select;
+mod view;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use password_manager::{
+    bitwarden, kdbx, onepassword, read_1pux, read_bitwarden_json, read_csv, read_kdbx, read_pass_store, BreachDatabase, CsvFormat, CustomField, FieldKind, GpgCommand, ImportedEntries, KdbxCipher, OtpSecret, PasswordManager,
+    PasswordPolicy, SortOrder, VaultError,
+};
+
+use crate::prompt::read_hidden;
+
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  success
+  1  I/O or other error
+  2  invalid command line
+  3  invalid master password
+  4  entry not found, or no search results
+  5  entry already exists
+  6  vault file corrupted or of an unsupported version
+  7  service has several entries; pass --account or an entry ID";
+
+/// Encrypted password vault. Starts the interactive menu when run without a
+/// subcommand.
+#[derive(Parser)]
+#[command(name = "password-manager", version, after_help = EXIT_CODES_HELP)]
+pub struct Cli {
+    /// Vault file to use
+    #[arg(long, global = true, value_name = "PATH", default_value = "passwords.dat")]
+    pub vault: PathBuf,
+
+    /// Print results (and errors) as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Read the master password from the first line of this file
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "password_fd")]
+    pub password_file: Option<PathBuf>,
+
+    /// Read the master password from this open file descriptor
+    #[arg(long, global = true, value_name = "FD")]
+    pub password_fd: Option<i32>,
+
+    /// Local Have I Been Pwned password list (a sorted HASH:COUNT file or a
+    /// directory of range files) to check passwords against
+    #[arg(long, global = true, value_name = "PATH")]
+    pub hibp: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Add a new entry
+    Add {
+        service: String,
+        #[arg(short, long, default_value = "")]
+        username: String,
+        /// Password to store; prompted for when neither this nor --generate is given
+        #[arg(short, long, conflicts_with = "generate")]
+        password: Option<String>,
+        /// Store a newly generated password of this length
+        #[arg(short, long, value_name = "LENGTH")]
+        generate: Option<usize>,
+        /// Web address of the service; may be repeated
+        #[arg(long = "url", value_name = "URL")]
+        urls: Vec<String>,
+        #[arg(long)]
+        notes: Option<String>,
+        /// Custom field as NAME=VALUE or KIND:NAME=VALUE, where KIND is text
+        /// (the default), hidden, url, email or date; may be repeated
+        #[arg(long = "field", value_name = "FIELD", value_parser = parse_field)]
+        fields: Vec<CustomField>,
+        /// Two-factor secret: an otpauth:// URI or a base32 TOTP key
+        #[arg(long, value_name = "URI", value_parser = parse_otp)]
+        otp: Option<OtpSecret>,
+        /// Folder path such as Work/Email; created if needed
+        #[arg(long, value_name = "PATH", default_value = "")]
+        folder: String,
+        /// Tag the entry; may be repeated
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        #[command(flatten)]
+        rules: PolicyArgs,
+    },
+    /// Show an entry
+    Get {
+        /// Service name or entry ID
+        entry: String,
+        /// Username of the entry to pick when the service has several
+        #[arg(short, long, value_name = "USERNAME")]
+        account: Option<String>,
+        /// Print only this field
+        #[arg(short, long, value_enum)]
+        field: Option<Field>,
+    },
+    /// Change fields of an entry; anything not given is left as it is
+    Edit {
+        /// Service name or entry ID
+        entry: String,
+        /// Username of the entry to pick when the service has several
+        #[arg(short, long, value_name = "USERNAME")]
+        account: Option<String>,
+        /// New service name
+        #[arg(short, long)]
+        service: Option<String>,
+        /// New username
+        #[arg(short, long)]
+        username: Option<String>,
+        #[arg(short, long, conflicts_with = "generate")]
+        password: Option<String>,
+        /// Replace the password with a newly generated one of this length
+        #[arg(short, long, value_name = "LENGTH")]
+        generate: Option<usize>,
+        /// Replace the URLs; may be repeated
+        #[arg(long = "url", value_name = "URL")]
+        urls: Vec<String>,
+        #[arg(long)]
+        notes: Option<String>,
+        /// Set a custom field (see `add`), replacing one of the same name
+        #[arg(long = "field", value_name = "FIELD", value_parser = parse_field)]
+        fields: Vec<CustomField>,
+        /// Set the two-factor secret (see `add`)
+        #[arg(long, value_name = "URI", value_parser = parse_otp, conflicts_with = "remove_otp")]
+        otp: Option<OtpSecret>,
+        /// Remove the two-factor secret
+        #[arg(long)]
+        remove_otp: bool,
+        /// Move the entry into this folder ("" for the top level)
+        #[arg(long, value_name = "PATH")]
+        folder: Option<String>,
+        /// Add a tag; may be repeated
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Remove a tag; may be repeated
+        #[arg(long = "untag", value_name = "TAG")]
+        untags: Vec<String>,
+        #[command(flatten)]
+        rules: PolicyArgs,
+    },
+    /// Print the current two-factor code of an entry
+    Otp {
+        /// Service name or entry ID
+        entry: String,
+        /// Username of the entry to pick when the service has several
+        #[arg(short, long, value_name = "USERNAME")]
+        account: Option<String>,
+    },
+    /// Delete an entry
+    #[command(alias = "remove")]
+    Rm {
+        /// Service name or entry ID
+        entry: String,
+        /// Username of the entry to pick when the service has several
+        #[arg(short, long, value_name = "USERNAME")]
+        account: Option<String>,
+    },
+    /// Show the previous passwords of an entry, newest first
+    History {
+        /// Service name or entry ID
+        entry: String,
+        /// Username of the entry to pick when the service has several
+        #[arg(short, long, value_name = "USERNAME")]
+        account: Option<String>,
+        /// Make the N-th previous password (as listed) current again
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+        restore: Option<u16>,
+    },
+    /// Report weak, reused and old passwords, missing usernames and likely
+    /// duplicate entries
+    Audit {
+        /// Report passwords unchanged for more than this many days
+        #[arg(long, value_name = "DAYS", default_value_t = 365)]
+        max_age: u32,
+        /// Report passwords scoring below this (0-4)
+        #[arg(long, value_name = "SCORE", default_value_t = 3)]
+        min_score: u8,
+    },
+    /// Look up every entry password in the --hibp breach list
+    BreachCheck,
+    /// Import entries from another password manager's export
+    Import {
+        file: PathBuf,
+        /// Format of the export; detected from its contents when not given
+        #[arg(long, value_enum)]
+        format: Option<ImportFormat>,
+        /// Only report what would be imported
+        #[arg(long)]
+        dry_run: bool,
+        /// Put the imported entries (and their folders) under this folder
+        #[arg(long, value_name = "PATH")]
+        folder: Option<String>,
+    },
+    /// Write the whole vault to a KeePass (KDBX 4) database, prompting for
+    /// the password to protect it with, or to a new `pass` store directory
+    Export {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Kdbx)]
+        format: ExportFormat,
+        /// KDBX encryption
+        #[arg(long, value_enum, default_value_t = ExportCipher::Aes256)]
+        cipher: ExportCipher,
+        /// GPG key to encrypt the pass store for; may be repeated. Keys are
+        /// taken from the keyring in $GNUPGHOME
+        #[arg(long = "recipient", value_name = "KEY", required_if_eq("format", "pass"))]
+        recipients: Vec<String>,
+    },
+    /// Find entries by service, username, URL or notes; every word of the
+    /// query must match, fuzzily for short fields. Best matches first
+    #[command(alias = "find")]
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+    /// List all services
+    #[command(alias = "list")]
+    Ls {
+        #[arg(long, value_enum, default_value_t = Sort::Name)]
+        sort: Sort,
+        /// Only entries in this folder or its subfolders
+        #[arg(long, value_name = "PATH")]
+        folder: Option<String>,
+        /// Only entries with this tag; may be repeated to require several
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// Move an entry into a folder, created if needed ("" for the top level)
+    Mv {
+        /// Service name or entry ID
+        entry: String,
+        folder: String,
+        /// Username of the entry to pick when the service has several
+        #[arg(short, long, value_name = "USERNAME")]
+        account: Option<String>,
+    },
+    /// Show the folder tree, or create, rename and remove folders
+    Folder {
+        #[command(subcommand)]
+        action: Option<FolderCommand>,
+    },
+    /// List the tags in use and how many entries carry each
+    Tags,
+    /// Show the settings stored in the vault, or change them
+    Config {
+        /// Number of backups of the vault file to keep; 0 disables them
+        #[arg(long, value_name = "N")]
+        backups: Option<usize>,
+        /// Number of previous passwords to keep per entry; 0 disables the
+        /// history
+        #[arg(long, value_name = "N")]
+        history: Option<usize>,
+    },
+    /// Generate a random password without touching the vault
+    Generate {
+        #[arg(short, long, default_value_t = 16)]
+        length: usize,
+        #[command(flatten)]
+        rules: PolicyArgs,
+    },
+    /// Estimate how hard a password is to guess; prompts for it when not given
+    Strength { password: Option<String> },
+    /// Generate a passphrase of random words; its entropy goes to stderr
+    Passphrase {
+        /// Number of words
+        #[arg(short, long, default_value_t = 6)]
+        words: usize,
+        /// Word file to draw from instead of the built-in EFF large list (one word per
+        /// line, or the numbered EFF format)
+        #[arg(long, value_name = "PATH")]
+        wordlist: Option<PathBuf>,
+        #[arg(short, long, default_value = "-")]
+        separator: String,
+        /// Capitalize every word
+        #[arg(short, long)]
+        capitalize: bool,
+        /// Add a digit to one of the words
+        #[arg(long)]
+        digit: bool,
+        /// Add a symbol to one of the words
+        #[arg(long)]
+        symbol: bool,
+    },
+}
+
+/// Rules for generated passwords. Every enabled class must appear at least
+/// once unless its minimum is set to 0.
+#[derive(Args)]
+#[command(next_help_heading = "Password rules")]
+pub struct PolicyArgs {
+    #[arg(long)]
+    no_lowercase: bool,
+    #[arg(long)]
+    no_uppercase: bool,
+    #[arg(long)]
+    no_digits: bool,
+    #[arg(long)]
+    no_symbols: bool,
+    #[arg(long, value_name = "N")]
+    min_lowercase: Option<usize>,
+    #[arg(long, value_name = "N")]
+    min_uppercase: Option<usize>,
+    #[arg(long, value_name = "N")]
+    min_digits: Option<usize>,
+    #[arg(long, value_name = "N")]
+    min_symbols: Option<usize>,
+    /// Also allow these characters
+    #[arg(long, value_name = "CHARS", default_value = "")]
+    include: String,
+    /// Never use these characters
+    #[arg(long, value_name = "CHARS", default_value = "")]
+    exclude: String,
+    /// Avoid look-alike characters such as l, 1, O and 0
+    #[arg(long)]
+    no_ambiguous: bool,
+    /// Allow the same character at most N times in a row
+    #[arg(long, value_name = "N")]
+    max_repeats: Option<usize>,
+}
+
+impl PolicyArgs {
+    pub fn policy(&self, length: usize) -> PasswordPolicy {
+        let min = |enabled: bool, min: Option<usize>| min.unwrap_or(usize::from(enabled));
+        PasswordPolicy {
+            length,
+            lowercase: !self.no_lowercase,
+            uppercase: !self.no_uppercase,
+            digits: !self.no_digits,
+            symbols: !self.no_symbols,
+            min_lowercase: min(!self.no_lowercase, self.min_lowercase),
+            min_uppercase: min(!self.no_uppercase, self.min_uppercase),
+            min_digits: min(!self.no_digits, self.min_digits),
+            min_symbols: min(!self.no_symbols, self.min_symbols),
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            avoid_ambiguous: self.no_ambiguous,
+            max_repeats: self.max_repeats,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Field {
+    Id,
+    Service,
+    Username,
+    Password,
+}
+
+#[derive(Subcommand)]
+pub enum FolderCommand {
+    /// Show the folder tree with the number of entries in each folder
+    Ls,
+    /// Create a folder and any missing parents
+    Add { path: String },
+    /// Rename or move a folder with everything in it
+    #[command(alias = "mv")]
+    Rename { from: String, to: String },
+    /// Remove an empty folder
+    Rm { path: String },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    Chrome,
+    Firefox,
+    /// Bitwarden CSV export
+    Bitwarden,
+    /// Bitwarden JSON export, plain or password-protected
+    BitwardenJson,
+    /// 1Password 1PUX export
+    #[value(name = "1pux")]
+    OnePux,
+    Lastpass,
+    Keepassxc,
+    /// Any CSV file with a header row naming its columns
+    Csv,
+    /// KeePass or KeePassXC database (KDBX 4); prompts for its password
+    Kdbx,
+    /// Directory of a `pass` password store, decrypted with gpg
+    Pass,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Kdbx,
+    Pass,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportCipher {
+    Aes256,
+    Chacha20,
+}
+
+impl ExportCipher {
+    pub fn cipher(self) -> KdbxCipher {
+        match self {
+            ExportCipher::Aes256 => KdbxCipher::Aes256,
+            ExportCipher::Chacha20 => KdbxCipher::ChaCha20,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Sort {
+    /// By service, then username
+    Name,
+    /// Most recently used first
+    Used,
+    /// Most recently modified first
+    Modified,
+}
+
+impl Sort {
+    pub fn order(self) -> SortOrder {
+        match self {
+            Sort::Name => SortOrder::Name,
+            Sort::Used => SortOrder::LastUsed,
+            Sort::Modified => SortOrder::Modified,
+        }
+    }
+}
+
+fn parse_field(arg: &str) -> Result<CustomField, String> {
+    let (name, value) = arg.split_once('=').ok_or("expected NAME=VALUE")?;
+    let (kind, name) = match name.split_once(':') {
+        Some((kind, name)) => {
+            let kind = FieldKind::from_name(kind).ok_or_else(|| format!("unknown field kind '{}'", kind))?;
+            (kind, name)
+        }
+        None => (FieldKind::Text, name),
+    };
+    Ok(CustomField {
+        name: name.to_string(),
+        kind,
+        value: value.to_string(),
+    })
+}
+
+fn parse_otp(arg: &str) -> Result<OtpSecret, String> {
+    let otp = if arg.starts_with("otpauth:") {
+        OtpSecret::from_uri(arg)
+    } else {
+        OtpSecret::totp(arg)
+    };
+    otp.map_err(|e| e.to_string())
+}
+
+fn exit_code(error: &VaultError) -> i32 {
+    match error {
+        VaultError::Io(_)
+        | VaultError::InvalidOtp(_)
+        | VaultError::WeakPassword(_)
+        | VaultError::InvalidFolder(_)
+        | VaultError::ImportFailed(_)
+        | VaultError::InvalidKdf(_)
+        | VaultError::NoBreachDatabase
+        | VaultError::InvalidBreachDatabase(_)
+        | VaultError::Locked => 1,
+        VaultError::InvalidPolicy(_) => 2,
+        VaultError::WrongPassword => 3,
+        VaultError::NotFound(_) => 4,
+        VaultError::DuplicateEntry(_) => 5,
+        VaultError::CorruptVault(_) | VaultError::UnsupportedVersion(_) => 6,
+        VaultError::AmbiguousEntry(_) => 7,
+    }
+}
+
+/// Reads an export file, returning a description of the format found.
+/// Directories are read as `pass` stores; KeePass databases, 1PUX archives
+/// and JSON exports are recognized by their contents; anything else is read
+/// as CSV.
+pub fn read_export(path: &Path, format: Option<ImportFormat>) -> password_manager::Result<(String, ImportedEntries)> {
+    if format == Some(ImportFormat::Pass) || (format.is_none() && path.is_dir()) {
+        return Ok(("pass".to_string(), read_pass_store(path, &GpgCommand::default())?));
+    }
+    let data = fs::read(path)?;
+    let json = data.trim_ascii_start().starts_with(b"{");
+    let csv_format = match format {
+        None if kdbx::is_kdbx(&data) => return read_keepass(&data),
+        None if onepassword::is_zip(&data) => return Ok(("1Password".to_string(), read_1pux(&data)?)),
+        None if json => return read_bitwarden(&data),
+        Some(ImportFormat::Kdbx) => return read_keepass(&data),
+        Some(ImportFormat::OnePux) => return Ok(("1Password".to_string(), read_1pux(&data)?)),
+        Some(ImportFormat::BitwardenJson) => return read_bitwarden(&data),
+        Some(ImportFormat::Pass) => unreachable!("read above"),
+        None => None,
+        Some(ImportFormat::Chrome) => Some(CsvFormat::Chrome),
+        Some(ImportFormat::Firefox) => Some(CsvFormat::Firefox),
+        Some(ImportFormat::Bitwarden) => Some(CsvFormat::Bitwarden),
+        Some(ImportFormat::Lastpass) => Some(CsvFormat::LastPass),
+        Some(ImportFormat::Keepassxc) => Some(CsvFormat::KeePassXc),
+        Some(ImportFormat::Csv) => Some(CsvFormat::Generic),
+    };
+    let (format, imported) = read_csv(&data[..], csv_format)?;
+    Ok((format.to_string(), imported))
+}
+
+fn read_keepass(data: &[u8]) -> password_manager::Result<(String, ImportedEntries)> {
+    let password = read_hidden("KeePass database password: ")?;
+    let imported = read_kdbx(data, &password).map_err(export_password_error)?;
+    Ok(("KeePass".to_string(), imported))
+}
+
+fn read_bitwarden(data: &[u8]) -> password_manager::Result<(String, ImportedEntries)> {
+    let password = if bitwarden::is_password_protected(data) { Some(read_hidden("Export file password: ")?) } else { None };
+    let imported = read_bitwarden_json(data, password.as_deref()).map_err(export_password_error)?;
+    Ok(("Bitwarden JSON".to_string(), imported))
+}
+
+// Not to be mistaken for the vault's own master password.
+fn export_password_error(e: VaultError) -> VaultError {
+    match e {
+        VaultError::WrongPassword => VaultError::ImportFailed("wrong password for the export".to_string()),
+        e => e,
+    }
+}
+
+/// The vault named on the command line, with the breach list attached.
+pub fn open_manager(cli: &Cli) -> password_manager::Result<PasswordManager> {
+    let mut manager = PasswordManager::open(&cli.vault);
+    if let Some(ref path) = cli.hibp {
+        manager.set_breach_database(Some(BreachDatabase::open(path)?));
+    }
+    Ok(manager)
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Some(ref command) => commands::run(&cli, command),
+        None => open_manager(&cli).map(|mut manager| menu::run(&mut manager)),
+    };
+
+    if let Err(e) = result {
+        if cli.json {
+            let error = serde_json::json!({ "error": e.to_string(), "code": exit_code(&e) });
+            eprintln!("{}", error);
+        } else {
+            eprintln!("Error: {}", e);
+        }
+        process::exit(exit_code(&e));
+    }
+}
@@ -0,0 +1,635 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use password_manager::strength;
+use password_manager::{
+    AuditOptions, CustomField, FieldKind, KdbxOptions, KdfAlgorithm, KdfParams, OtpSecret, PassphrasePolicy, PasswordEntry, PasswordManager, PasswordPolicy,
+    Result, SortOrder, Uuid, Wordlist,
+    DEFAULT_UNLOCK_TIME,
+};
+
+use crate::prompt::{read_hidden, read_line, read_new_password, read_password};
+use crate::read_export;
+use crate::select::select_entry;
+use crate::view::{
+    folder_counts, print_audit, print_code, print_entry, print_folders, print_history, print_import, print_strength, warn_if_weak,
+};
+
+// The numbered menu used when the program is started without a subcommand.
+pub fn run(manager: &mut PasswordManager) {
+    if let Err(e) = open_vault(manager) {
+        println!("Error: {}", e);
+        return;
+    }
+
+    loop {
+        println!("\n=== Password Manager ===");
+        println!("1. Add Entry");
+        println!("2. Get Entry");
+        println!("3. Delete Entry");
+        println!("4. List Services");
+        println!("5. Generate Password");
+        println!("6. Change Master Password");
+        println!("7. Backups");
+        println!("8. Edit Entry");
+        println!("9. Password History");
+        println!("10. One-Time Code");
+        println!("11. Audit Vault");
+        println!("12. Search");
+        println!("13. Folders and Tags");
+        println!("14. Import");
+        println!("15. Export to KeePass");
+        println!("16. Save and Exit");
+
+        print!("\nEnter choice: ");
+        io::stdout().flush().unwrap();
+        let choice = match read_line() {
+            Ok(choice) => choice,
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
+            }
+        };
+
+        match run_choice(manager, &choice) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+}
+
+// Carries out one choice of the main menu; false once the vault has been
+// saved and the menu should close.
+fn run_choice(manager: &mut PasswordManager, choice: &str) -> Result<bool> {
+    match choice {
+        "1" => {
+            print!("Service name: ");
+            io::stdout().flush().unwrap();
+            let service = read_line()?;
+
+            print!("Username: ");
+            io::stdout().flush().unwrap();
+            let username = read_line()?;
+
+            let password = read_new_password("Enter password: ")?;
+            warn_if_weak(&password, &[&service, &username]);
+
+            let mut entry = PasswordEntry::new(service, username, password);
+            read_urls(&mut entry.urls)?;
+            entry.notes = read_notes()?;
+            read_fields(&mut entry.fields)?;
+            entry.otp = read_otp()?;
+            print!("Folder (blank for none): ");
+            io::stdout().flush().unwrap();
+            entry.folder = read_line()?;
+            entry.tags = read_tags("")?;
+            manager.insert_entry(entry)?;
+            println!("Entry added successfully");
+        }
+        "2" => {
+            print!("Service name: ");
+            io::stdout().flush().unwrap();
+            let service = read_line()?;
+
+            let id = select_entry(manager, &service, None, true)?;
+            println!();
+            print_entry(manager.use_entry(id)?);
+        }
+        "3" => {
+            print!("Service name: ");
+            io::stdout().flush().unwrap();
+            let service = read_line()?;
+            let id = select_entry(manager, &service, None, true)?;
+            manager.delete_entry(id)?;
+            println!("Entry deleted");
+        }
+        "4" => {
+            print!("Sort by name, last used or modified? [N/u/m]: ");
+            io::stdout().flush().unwrap();
+            let order = match read_line()?.to_ascii_lowercase().as_str() {
+                "u" => SortOrder::LastUsed,
+                "m" => SortOrder::Modified,
+                _ => SortOrder::Name,
+            };
+            let entries = manager.list_sorted(order);
+            if entries.is_empty() {
+                println!("No entries saved");
+            } else {
+                println!("\n=== Saved Services ===");
+                for entry in entries {
+                    println!("{} - {}", entry.service, entry.username);
+                }
+            }
+        }
+        "5" => {
+            print!("Password or passphrase? [P/w]: ");
+            io::stdout().flush().unwrap();
+            if read_line()?.eq_ignore_ascii_case("w") {
+                generate_passphrase()?;
+            } else {
+                let password = PasswordManager::generate_password(&read_policy()?)?;
+                println!("Generated password: {}", password);
+            }
+        }
+        "6" => {
+            println!("Current master password:");
+            let old_password = read_password()?;
+            println!("New master password:");
+            let new_password = read_new_password("Enter password: ")?;
+            let kdf = manager.kdf_params().unwrap_or_else(|| KdfParams::default_for(KdfAlgorithm::Argon2id));
+            manager.change_master_password(&old_password, &new_password, kdf)?;
+            println!("Master password changed");
+        }
+        "7" => restore_from_backup(manager)?,
+        "8" => {
+            print!("Service name: ");
+            io::stdout().flush().unwrap();
+            let service = read_line()?;
+            let id = select_entry(manager, &service, None, true)?;
+            edit_entry(manager, id)?;
+        }
+        "9" => {
+            print!("Service name: ");
+            io::stdout().flush().unwrap();
+            let service = read_line()?;
+            let id = select_entry(manager, &service, None, true)?;
+            password_history(manager, id)?;
+        }
+        "10" => {
+            print!("Service name: ");
+            io::stdout().flush().unwrap();
+            let service = read_line()?;
+            let id = select_entry(manager, &service, None, true)?;
+            let code = manager.one_time_code(id)?;
+            // A HOTP code uses up its counter value, so the new counter must
+            // not wait for Save and Exit.
+            if code.remaining.is_none() {
+                manager.save_to_file()?;
+            }
+            print_code(&code);
+        }
+        "11" => print_audit(&manager.audit(&AuditOptions::default())),
+        "12" => {
+            print!("Search for: ");
+            io::stdout().flush().unwrap();
+            let results = manager.search(&read_line()?);
+            if results.is_empty() {
+                println!("Nothing found");
+            } else {
+                for result in results {
+                    let fields: Vec<String> = result.fields.iter().map(|field| field.to_string()).collect();
+                    println!("{} - {} (matched {})", result.entry.service, result.entry.username, fields.join(", "));
+                }
+            }
+        }
+        "13" => folders_menu(manager)?,
+        "14" => import(manager)?,
+        "15" => export(manager)?,
+        "16" => {
+            manager.save_to_file()?;
+            println!("Data saved");
+            return Ok(false);
+        }
+        _ => println!("Invalid choice"),
+    }
+    Ok(true)
+}
+
+// Changes fields of a copy of the entry one at a time and stores it only
+// when the user saves, so cancelling leaves the entry untouched.
+fn edit_entry(manager: &mut PasswordManager, id: Uuid) -> Result<()> {
+    let mut entry = manager.get_entry(id)?.clone();
+    loop {
+        println!();
+        print_entry(&entry);
+        println!("\n--- Edit {} ---", entry.label());
+        println!("1. Service");
+        println!("2. Username");
+        println!("3. Password");
+        println!("4. URLs");
+        println!("5. Notes");
+        println!("6. Custom Fields");
+        println!("7. Two-Factor Secret");
+        println!("8. Folder");
+        println!("9. Tags");
+        println!("10. Save Changes");
+        println!("11. Cancel");
+
+        print!("\nEnter choice: ");
+        io::stdout().flush().unwrap();
+        match read_line()?.as_str() {
+            "1" => entry.service = read_with_default("Service name", &entry.service)?,
+            "2" => entry.username = read_with_default("Username", &entry.username)?,
+            "3" => {
+                print!("Generate a new password? [y/N]: ");
+                io::stdout().flush().unwrap();
+                if !read_line()?.eq_ignore_ascii_case("y") {
+                    entry.password = read_new_password("Enter password: ")?;
+                    warn_if_weak(&entry.password, &[&entry.service, &entry.username]);
+                } else {
+                    match PasswordManager::generate_password(&read_policy()?) {
+                        Ok(password) => entry.password = password,
+                        Err(e) => println!("Error: {}", e),
+                    }
+                }
+            }
+            "4" => {
+                println!("Enter the new list of URLs");
+                entry.urls.clear();
+                read_urls(&mut entry.urls)?;
+            }
+            "5" => entry.notes = read_notes()?,
+            "6" => edit_fields(&mut entry.fields)?,
+            "7" => entry.otp = read_otp()?,
+            "8" => entry.folder = read_with_default("Folder (/ for none)", &entry.folder)?,
+            "9" => entry.tags = read_tags(&entry.tags.join(", "))?,
+            "10" => {
+                manager.update_entry(entry)?;
+                println!("Entry updated");
+                return Ok(());
+            }
+            "11" | "" => return Ok(()),
+            _ => println!("Invalid choice"),
+        }
+    }
+}
+
+fn folders_menu(manager: &mut PasswordManager) -> Result<()> {
+    loop {
+        println!("\n=== Folders ===");
+        print_folders(&folder_counts(manager));
+        let tags = manager.tags();
+        if !tags.is_empty() {
+            let tags: Vec<String> = tags.iter().map(|(tag, count)| format!("{} ({})", tag, count)).collect();
+            println!("Tags: {}", tags.join(", "));
+        }
+        println!("\n1. List Folder");
+        println!("2. List Tag");
+        println!("3. New Folder");
+        println!("4. Rename Folder");
+        println!("5. Remove Folder");
+        println!("6. Back");
+
+        print!("\nEnter choice: ");
+        io::stdout().flush().unwrap();
+        let choice = read_line()?;
+        let result = match choice.as_str() {
+            "1" | "2" => {
+                let by_folder = choice == "1";
+                print!("{}: ", if by_folder { "Folder" } else { "Tag" });
+                io::stdout().flush().unwrap();
+                let name = read_line()?;
+                let entries: Vec<&PasswordEntry> = manager
+                    .list_services()
+                    .into_iter()
+                    .filter(|entry| if by_folder { entry.in_folder(&name) } else { entry.has_tag(&name) })
+                    .collect();
+                if entries.is_empty() {
+                    println!("No entries");
+                }
+                for entry in entries {
+                    println!("{} - {}", entry.service, entry.username);
+                }
+                Ok(())
+            }
+            "3" => {
+                print!("Folder path (e.g. Work/Email): ");
+                io::stdout().flush().unwrap();
+                manager.create_folder(&read_line()?).map(|path| println!("Created {}", path))
+            }
+            "4" => {
+                print!("Folder to rename: ");
+                io::stdout().flush().unwrap();
+                let from = read_line()?;
+                print!("New path: ");
+                io::stdout().flush().unwrap();
+                manager.rename_folder(&from, &read_line()?).map(|_| println!("Folder renamed"))
+            }
+            "5" => {
+                print!("Folder to remove: ");
+                io::stdout().flush().unwrap();
+                manager.delete_folder(&read_line()?).map(|_| println!("Folder removed"))
+            }
+            "6" | "" => return Ok(()),
+            _ => {
+                println!("Invalid choice");
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            println!("Error: {}", e);
+        }
+    }
+}
+
+// Shows a dry run first and imports only once the user agrees.
+fn import(manager: &mut PasswordManager) -> Result<()> {
+    print!("Export file: ");
+    io::stdout().flush().unwrap();
+    let path = read_line()?;
+    if path.is_empty() {
+        return Ok(());
+    }
+    let (format, imported) = read_export(Path::new(&path), None)?;
+    let preview = manager.import(imported.clone(), true);
+    print_import(&preview, &format);
+    if preview.imported.is_empty() {
+        return Ok(());
+    }
+
+    print!("Import {} entries? [y/N]: ", preview.imported.len());
+    io::stdout().flush().unwrap();
+    if read_line()?.eq_ignore_ascii_case("y") {
+        let report = manager.import(imported, false);
+        println!("Imported {} entries", report.imported.len());
+    }
+    Ok(())
+}
+
+fn export(manager: &PasswordManager) -> Result<()> {
+    print!("KeePass database to write: ");
+    io::stdout().flush().unwrap();
+    let path = read_line()?;
+    if path.is_empty() {
+        return Ok(());
+    }
+    let password = read_new_password("Password for the KeePass database: ")?;
+    fs::write(&path, manager.export_kdbx(&password, &KdbxOptions::default())?)?;
+    println!("Exported {} entries to {}", manager.list_services().len(), path);
+    Ok(())
+}
+
+fn password_history(manager: &mut PasswordManager, id: Uuid) -> Result<()> {
+    let history = &manager.get_entry(id)?.history;
+    print_history(history);
+    if history.is_empty() {
+        return Ok(());
+    }
+
+    print!("\nRestore which password (blank to cancel): ");
+    io::stdout().flush().unwrap();
+    match read_line()?.parse::<usize>() {
+        Ok(n) if n >= 1 && n <= history.len() => {
+            manager.restore_password(id, n - 1)?;
+            println!("Password restored");
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Asks for the common generator rules; blank answers keep the defaults.
+fn read_policy() -> Result<PasswordPolicy> {
+    let mut policy = PasswordPolicy::default();
+
+    print!("Password length [{}]: ", policy.length);
+    io::stdout().flush().unwrap();
+    policy.length = read_line()?.parse::<usize>().unwrap_or(policy.length);
+
+    print!("Character classes (l=lower, u=upper, d=digits, s=symbols) [luds]: ");
+    io::stdout().flush().unwrap();
+    let classes = read_line()?.to_ascii_lowercase();
+    if !classes.is_empty() {
+        policy.lowercase = classes.contains('l');
+        policy.uppercase = classes.contains('u');
+        policy.digits = classes.contains('d');
+        policy.symbols = classes.contains('s');
+        policy.min_lowercase = usize::from(policy.lowercase);
+        policy.min_uppercase = usize::from(policy.uppercase);
+        policy.min_digits = usize::from(policy.digits);
+        policy.min_symbols = usize::from(policy.symbols);
+    }
+
+    print!("Avoid look-alike characters like l, 1, O, 0? [y/N]: ");
+    io::stdout().flush().unwrap();
+    policy.avoid_ambiguous = read_line()?.eq_ignore_ascii_case("y");
+
+    print!("Characters to exclude: ");
+    io::stdout().flush().unwrap();
+    policy.exclude = read_line()?;
+    Ok(policy)
+}
+
+fn generate_passphrase() -> Result<()> {
+    let mut policy = PassphrasePolicy::default();
+
+    print!("Number of words [{}]: ", policy.words);
+    io::stdout().flush().unwrap();
+    policy.words = read_line()?.parse::<usize>().unwrap_or(policy.words);
+
+    print!("Separator [{}]: ", policy.separator);
+    io::stdout().flush().unwrap();
+    let separator = read_line()?;
+    if !separator.is_empty() {
+        policy.separator = separator;
+    }
+
+    print!("Capitalize words? [y/N]: ");
+    io::stdout().flush().unwrap();
+    policy.capitalize = read_line()?.eq_ignore_ascii_case("y");
+
+    print!("Add a digit and a symbol? [y/N]: ");
+    io::stdout().flush().unwrap();
+    if read_line()?.eq_ignore_ascii_case("y") {
+        policy.add_digit = true;
+        policy.add_symbol = true;
+    }
+
+    print!("Wordlist file (blank for the built-in list): ");
+    io::stdout().flush().unwrap();
+    let wordlist = match read_line()? {
+        path if path.is_empty() => Wordlist::builtin(),
+        path => Wordlist::from_file(Path::new(&path))?,
+    };
+
+    let passphrase = PasswordManager::generate_passphrase(&policy, &wordlist)?;
+    println!("Generated passphrase: {}", passphrase.passphrase);
+    println!("Entropy: {:.1} bits", passphrase.entropy_bits);
+    Ok(())
+}
+
+// Blank input keeps the current value.
+fn read_with_default(prompt: &str, current: &str) -> Result<String> {
+    print!("{} [{}]: ", prompt, current);
+    io::stdout().flush().unwrap();
+    match read_line()? {
+        value if value.is_empty() => Ok(current.to_string()),
+        value => Ok(value),
+    }
+}
+
+fn read_urls(urls: &mut Vec<String>) -> Result<()> {
+    loop {
+        print!("URL (blank to skip): ");
+        io::stdout().flush().unwrap();
+        match read_line()? {
+            url if url.is_empty() => return Ok(()),
+            url => urls.push(url),
+        }
+    }
+}
+
+// Comma-separated; a blank answer keeps `current` and `-` clears it.
+fn read_tags(current: &str) -> Result<Vec<String>> {
+    if current.is_empty() {
+        print!("Tags, comma-separated (blank for none): ");
+    } else {
+        print!("Tags, comma-separated (- for none) [{}]: ", current);
+    }
+    io::stdout().flush().unwrap();
+    let tags = match read_line()? {
+        answer if answer.is_empty() => current.to_string(),
+        answer if answer == "-" => String::new(),
+        answer => answer,
+    };
+    Ok(tags.split(',').map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()).collect())
+}
+
+// Ends at an empty line or at the end of input.
+fn read_notes() -> Result<String> {
+    println!("Notes (end with an empty line):");
+    let mut notes = Vec::new();
+    loop {
+        let mut line = String::new();
+        io::stdin().read_line(&mut line)?;
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        notes.push(line.to_string());
+    }
+    Ok(notes.join("\n"))
+}
+
+fn read_otp() -> Result<Option<OtpSecret>> {
+    loop {
+        print!("Two-factor secret or otpauth:// URI (blank for none): ");
+        io::stdout().flush().unwrap();
+        let input = read_line()?;
+        let otp = match input.as_str() {
+            "" => return Ok(None),
+            uri if uri.starts_with("otpauth:") => OtpSecret::from_uri(uri),
+            secret => OtpSecret::totp(secret),
+        };
+        match otp {
+            Ok(otp) => return Ok(Some(otp)),
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+}
+
+fn edit_fields(fields: &mut Vec<CustomField>) -> Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        println!("{}. {} ({})", i + 1, field.name, field.kind);
+    }
+    if !fields.is_empty() {
+        print!("Remove which field (blank to keep all): ");
+        io::stdout().flush().unwrap();
+        if let Ok(n) = read_line()?.parse::<usize>() {
+            if n >= 1 && n <= fields.len() {
+                fields.remove(n - 1);
+            }
+        }
+    }
+    read_fields(fields)
+}
+
+fn read_fields(fields: &mut Vec<CustomField>) -> Result<()> {
+    loop {
+        print!("Custom field name (blank to skip): ");
+        io::stdout().flush().unwrap();
+        let name = read_line()?;
+        if name.is_empty() {
+            return Ok(());
+        }
+        print!("Type (text, hidden, url, email, date) [text]: ");
+        io::stdout().flush().unwrap();
+        let kind = FieldKind::from_name(&read_line()?).unwrap_or(FieldKind::Text);
+        let value = if kind == FieldKind::Hidden {
+            read_hidden("Value: ")?
+        } else {
+            print!("Value: ");
+            io::stdout().flush().unwrap();
+            read_line()?
+        };
+        fields.push(CustomField { name, kind, value });
+    }
+}
+
+// Migrates, unlocks or creates the vault depending on what is on disk.
+fn open_vault(manager: &mut PasswordManager) -> Result<()> {
+    if manager.needs_migration() {
+        println!("Found a vault in the old format. Enter master password to upgrade it:");
+        let password = read_password()?;
+        println!("Calibrating key derivation...");
+        let kdf = KdfParams::benchmark(KdfAlgorithm::Argon2id, DEFAULT_UNLOCK_TIME);
+        let migration = manager.migrate_legacy(&password, kdf)?;
+        println!("Migrated {} entries to the new vault format", migration.migrated);
+        if migration.skipped > 0 {
+            println!(
+                "Skipped {} unreadable lines; they are kept in {}",
+                migration.skipped,
+                migration.vault_backup.display()
+            );
+        }
+    } else if manager.vault_exists() {
+        println!("Enter master password:");
+        let password = read_password()?;
+        manager.unlock(&password)?;
+        println!("Data loaded");
+    } else {
+        println!("Setup new master password:");
+        let password = loop {
+            let password = read_new_password("Enter password: ")?;
+            match manager.check_master_password(&password) {
+                Ok(strength) => {
+                    println!("Strength: {} ({}/4)", strength.label(), strength.score);
+                    break password;
+                }
+                // An empty answer gives up rather than asking forever.
+                Err(e) if password.is_empty() => return Err(e),
+                Err(e) => {
+                    println!("{}", e);
+                    print_strength(&strength::estimate(&password, &[]));
+                }
+            }
+        };
+
+        print!("Key derivation [argon2id/scrypt/pbkdf2] (default argon2id): ");
+        io::stdout().flush().unwrap();
+        let algorithm = KdfAlgorithm::from_name(&read_line()?).unwrap_or(KdfAlgorithm::Argon2id);
+
+        println!("Calibrating key derivation...");
+        let kdf = KdfParams::benchmark(algorithm, DEFAULT_UNLOCK_TIME);
+        println!("Using {}", kdf);
+        manager.setup_master_password(&password, kdf)?;
+    }
+    Ok(())
+}
+
+fn restore_from_backup(manager: &mut PasswordManager) -> Result<()> {
+    let backups = manager.list_backups()?;
+    if backups.is_empty() {
+        println!("No backups yet");
+        return Ok(());
+    }
+
+    println!("\n=== Backups (newest first) ===");
+    for (i, backup) in backups.iter().enumerate() {
+        println!("{}. {}", i + 1, backup.display());
+    }
+    print!("\nRestore which backup (blank to cancel): ");
+    io::stdout().flush().unwrap();
+    let backup = match read_line()?.parse::<usize>() {
+        Ok(n) if n >= 1 && n <= backups.len() => &backups[n - 1],
+        _ => return Ok(()),
+    };
+
+    println!("Master password for this backup:");
+    let password = read_password()?;
+    manager.restore_backup(backup, &password)?;
+    println!("Restored {}", backup.display());
+    Ok(())
+}
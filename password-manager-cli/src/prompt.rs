@@ -0,0 +1,86 @@
+// Prompts go to stderr so they never end up in piped output. Passwords are
+// read without echo when stdin is a terminal and as a plain line otherwise.
+// A closed stdin is an error rather than an endless stream of blank answers.
+
+use std::io::{self, IsTerminal, Write};
+
+use password_manager::Result;
+
+pub fn read_line() -> Result<String> {
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no more input").into());
+    }
+    Ok(input.trim().to_string())
+}
+
+pub fn read_hidden(prompt: &str) -> Result<String> {
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    if io::stdin().is_terminal() {
+        Ok(rpassword::read_password()?)
+    } else {
+        read_line()
+    }
+}
+
+pub fn read_password() -> Result<String> {
+    read_hidden("Enter password: ")
+}
+
+// Asks twice on a terminal, where a typo cannot be seen. Piped input is
+// taken as is.
+pub fn read_new_password(prompt: &str) -> Result<String> {
+    loop {
+        let password = read_hidden(prompt)?;
+        if !io::stdin().is_terminal() {
+            return Ok(password);
+        }
+        if read_hidden("Confirm password: ")? == password {
+            return Ok(password);
+        }
+        eprintln!("Passwords do not match, try again");
+    }
+}
+
+// Reads the first line from an inherited file descriptor, e.g.
+// `password-manager --password-fd 3 ls 3< secret`. The descriptor itself is
+// left open, since it belongs to the caller (and may well be stdin). It is
+// read a byte at a time so that nothing after the newline is consumed: the
+// rest may be input meant for a later prompt or for another process.
+#[cfg(unix)]
+pub fn read_password_from_fd(fd: i32) -> io::Result<String> {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::unix::io::BorrowedFd;
+
+    // SAFETY: `borrow_raw` needs `fd` to stay open while it is borrowed. The
+    // borrow only lasts for the dup below and nothing in this process closes
+    // descriptors meanwhile; a number that is not open makes the dup fail
+    // with EBADF. Only the duplicate is read from and closed.
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    let mut file = File::from(borrowed.try_clone_to_owned()?);
+    let mut line = Vec::new();
+    let mut byte = [0u8];
+    loop {
+        match file.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "the password is not valid UTF-8"))
+}
+
+#[cfg(not(unix))]
+pub fn read_password_from_fd(_fd: i32) -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--password-fd is only supported on Unix",
+    ))
+}
@@ -0,0 +1,57 @@
+use std::io::{self, Write};
+
+use password_manager::{PasswordEntry, PasswordManager, Result, Uuid, VaultError};
+
+use crate::prompt::read_line;
+
+// Resolves what the user typed to a single entry: either an entry ID or a
+// service name, optionally narrowed down by username. When a service has
+// several entries and `interactive` is set, the user picks one from a list
+// (a blank answer cancels); when it has none, the list offers the closest
+// search results instead.
+pub fn select_entry(manager: &PasswordManager, query: &str, username: Option<&str>, interactive: bool) -> Result<Uuid> {
+    if let Ok(id) = Uuid::parse_str(query) {
+        return manager.get_entry(id).map(|entry| entry.id);
+    }
+
+    let candidates = manager.find_entries(query, username);
+    match candidates.as_slice() {
+        [] if interactive => {
+            let similar: Vec<&PasswordEntry> = manager
+                .search(query)
+                .into_iter()
+                .map(|result| result.entry)
+                .filter(|entry| username.is_none_or(|username| entry.username == username))
+                .collect();
+            if similar.is_empty() {
+                return Err(VaultError::NotFound(query.to_string()));
+            }
+            eprintln!("No entry for '{}'. Did you mean:", query);
+            choose(&similar)?.ok_or_else(|| VaultError::NotFound(query.to_string()))
+        }
+        [] => Err(VaultError::NotFound(query.to_string())),
+        [entry] => Ok(entry.id),
+        _ if !interactive => Err(VaultError::AmbiguousEntry(query.to_string())),
+        _ => {
+            eprintln!("'{}' has several entries:", query);
+            choose(&candidates)?.ok_or_else(|| VaultError::AmbiguousEntry(query.to_string()))
+        }
+    }
+}
+
+// Numbered list on stderr; `None` when the user gives a blank answer.
+fn choose(entries: &[&PasswordEntry]) -> Result<Option<Uuid>> {
+    for (i, entry) in entries.iter().enumerate() {
+        eprintln!("{}. {} ({})", i + 1, entry.label(), entry.id);
+    }
+    loop {
+        eprint!("Which one? ");
+        io::stderr().flush()?;
+        let choice = read_line()?;
+        match choice.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= entries.len() => return Ok(Some(entries[n - 1].id)),
+            _ if choice.is_empty() => return Ok(None),
+            _ => eprintln!("Enter a number between 1 and {}", entries.len()),
+        }
+    }
+}
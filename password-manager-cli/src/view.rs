@@ -0,0 +1,164 @@
+use chrono::{DateTime, Local, Utc};
+use password_manager::strength::{self, Strength};
+use password_manager::{AuditReport, ImportReport, OtpCode, OtpKind, PasswordEntry, PasswordManager, PreviousPassword};
+
+// The full "Get Entry" view shared by the menu and `get`.
+pub fn print_entry(entry: &PasswordEntry) {
+    println!("ID: {}", entry.id);
+    println!("Service: {}", entry.service);
+    println!("Username: {}", entry.username);
+    println!("Password: {}", entry.password);
+    for url in &entry.urls {
+        println!("URL: {}", url);
+    }
+    if !entry.folder.is_empty() {
+        println!("Folder: {}", entry.folder);
+    }
+    if !entry.tags.is_empty() {
+        println!("Tags: {}", entry.tags.join(", "));
+    }
+    for field in &entry.fields {
+        println!("{} ({}): {}", field.name, field.kind, field.value);
+    }
+    match entry.otp.as_ref().map(|otp| (otp.kind, otp.digits)) {
+        Some((OtpKind::Totp { period }, digits)) => {
+            println!("Two-factor: TOTP, {} digits every {} s", digits, period)
+        }
+        Some((OtpKind::Hotp { counter }, digits)) => {
+            println!("Two-factor: HOTP, {} digits, counter {}", digits, counter)
+        }
+        None => {}
+    }
+    if !entry.notes.is_empty() {
+        println!("Notes:");
+        for line in entry.notes.lines() {
+            println!("  {}", line);
+        }
+    }
+    println!("Created: {}", local_time(entry.created));
+    println!("Modified: {}", local_time(entry.modified));
+    match entry.last_used {
+        Some(time) => println!("Last used: {}", local_time(time)),
+        None => println!("Last used: never"),
+    }
+}
+
+// Every folder with the number of entries directly inside it.
+pub fn folder_counts(manager: &PasswordManager) -> Vec<(&str, usize)> {
+    let entries = manager.list_services();
+    manager
+        .folders()
+        .into_iter()
+        .map(|folder| (folder, entries.iter().filter(|entry| entry.folder == folder).count()))
+        .collect()
+}
+
+// Indented tree; `folders` is sorted, so subfolders follow their parent.
+pub fn print_folders(folders: &[(&str, usize)]) {
+    if folders.is_empty() {
+        println!("No folders");
+    }
+    for (path, count) in folders {
+        let depth = path.matches('/').count();
+        let name = path.rsplit('/').next().unwrap_or(path);
+        println!("{}{} ({})", "  ".repeat(depth), name, count);
+    }
+}
+
+pub fn print_import(report: &ImportReport, format: &str) {
+    let verb = if report.dry_run { "Would import" } else { "Imported" };
+    println!("{} {} entries from a {} export", verb, report.imported.len(), format);
+    for duplicate in &report.duplicates {
+        let password = if duplicate.same_password { "same password" } else { "different password" };
+        println!("Duplicate of an existing entry: {} ({})", duplicate.label, password);
+    }
+    for skipped in &report.skipped {
+        println!("Skipped {}: {}", skipped.location, skipped.reason);
+    }
+    for unmapped in &report.unmapped {
+        println!("Not imported from {}: {}", unmapped.item, unmapped.field);
+    }
+}
+
+pub fn print_history(history: &[PreviousPassword]) {
+    if history.is_empty() {
+        println!("No previous passwords");
+    }
+    for (i, previous) in history.iter().enumerate() {
+        println!("{}. {} (replaced {})", i + 1, previous.password, local_time(previous.replaced));
+    }
+}
+
+pub fn print_code(code: &OtpCode) {
+    match code.remaining {
+        Some(seconds) => println!("{} (valid for {} s)", code.code, seconds),
+        None => println!("{}", code.code),
+    }
+}
+
+pub fn print_strength(strength: &Strength) {
+    println!("Score: {}/4 ({})", strength.score, strength.label());
+    println!("Estimated guesses: 10^{:.1} ({:.1} bits)", strength.guesses.log10(), strength.entropy_bits);
+    if let Some(ref warning) = strength.warning {
+        println!("Warning: {}", warning);
+    }
+    for suggestion in &strength.suggestions {
+        println!("Suggestion: {}", suggestion);
+    }
+}
+
+pub fn print_audit(report: &AuditReport) {
+    println!("Vault health: {}/100 ({} entries)", report.score, report.entries);
+    if report.is_clean() {
+        println!("No problems found");
+        return;
+    }
+    if !report.weak.is_empty() {
+        println!("\nWeak passwords:");
+        for finding in &report.weak {
+            match finding.warning {
+                Some(ref warning) => println!("  {} ({}/4): {}", finding.entry.label, finding.score, warning),
+                None => println!("  {} ({}/4)", finding.entry.label, finding.score),
+            }
+        }
+    }
+    if !report.reused.is_empty() {
+        println!("\nReused passwords:");
+        for group in &report.reused {
+            let labels: Vec<&str> = group.iter().map(|entry| entry.label.as_str()).collect();
+            println!("  {}", labels.join(", "));
+        }
+    }
+    if !report.old.is_empty() {
+        println!("\nOld passwords:");
+        for finding in &report.old {
+            println!("  {} (unchanged for {} days)", finding.entry.label, finding.age_days);
+        }
+    }
+    if !report.empty_usernames.is_empty() {
+        println!("\nNo username:");
+        for entry in &report.empty_usernames {
+            println!("  {}", entry.label);
+        }
+    }
+    if !report.near_duplicates.is_empty() {
+        println!("\nPossible duplicates:");
+        for [a, b] in &report.near_duplicates {
+            println!("  {} and {}", a.label, b.label);
+        }
+    }
+}
+
+// Entry passwords are not enforced, only flagged; goes to stderr so
+// subcommand output stays clean.
+pub fn warn_if_weak(password: &str, user_inputs: &[&str]) {
+    let strength = strength::estimate(password, user_inputs);
+    if strength.score < 3 {
+        let reason = strength.warning.unwrap_or_else(|| "easy to guess".to_string());
+        eprintln!("Warning: weak password ({}/4): {}", strength.score, reason);
+    }
+}
+
+fn local_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+}
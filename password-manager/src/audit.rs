@@ -0,0 +1,311 @@
+//! Vault health checks: weak, reused and old passwords, entries without a
+//! username and entries that look like duplicates of each other.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::entry::PasswordEntry;
+use crate::strength;
+
+/// Thresholds for [`PasswordManager::audit`](crate::PasswordManager::audit).
+#[derive(Clone, Copy, Debug)]
+pub struct AuditOptions {
+    /// Passwords unchanged for longer than this are reported as old.
+    pub max_age_days: u32,
+    /// Passwords scoring below this are reported as weak.
+    pub min_score: u8,
+}
+
+impl Default for AuditOptions {
+    fn default() -> AuditOptions {
+        AuditOptions {
+            max_age_days: 365,
+            min_score: 3,
+        }
+    }
+}
+
+/// Findings of an audit, serializable for dashboards.
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditReport {
+    /// 0 (every entry has problems) to 100 (no findings).
+    pub score: u8,
+    pub entries: usize,
+    pub weak: Vec<WeakFinding>,
+    /// Groups of entries sharing one password.
+    pub reused: Vec<Vec<EntryRef>>,
+    pub old: Vec<OldFinding>,
+    pub empty_usernames: Vec<EntryRef>,
+    /// Pairs of entries for what looks like the same account.
+    pub near_duplicates: Vec<[EntryRef; 2]>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct EntryRef {
+    pub id: Uuid,
+    pub label: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WeakFinding {
+    pub entry: EntryRef,
+    pub score: u8,
+    pub warning: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OldFinding {
+    pub entry: EntryRef,
+    pub changed: DateTime<Utc>,
+    pub age_days: i64,
+}
+
+impl AuditReport {
+    /// True when nothing was found.
+    pub fn is_clean(&self) -> bool {
+        self.weak.is_empty()
+            && self.reused.is_empty()
+            && self.old.is_empty()
+            && self.empty_usernames.is_empty()
+            && self.near_duplicates.is_empty()
+    }
+}
+
+// How much each kind of finding takes off an entry's health, out of 1.
+const WEAK_PENALTY: f64 = 0.4;
+const REUSED_PENALTY: f64 = 0.3;
+const OLD_PENALTY: f64 = 0.15;
+const DUPLICATE_PENALTY: f64 = 0.1;
+const EMPTY_USERNAME_PENALTY: f64 = 0.05;
+
+pub(crate) fn audit(entries: &[&PasswordEntry], options: &AuditOptions, now: DateTime<Utc>) -> AuditReport {
+    let reference = |entry: &PasswordEntry| EntryRef {
+        id: entry.id,
+        label: entry.label(),
+    };
+    let mut penalties: HashMap<Uuid, f64> = HashMap::new();
+    let mut penalize = |id: Uuid, penalty: f64| *penalties.entry(id).or_default() += penalty;
+
+    let mut weak = Vec::new();
+    for entry in entries {
+        let strength = strength::estimate(&entry.password, &[&entry.service, &entry.username]);
+        if strength.score < options.min_score {
+            penalize(entry.id, WEAK_PENALTY);
+            weak.push(WeakFinding {
+                entry: reference(entry),
+                score: strength.score,
+                warning: strength.warning,
+            });
+        }
+    }
+
+    let mut by_password: HashMap<&str, Vec<&PasswordEntry>> = HashMap::new();
+    for entry in entries.iter().filter(|entry| !entry.password.is_empty()) {
+        by_password.entry(&entry.password).or_default().push(entry);
+    }
+    let mut reused: Vec<Vec<EntryRef>> = by_password
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|group| {
+            group.iter().for_each(|entry| penalize(entry.id, REUSED_PENALTY));
+            group.iter().map(|entry| reference(entry)).collect()
+        })
+        .collect();
+    reused.sort_by(|a: &Vec<EntryRef>, b| a[0].label.cmp(&b[0].label));
+
+    let mut old = Vec::new();
+    for entry in entries {
+        let changed = entry.password_set_at();
+        let age_days = (now - changed).num_days();
+        if age_days > i64::from(options.max_age_days) {
+            penalize(entry.id, OLD_PENALTY);
+            old.push(OldFinding {
+                entry: reference(entry),
+                changed,
+                age_days,
+            });
+        }
+    }
+
+    let mut empty_usernames = Vec::new();
+    for entry in entries.iter().filter(|entry| entry.username.trim().is_empty()) {
+        penalize(entry.id, EMPTY_USERNAME_PENALTY);
+        empty_usernames.push(reference(entry));
+    }
+
+    let mut near_duplicates = Vec::new();
+    for (i, a) in entries.iter().enumerate() {
+        for b in &entries[i + 1..] {
+            let same_service = normalize_service(&a.service) == normalize_service(&b.service);
+            if same_service && a.username.trim().eq_ignore_ascii_case(b.username.trim()) {
+                penalize(a.id, DUPLICATE_PENALTY);
+                penalize(b.id, DUPLICATE_PENALTY);
+                near_duplicates.push([reference(a), reference(b)]);
+            }
+        }
+    }
+
+    let health: f64 = entries
+        .iter()
+        .map(|entry| (1.0 - penalties.get(&entry.id).copied().unwrap_or(0.0)).max(0.0))
+        .sum();
+    let score = if entries.is_empty() {
+        100
+    } else {
+        (100.0 * health / entries.len() as f64).round() as u8
+    };
+
+    AuditReport {
+        score,
+        entries: entries.len(),
+        weak,
+        reused,
+        old,
+        empty_usernames,
+        near_duplicates,
+    }
+}
+
+// "https://www.GitHub.com/" and "github" name the same service.
+fn normalize_service(service: &str) -> String {
+    let service = service.trim().to_lowercase();
+    let service = service.split("://").last().unwrap_or(&service);
+    let host = service.split('/').next().unwrap_or(service);
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let name = match host.rsplit_once('.') {
+        Some((name, tld)) if !name.is_empty() && tld.chars().all(|c| c.is_ascii_alphabetic()) => name,
+        _ => host,
+    };
+    name.chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::entry::PreviousPassword;
+
+    const STRONG: &str = "vR7#qLm2!xT9zK4p";
+
+    fn entry(service: &str, username: &str, password: &str) -> PasswordEntry {
+        PasswordEntry::new(service.to_string(), username.to_string(), password.to_string())
+    }
+
+    fn run(entries: &[PasswordEntry]) -> AuditReport {
+        audit(&entries.iter().collect::<Vec<_>>(), &AuditOptions::default(), Utc::now())
+    }
+
+    fn labels(refs: &[EntryRef]) -> Vec<&str> {
+        refs.iter().map(|entry| entry.label.as_str()).collect()
+    }
+
+    #[test]
+    fn finds_weak_passwords() {
+        let report = run(&[entry("github", "octocat", "password1"), entry("mail", "bob", STRONG)]);
+        assert_eq!(report.weak.len(), 1);
+        assert_eq!(report.weak[0].entry.label, "octocat@github");
+        assert!(report.weak[0].score < AuditOptions::default().min_score);
+    }
+
+    #[test]
+    fn groups_reused_passwords() {
+        let report = run(&[
+            entry("github", "octocat", STRONG),
+            entry("mail", "bob", "9xQ!w2Lp#Zr7vTk3"),
+            entry("bank", "me", STRONG),
+            entry("shop", "me", ""),
+            entry("forum", "me", ""),
+        ]);
+        // Blank passwords are not counted as reused.
+        assert_eq!(report.reused.len(), 1);
+        let mut group = labels(&report.reused[0]);
+        group.sort();
+        assert_eq!(group, ["me@bank", "octocat@github"]);
+    }
+
+    #[test]
+    fn finds_old_passwords() {
+        let now = Utc::now();
+        let mut never_changed = entry("github", "octocat", STRONG);
+        never_changed.created = now - Duration::days(400);
+        let mut changed_since = entry("mail", "bob", "9xQ!w2Lp#Zr7vTk3");
+        changed_since.created = now - Duration::days(400);
+        changed_since.history.push(PreviousPassword {
+            password: "old".to_string(),
+            replaced: now - Duration::days(30),
+        });
+        // Changed without keeping history: only the recorded time tells.
+        let mut no_history = entry("bank", "me", "Tz8$kW3m!Qp6rN2v");
+        no_history.created = now - Duration::days(400);
+        no_history.password_changed = Some(now - Duration::days(30));
+        // The recorded time wins over an older history item.
+        let mut restored = entry("shop", "me", "Hb5@nY9e!Lc2wS7u");
+        restored.created = now - Duration::days(800);
+        restored.history.push(PreviousPassword {
+            password: "older".to_string(),
+            replaced: now - Duration::days(500),
+        });
+        restored.password_changed = Some(now - Duration::days(2));
+
+        let report = run(&[never_changed, changed_since, no_history, restored]);
+        assert_eq!(report.old.len(), 1);
+        assert_eq!(report.old[0].entry.label, "octocat@github");
+        assert_eq!(report.old[0].age_days, 400);
+
+        let strict = AuditOptions {
+            max_age_days: 10,
+            ..AuditOptions::default()
+        };
+        let mut entries = [entry("github", "octocat", STRONG)];
+        entries[0].password_changed = Some(now - Duration::days(11));
+        let report = audit(&entries.iter().collect::<Vec<_>>(), &strict, now);
+        assert_eq!(report.old.len(), 1);
+    }
+
+    #[test]
+    fn finds_empty_usernames_and_near_duplicates() {
+        let report = run(&[
+            entry("https://www.GitHub.com/login", "OctoCat", STRONG),
+            entry("github", " octocat ", "9xQ!w2Lp#Zr7vTk3"),
+            entry("gitlab", "octocat", "Tz8$kW3m!Qp6rN2v"),
+            entry("wifi", "  ", "Hb5@nY9e!Lc2wS7u"),
+        ]);
+        assert_eq!(labels(&report.empty_usernames), ["  @wifi"]);
+        assert_eq!(report.near_duplicates.len(), 1);
+        assert_eq!(labels(&report.near_duplicates[0]), ["OctoCat@https://www.GitHub.com/login", " octocat @github"]);
+    }
+
+    #[test]
+    fn scores_the_share_of_healthy_entries() {
+        let report = run(&[]);
+        assert_eq!(report.score, 100);
+        assert!(report.is_clean());
+
+        let clean = [entry("github", "octocat", STRONG), entry("mail", "bob", "9xQ!w2Lp#Zr7vTk3")];
+        let report = run(&clean);
+        assert_eq!((report.score, report.entries), (100, 2));
+        assert!(report.is_clean());
+
+        // One weak entry of four loses 40% of its health.
+        let report = run(&[
+            entry("github", "octocat", STRONG),
+            entry("mail", "bob", "9xQ!w2Lp#Zr7vTk3"),
+            entry("bank", "me", "Tz8$kW3m!Qp6rN2v"),
+            entry("shop", "me", "password1"),
+        ]);
+        assert_eq!(report.score, 90);
+        assert!(!report.is_clean());
+
+        // An entry's penalties add up.
+        let mut old = entry("shop", "", "password1");
+        old.created = Utc::now() - Duration::days(400);
+        let report = run(&[old, entry("forum", "", "password1")]);
+        // Weak + reused + old + empty username, and weak + reused + empty username.
+        let expected = 100.0 * ((1.0f64 - 0.4 - 0.3 - 0.15 - 0.05) + (1.0 - 0.4 - 0.3 - 0.05)) / 2.0;
+        assert_eq!(report.score, expected.round() as u8);
+    }
+}
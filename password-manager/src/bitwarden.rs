@@ -0,0 +1,629 @@
+//! Bitwarden's JSON export, plain or protected with a file password.
+//!
+//! Unlike Bitwarden's CSV export this keeps custom field types, password
+//! history, cards, identities and SSH keys. Items that are not logins are
+//! imported with a tag naming their type (`card`, `identity`, `secure note`
+//! or `ssh key`) and their details as custom fields.
+//!
+//! "Account restricted" encrypted exports need the account's own keys and
+//! cannot be read; they are rejected with an explanation.
+
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use crypto::aes::{self, KeySize};
+use crypto::blockmodes::PkcsPadding;
+use crypto::buffer::{BufferResult, ReadBuffer, RefReadBuffer, RefWriteBuffer, WriteBuffer};
+use crypto::digest::Digest;
+use crypto::hmac::Hmac;
+use crypto::mac::{Mac, MacResult};
+use crypto::pbkdf2::pbkdf2;
+use crypto::sha2::Sha256;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::entry::{CustomField, FieldKind, PasswordEntry, PreviousPassword};
+use crate::error::{Result, VaultError};
+use crate::import::{self, ImportedEntries, SkippedRecord, UnmappedField};
+use crate::kdf::{self, MAX_ITERATIONS};
+
+const KDF_PBKDF2: u32 = 0;
+const KDF_ARGON2ID: u32 = 1;
+
+const LOGIN: u32 = 1;
+const SECURE_NOTE: u32 = 2;
+const CARD: u32 = 3;
+const IDENTITY: u32 = 4;
+const SSH_KEY: u32 = 5;
+
+const FIELD_TEXT: u32 = 0;
+const FIELD_HIDDEN: u32 = 1;
+const FIELD_BOOLEAN: u32 = 2;
+
+// Identity properties in the order Bitwarden shows them, with the name
+// and kind of the custom field each becomes.
+const IDENTITY_FIELDS: [(&str, &str, FieldKind); 17] = [
+    ("title", "Title", FieldKind::Text),
+    ("firstName", "First name", FieldKind::Text),
+    ("middleName", "Middle name", FieldKind::Text),
+    ("lastName", "Last name", FieldKind::Text),
+    ("company", "Company", FieldKind::Text),
+    ("email", "Email", FieldKind::Email),
+    ("phone", "Phone", FieldKind::Text),
+    ("address1", "Address", FieldKind::Text),
+    ("address2", "Address 2", FieldKind::Text),
+    ("address3", "Address 3", FieldKind::Text),
+    ("city", "City", FieldKind::Text),
+    ("state", "State", FieldKind::Text),
+    ("postalCode", "Postal code", FieldKind::Text),
+    ("country", "Country", FieldKind::Text),
+    ("ssn", "Social security number", FieldKind::Hidden),
+    ("passportNumber", "Passport number", FieldKind::Hidden),
+    ("licenseNumber", "License number", FieldKind::Hidden),
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    password_protected: bool,
+    salt: Option<String>,
+    kdf_type: Option<u32>,
+    kdf_iterations: Option<u32>,
+    kdf_memory: Option<u32>,
+    kdf_parallelism: Option<u32>,
+    #[serde(rename = "encKeyValidation_DO_NOT_EDIT")]
+    key_validation: Option<String>,
+    data: Option<String>,
+    #[serde(default)]
+    folders: Vec<Folder>,
+    // Organization exports group items in collections instead.
+    #[serde(default)]
+    collections: Vec<Folder>,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct Folder {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    id: Option<Uuid>,
+    folder_id: Option<String>,
+    collection_ids: Option<Vec<String>>,
+    #[serde(rename = "type")]
+    kind: u32,
+    #[serde(default)]
+    reprompt: u32,
+    name: Option<String>,
+    notes: Option<String>,
+    #[serde(default)]
+    favorite: bool,
+    fields: Option<Vec<Field>>,
+    login: Option<Login>,
+    card: Option<Card>,
+    identity: Option<HashMap<String, Option<String>>>,
+    ssh_key: Option<SshKey>,
+    password_history: Option<Vec<OldPassword>>,
+    creation_date: Option<String>,
+    revision_date: Option<String>,
+    deleted_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Field {
+    name: Option<String>,
+    value: Option<String>,
+    #[serde(rename = "type")]
+    kind: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Login {
+    uris: Option<Vec<LoginUri>>,
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+    fido2_credentials: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginUri {
+    uri: Option<String>,
+    #[serde(rename = "match")]
+    match_rule: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Card {
+    cardholder_name: Option<String>,
+    brand: Option<String>,
+    number: Option<String>,
+    exp_month: Option<String>,
+    exp_year: Option<String>,
+    code: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SshKey {
+    private_key: Option<String>,
+    public_key: Option<String>,
+    key_fingerprint: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OldPassword {
+    password: Option<String>,
+    last_used_date: Option<String>,
+}
+
+fn failed(reason: &str) -> VaultError {
+    VaultError::ImportFailed(reason.to_string())
+}
+
+fn parse(data: &[u8]) -> Result<Export> {
+    serde_json::from_slice(data).map_err(|e| failed(&format!("not a Bitwarden JSON export: {}", e)))
+}
+
+/// Whether `data` is a Bitwarden JSON export protected with a file password,
+/// which [`read_bitwarden_json`] then needs.
+pub fn is_password_protected(data: &[u8]) -> bool {
+    parse(data).is_ok_and(|export| export.encrypted && export.password_protected)
+}
+
+/// Reads a Bitwarden JSON export. `password` is only used for exports
+/// protected with a file password; a wrong one is reported as
+/// [`VaultError::WrongPassword`].
+pub fn read_bitwarden_json(data: &[u8], password: Option<&str>) -> Result<ImportedEntries> {
+    let mut export = parse(data)?;
+    if export.encrypted {
+        if !export.password_protected {
+            return Err(failed(
+                "account restricted exports can only be read by Bitwarden; export again with a file password",
+            ));
+        }
+        let password = password.ok_or_else(|| failed("the export is password-protected"))?;
+        let decrypted = decrypt_export(&export, password)?;
+        export = parse(&decrypted)?;
+        if export.encrypted {
+            return Err(failed("the decrypted export is itself encrypted"));
+        }
+    }
+
+    let folders: HashMap<&str, &str> =
+        export.folders.iter().chain(&export.collections).map(|folder| (folder.id.as_str(), folder.name.as_str())).collect();
+    let mut imported = ImportedEntries {
+        folders: export.folders.iter().map(|folder| folder.name.clone()).collect(),
+        ..ImportedEntries::default()
+    };
+    for item in &export.items {
+        let title = item.name.clone().unwrap_or_default();
+        match to_entry(item, &folders, &mut imported.unmapped) {
+            Ok(entry) => imported.entries.push(entry),
+            Err(reason) => imported.skipped.push(SkippedRecord::new(format!("item '{}'", title), reason)),
+        }
+    }
+    Ok(imported)
+}
+
+fn text(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("")
+}
+
+fn to_entry(item: &Item, folders: &HashMap<&str, &str>, unmapped: &mut Vec<UnmappedField>) -> std::result::Result<PasswordEntry, String> {
+    if item.deleted_date.is_some() {
+        return Err("in the trash".to_string());
+    }
+    let type_tag = match item.kind {
+        LOGIN => None,
+        SECURE_NOTE => Some("secure note"),
+        CARD => Some("card"),
+        IDENTITY => Some("identity"),
+        SSH_KEY => Some("ssh key"),
+        kind => return Err(format!("unknown item type {}", kind)),
+    };
+    let title = text(&item.name).trim();
+    let mut unmapped_field = |field: String| unmapped.push(UnmappedField::new(title, field));
+
+    let login = item.login.as_ref();
+    let mut urls = Vec::new();
+    for uri in login.and_then(|login| login.uris.as_ref()).into_iter().flatten() {
+        let url = text(&uri.uri).trim();
+        if url.is_empty() {
+            continue;
+        }
+        if let Some(rule) = uri_match_rule(uri.match_rule) {
+            unmapped_field(format!("URI match rule \"{}\" for {}", rule, url));
+        }
+        urls.push(url.to_string());
+    }
+    let service = match title {
+        "" => urls.first().map(|url| import::host_of(url)).unwrap_or_default(),
+        title => title.to_string(),
+    };
+    if service.is_empty() {
+        return Err("no name or URL".to_string());
+    }
+
+    let username = login.map_or("", |login| text(&login.username)).to_string();
+    let password = login.map_or("", |login| text(&login.password)).to_string();
+    let mut entry = PasswordEntry::new(service, username, password);
+    if let Some(id) = item.id {
+        entry.id = id;
+    }
+    entry.urls = urls;
+    entry.notes = text(&item.notes).to_string();
+    let collection = item.collection_ids.iter().flatten().find_map(|id| folders.get(id.as_str()));
+    entry.folder = item.folder_id.as_deref().and_then(|id| folders.get(id)).or(collection).map_or(String::new(), |name| name.to_string());
+    entry.tags.extend(type_tag.map(String::from));
+    if item.favorite {
+        entry.tags.push("favorite".to_string());
+    }
+
+    for field in item.fields.iter().flatten() {
+        let name = text(&field.name).to_string();
+        let kind = match field.kind {
+            FIELD_TEXT | FIELD_BOOLEAN => FieldKind::Text,
+            FIELD_HIDDEN => FieldKind::Hidden,
+            // Linked fields only point at another property of the item.
+            _ => {
+                unmapped_field(format!("linked field \"{}\"", name));
+                continue;
+            }
+        };
+        entry.fields.push(CustomField {
+            name,
+            kind,
+            value: text(&field.value).to_string(),
+        });
+    }
+    if let Some(login) = login {
+        import::set_otp(&mut entry, text(&login.totp));
+        if login.fido2_credentials.as_ref().is_some_and(|passkeys| !passkeys.is_empty()) {
+            unmapped_field("passkey".to_string());
+        }
+    }
+    if let Some(card) = &item.card {
+        let expiry = match (text(&card.exp_month), text(&card.exp_year)) {
+            ("", year) => year.to_string(),
+            (month, "") => month.to_string(),
+            (month, year) => format!("{:0>2}/{}", month, year),
+        };
+        push_fields(&mut entry, [
+            ("Cardholder name", FieldKind::Text, text(&card.cardholder_name)),
+            ("Brand", FieldKind::Text, text(&card.brand)),
+            ("Number", FieldKind::Hidden, text(&card.number)),
+            ("Expiry", FieldKind::Text, &expiry),
+            ("Security code", FieldKind::Hidden, text(&card.code)),
+        ]);
+    }
+    if let Some(identity) = &item.identity {
+        if entry.username.is_empty() {
+            entry.username = identity.get("username").and_then(Option::as_deref).unwrap_or("").to_string();
+        }
+        push_fields(
+            &mut entry,
+            IDENTITY_FIELDS.map(|(key, name, kind)| (name, kind, identity.get(key).and_then(Option::as_deref).unwrap_or(""))),
+        );
+    }
+    if let Some(key) = &item.ssh_key {
+        push_fields(&mut entry, [
+            ("Private key", FieldKind::Hidden, text(&key.private_key)),
+            ("Public key", FieldKind::Text, text(&key.public_key)),
+            ("Fingerprint", FieldKind::Text, text(&key.key_fingerprint)),
+        ]);
+    }
+    if item.reprompt != 0 {
+        unmapped_field("master password re-prompt".to_string());
+    }
+
+    if let Some(created) = item.creation_date.as_deref().and_then(import::parse_time) {
+        entry.created = created;
+        entry.modified = created;
+    }
+    if let Some(modified) = item.revision_date.as_deref().and_then(import::parse_time) {
+        entry.modified = modified;
+    }
+    entry.history = item
+        .password_history
+        .iter()
+        .flatten()
+        .filter_map(|old| {
+            Some(PreviousPassword {
+                password: old.password.clone()?,
+                replaced: import::parse_time(old.last_used_date.as_deref()?)?,
+            })
+        })
+        .collect();
+    entry.history.sort_by_key(|old| std::cmp::Reverse(old.replaced));
+    Ok(entry)
+}
+
+fn uri_match_rule(rule: Option<u32>) -> Option<&'static str> {
+    match rule? {
+        0 => Some("base domain"),
+        1 => Some("host"),
+        2 => Some("starts with"),
+        3 => Some("exact"),
+        4 => Some("regular expression"),
+        5 => Some("never"),
+        _ => Some("unknown"),
+    }
+}
+
+// Adds the non-blank ones.
+fn push_fields<'a>(entry: &mut PasswordEntry, fields: impl IntoIterator<Item = (&'a str, FieldKind, &'a str)>) {
+    for (name, kind, value) in fields {
+        if !value.trim().is_empty() {
+            entry.fields.push(CustomField {
+                name: name.to_string(),
+                kind,
+                value: value.to_string(),
+            });
+        }
+    }
+}
+
+fn missing(name: &str) -> VaultError {
+    failed(&format!("protected export has no {}", name))
+}
+
+fn decrypt_export(export: &Export, password: &str) -> Result<Vec<u8>> {
+    let keys = derive_keys(export, password)?;
+    let validation = export.key_validation.as_deref().ok_or_else(|| missing("key validation"))?;
+    keys.decrypt(validation)?;
+    keys.decrypt(export.data.as_deref().ok_or_else(|| missing("data"))?)
+}
+
+// The file password is stretched like a Bitwarden master password, with
+// the export's salt, then split into an encryption and a MAC key.
+fn derive_keys(export: &Export, password: &str) -> Result<Keys> {
+    let salt = export.salt.as_deref().ok_or_else(|| missing("salt"))?;
+    let iterations = export.kdf_iterations.ok_or_else(|| missing("KDF iterations"))?;
+    // The settings come from the file, so they are held to the same limits
+    // as the vault's own.
+    let out_of_range = || failed("the export's key derivation settings are out of range");
+    let mut key = [0u8; 32];
+    match export.kdf_type.unwrap_or(KDF_PBKDF2) {
+        KDF_PBKDF2 => {
+            if !(1..=MAX_ITERATIONS).contains(&iterations) {
+                return Err(out_of_range());
+            }
+            let mut mac = Hmac::new(Sha256::new(), password.as_bytes());
+            pbkdf2(&mut mac, salt.as_bytes(), iterations, &mut key);
+        }
+        KDF_ARGON2ID => {
+            let memory_mib = export.kdf_memory.ok_or_else(|| missing("KDF memory"))?;
+            let parallelism = export.kdf_parallelism.ok_or_else(|| missing("KDF parallelism"))?;
+            let mut salt_hash = [0u8; 32];
+            let mut sha = Sha256::new();
+            sha.input(salt.as_bytes());
+            sha.result(&mut salt_hash);
+            let memory_kib = memory_mib.checked_mul(1024).ok_or_else(out_of_range)?;
+            if !kdf::argon2_is_usable(memory_kib, iterations, parallelism) {
+                return Err(out_of_range());
+            }
+            let params = argon2::Params::new(memory_kib, iterations, parallelism, Some(32))
+                .map_err(|e| failed(&format!("invalid Argon2 parameters: {}", e)))?;
+            argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                .hash_password_into(password.as_bytes(), &salt_hash, &mut key)
+                .map_err(|e| failed(&format!("key derivation failed: {}", e)))?;
+        }
+        kind => return Err(failed(&format!("unsupported key derivation type {}", kind))),
+    }
+    Ok(Keys {
+        encryption: hkdf_expand(&key, b"enc"),
+        mac: hkdf_expand(&key, b"mac"),
+    })
+}
+
+// HKDF-Expand (RFC 5869) for a single 32-byte block of SHA-256 output.
+fn hkdf_expand(key: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::new(Sha256::new(), key);
+    mac.input(info);
+    mac.input(&[1]);
+    let mut out = [0u8; 32];
+    mac.raw_result(&mut out);
+    out
+}
+
+struct Keys {
+    encryption: [u8; 32],
+    mac: [u8; 32],
+}
+
+impl Keys {
+    // Bitwarden's "2.iv|data|mac" strings: AES-256-CBC with an HMAC-SHA256
+    // over the IV and ciphertext. A MAC that does not match means the
+    // password is wrong.
+    fn decrypt(&self, encrypted: &str) -> Result<Vec<u8>> {
+        let invalid = || failed("invalid encrypted string in the export");
+        let (kind, rest) = encrypted.split_once('.').ok_or_else(invalid)?;
+        if kind != "2" {
+            return Err(failed(&format!("unsupported encryption type {}", kind)));
+        }
+        let parts: Vec<Vec<u8>> =
+            rest.split('|').map(|part| BASE64.decode(part)).collect::<std::result::Result<_, _>>().map_err(|_| invalid())?;
+        let [iv, data, mac] = <[Vec<u8>; 3]>::try_from(parts).map_err(|_| invalid())?;
+        if iv.len() != 16 {
+            return Err(invalid());
+        }
+
+        let mut hmac = Hmac::new(Sha256::new(), &self.mac);
+        hmac.input(&iv);
+        hmac.input(&data);
+        if hmac.result() != MacResult::new(&mac) {
+            return Err(VaultError::WrongPassword);
+        }
+
+        let mut decryptor = aes::cbc_decryptor(KeySize::KeySize256, &self.encryption, &iv, PkcsPadding);
+        let mut out = Vec::with_capacity(data.len());
+        let mut input = RefReadBuffer::new(&data);
+        let mut buffer = [0u8; 4096];
+        loop {
+            let mut output = RefWriteBuffer::new(&mut buffer);
+            let result = decryptor.decrypt(&mut input, &mut output, true).map_err(|_| invalid())?;
+            out.extend_from_slice(output.take_read_buffer().take_remaining());
+            if let BufferResult::BufferUnderflow = result {
+                return Ok(out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ITEMS: &str = r#"{
+        "encrypted": false,
+        "folders": [{"id": "f1", "name": "Work"}],
+        "items": [
+            {
+                "id": "8c6a9e43-4f3b-4c36-9d0b-3a7d5bbf4a10",
+                "folderId": "f1",
+                "type": 1,
+                "name": "GitHub",
+                "notes": "recovery codes in the safe",
+                "favorite": true,
+                "fields": [
+                    {"name": "PIN", "value": "1234", "type": 1},
+                    {"name": "Linked", "value": null, "type": 3}
+                ],
+                "login": {
+                    "uris": [{"uri": "https://github.com/login", "match": null}],
+                    "username": "octocat",
+                    "password": "hunter2",
+                    "totp": "JBSWY3DPEHPK3PXP"
+                },
+                "passwordHistory": [{"password": "hunter1", "lastUsedDate": "2024-01-02T03:04:05.000Z"}],
+                "revisionDate": "2024-02-03T04:05:06.000Z"
+            },
+            {"type": 3, "name": "Visa", "card": {"number": "4111111111111111", "expMonth": "7", "expYear": "2030"}},
+            {"type": 1, "name": "Old", "deletedDate": "2024-01-01T00:00:00.000Z", "login": {}}
+        ]
+    }"#;
+
+    fn encrypt(keys: &Keys, plain: &[u8]) -> String {
+        let iv = [7u8; 16];
+        let mut encryptor = aes::cbc_encryptor(KeySize::KeySize256, &keys.encryption, &iv, PkcsPadding);
+        let mut data = Vec::new();
+        let mut input = RefReadBuffer::new(plain);
+        let mut buffer = [0u8; 4096];
+        loop {
+            let mut output = RefWriteBuffer::new(&mut buffer);
+            let result = encryptor.encrypt(&mut input, &mut output, true).unwrap();
+            data.extend_from_slice(output.take_read_buffer().take_remaining());
+            if let BufferResult::BufferUnderflow = result {
+                break;
+            }
+        }
+        let mut hmac = Hmac::new(Sha256::new(), &keys.mac);
+        hmac.input(&iv);
+        hmac.input(&data);
+        format!("2.{}|{}|{}", BASE64.encode(iv), BASE64.encode(&data), BASE64.encode(hmac.result().code()))
+    }
+
+    // A password-protected export of `ITEMS` as Bitwarden writes it.
+    fn protect(password: &str, kdf: serde_json::Value) -> Vec<u8> {
+        let mut export = json!({
+            "encrypted": true,
+            "passwordProtected": true,
+            "salt": "c2FsdHNhbHRzYWx0",
+        });
+        export.as_object_mut().unwrap().extend(kdf.as_object().unwrap().clone());
+        let keys = derive_keys(&serde_json::from_value(export.clone()).unwrap(), password).unwrap();
+        export["encKeyValidation_DO_NOT_EDIT"] = json!(encrypt(&keys, Uuid::new_v4().to_string().as_bytes()));
+        export["data"] = json!(encrypt(&keys, ITEMS.as_bytes()));
+        serde_json::to_vec(&export).unwrap()
+    }
+
+    #[test]
+    fn reads_plain_exports() {
+        assert!(!is_password_protected(ITEMS.as_bytes()));
+        let imported = read_bitwarden_json(ITEMS.as_bytes(), None).unwrap();
+        assert_eq!(imported.folders, ["Work"]);
+        assert_eq!(imported.entries.len(), 2);
+        assert_eq!(imported.skipped.len(), 1);
+
+        let github = &imported.entries[0];
+        assert_eq!(github.id.to_string(), "8c6a9e43-4f3b-4c36-9d0b-3a7d5bbf4a10");
+        assert_eq!((github.service.as_str(), github.username.as_str(), github.password.as_str()), ("GitHub", "octocat", "hunter2"));
+        assert_eq!(github.urls, ["https://github.com/login"]);
+        assert_eq!(github.folder, "Work");
+        assert_eq!(github.tags, ["favorite"]);
+        assert!(github.otp.is_some());
+        assert_eq!(github.fields.len(), 1);
+        assert_eq!((github.fields[0].name.as_str(), github.fields[0].kind), ("PIN", FieldKind::Hidden));
+        assert_eq!(github.history.len(), 1);
+        assert_eq!(github.history[0].password, "hunter1");
+        assert_eq!(imported.unmapped.len(), 1);
+
+        let card = &imported.entries[1];
+        assert_eq!(card.tags, ["card"]);
+        assert!(card.fields.iter().any(|field| field.name == "Expiry" && field.value == "07/2030"));
+    }
+
+    #[test]
+    fn reads_pbkdf2_protected_exports() {
+        let data = protect("file password", json!({"kdfType": KDF_PBKDF2, "kdfIterations": 1000}));
+        assert!(is_password_protected(&data));
+        let imported = read_bitwarden_json(&data, Some("file password")).unwrap();
+        assert_eq!(imported.entries[0].password, "hunter2");
+        assert!(matches!(read_bitwarden_json(&data, Some("wrong")), Err(VaultError::WrongPassword)));
+        assert!(matches!(read_bitwarden_json(&data, None), Err(VaultError::ImportFailed(_))));
+    }
+
+    #[test]
+    fn reads_argon2id_protected_exports() {
+        let kdf = json!({"kdfType": KDF_ARGON2ID, "kdfIterations": 1, "kdfMemory": 1, "kdfParallelism": 1});
+        let data = protect("file password", kdf);
+        let imported = read_bitwarden_json(&data, Some("file password")).unwrap();
+        assert_eq!(imported.entries[0].password, "hunter2");
+        assert!(matches!(read_bitwarden_json(&data, Some("wrong")), Err(VaultError::WrongPassword)));
+    }
+
+    #[test]
+    fn rejects_out_of_range_key_derivation_settings() {
+        for kdf in [
+            json!({"kdfType": KDF_PBKDF2, "kdfIterations": 0}),
+            json!({"kdfType": KDF_PBKDF2, "kdfIterations": MAX_ITERATIONS + 1}),
+            json!({"kdfType": KDF_ARGON2ID, "kdfIterations": 1, "kdfMemory": 64 * 1024, "kdfParallelism": 1}),
+            json!({"kdfType": KDF_ARGON2ID, "kdfIterations": 1, "kdfMemory": u32::MAX, "kdfParallelism": 1}),
+            json!({"kdfType": KDF_ARGON2ID, "kdfIterations": 0, "kdfMemory": 1, "kdfParallelism": 1}),
+        ] {
+            let mut export = json!({
+                "encrypted": true,
+                "passwordProtected": true,
+                "salt": "c2FsdHNhbHRzYWx0",
+                "encKeyValidation_DO_NOT_EDIT": "2.AAAAAAAAAAAAAAAAAAAAAA==|AAAA|AAAA",
+                "data": "2.AAAAAAAAAAAAAAAAAAAAAA==|AAAA|AAAA",
+            });
+            export.as_object_mut().unwrap().extend(kdf.as_object().unwrap().clone());
+            let data = serde_json::to_vec(&export).unwrap();
+            assert!(matches!(read_bitwarden_json(&data, Some("password")), Err(VaultError::ImportFailed(_))), "{}", kdf);
+        }
+    }
+
+    #[test]
+    fn rejects_account_restricted_exports() {
+        let data = br#"{"encrypted": true, "passwordProtected": false, "encKeyValidation_DO_NOT_EDIT": "2.x|y|z", "data": "2.x|y|z"}"#;
+        assert!(!is_password_protected(data));
+        assert!(matches!(read_bitwarden_json(data, Some("anything")), Err(VaultError::ImportFailed(_))));
+    }
+}
@@ -0,0 +1,252 @@
+//! Offline lookups in the Have I Been Pwned "Pwned Passwords" SHA-1 list.
+//!
+//! Passwords are hashed in memory and only the hash is compared, so
+//! nothing but the downloaded list is ever read from disk.
+
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+use serde::Serialize;
+
+use crate::audit::EntryRef;
+use crate::entry::PasswordEntry;
+use crate::error::{Result, VaultError};
+
+// Hashes are split into this many leading hex digits for range files.
+const RANGE_PREFIX_LEN: usize = 5;
+
+/// A local copy of the Pwned Passwords list, either as one file of
+/// `HASH:COUNT` lines sorted by hash, or as a directory of range files
+/// named after the first five hex digits (`21BD1.txt`) holding
+/// `SUFFIX:COUNT` lines, as produced by the official downloader.
+pub enum BreachDatabase {
+    Sorted { file: File, len: u64 },
+    Ranges(PathBuf),
+}
+
+/// An entry whose password is in the breach list.
+#[derive(Clone, Debug, Serialize)]
+pub struct BreachFinding {
+    pub entry: EntryRef,
+    /// How often the password was seen in breaches.
+    pub count: u64,
+}
+
+impl BreachDatabase {
+    pub fn open(path: &Path) -> Result<BreachDatabase> {
+        if path.is_dir() {
+            return Ok(BreachDatabase::Ranges(path.to_path_buf()));
+        }
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(BreachDatabase::Sorted { file, len })
+    }
+
+    /// How many times `password` appears in the list; 0 if it does not.
+    pub fn count(&self, password: &str) -> Result<u64> {
+        let mut sha1 = Sha1::new();
+        sha1.input_str(password);
+        let hash = sha1.result_str().to_ascii_uppercase();
+
+        match self {
+            BreachDatabase::Sorted { file, len } => search_sorted(file, *len, &hash),
+            BreachDatabase::Ranges(dir) => {
+                let (prefix, suffix) = hash.split_at(RANGE_PREFIX_LEN);
+                let path = dir.join(format!("{}.txt", prefix));
+                let range = match fs::read_to_string(&path) {
+                    Ok(range) => range,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+                    Err(e) => return Err(e.into()),
+                };
+                for (i, line) in range.lines().enumerate() {
+                    if let Some((candidate, count)) = parse_line(line, || format!("{} line {}", path.display(), i + 1))? {
+                        if candidate.eq_ignore_ascii_case(suffix) {
+                            return Ok(count);
+                        }
+                    }
+                }
+                Ok(0)
+            }
+        }
+    }
+
+    /// Checks every entry, looking each distinct password up only once.
+    pub(crate) fn check_entries(&self, entries: &[&PasswordEntry]) -> Result<Vec<BreachFinding>> {
+        let mut counts = std::collections::HashMap::new();
+        let mut findings = Vec::new();
+        for entry in entries.iter().filter(|entry| !entry.password.is_empty()) {
+            let count = match counts.get(entry.password.as_str()) {
+                Some(&count) => count,
+                None => {
+                    let count = self.count(&entry.password)?;
+                    counts.insert(entry.password.as_str(), count);
+                    count
+                }
+            };
+            if count > 0 {
+                findings.push(BreachFinding {
+                    entry: EntryRef {
+                        id: entry.id,
+                        label: entry.label(),
+                    },
+                    count,
+                });
+            }
+        }
+        Ok(findings)
+    }
+}
+
+// Binary search over byte offsets: every probe reads the first whole line
+// starting at or after the midpoint, so only a few dozen short reads are
+// needed even for the full 30+ GB list.
+fn search_sorted(file: &File, len: u64, hash: &str) -> Result<u64> {
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let Some((start, line)) = line_from(file, mid)? else {
+            hi = mid;
+            continue;
+        };
+        let Some((candidate, count)) = parse_line(line.trim_end(), || format!("line at byte {}", start))? else {
+            // A blank line (normally only at the very end) sorts last.
+            hi = mid;
+            continue;
+        };
+        match candidate.to_ascii_uppercase().as_str().cmp(hash) {
+            Ordering::Equal => return Ok(count),
+            Ordering::Less => lo = start + line.len() as u64,
+            Ordering::Greater => hi = mid,
+        }
+    }
+    Ok(0)
+}
+
+// The first line that starts at `offset` or later, with its start offset.
+fn line_from(mut file: &File, offset: u64) -> io::Result<Option<(u64, String)>> {
+    let start_search = offset.saturating_sub(1);
+    file.seek(SeekFrom::Start(start_search))?;
+    let mut reader = BufReader::with_capacity(256, file);
+    let mut start = start_search;
+    if offset > 0 {
+        // Skip the rest of the line the byte before `offset` belongs to.
+        let mut skipped = Vec::new();
+        start += reader.read_until(b'\n', &mut skipped)? as u64;
+    }
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some((start, line)))
+}
+
+// `location` describes where the line is, for the error on a malformed one.
+fn parse_line(line: &str, location: impl FnOnce() -> String) -> Result<Option<(&str, u64)>> {
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    let parsed = line.trim().split_once(':').and_then(|(hash, count)| Some((hash, count.parse().ok()?)));
+    match parsed {
+        Some(parsed) => Ok(Some(parsed)),
+        None => Err(VaultError::InvalidBreachDatabase(format!("{}: {:?}", location(), line))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("breach-test-{}-{}", name, Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn sorted_list(name: &str, contents: &str) -> BreachDatabase {
+        let path = temp_dir(name).join("pwned.txt");
+        fs::write(&path, contents).unwrap();
+        BreachDatabase::open(&path).unwrap()
+    }
+
+    fn lookup(database: &BreachDatabase, hash: &str) -> Result<u64> {
+        match database {
+            BreachDatabase::Sorted { file, len } => search_sorted(file, *len, hash),
+            BreachDatabase::Ranges(_) => unreachable!("only sorted lists are searched by hash"),
+        }
+    }
+
+    #[test]
+    fn reads_the_line_starting_at_or_after_an_offset() {
+        let path = temp_dir("line-from").join("list.txt");
+        fs::write(&path, "AAA:1\nBBBB:2\nCC:3").unwrap();
+        let file = File::open(&path).unwrap();
+        let line = |offset| line_from(&file, offset).unwrap();
+        assert_eq!(line(0), Some((0, "AAA:1\n".to_string())));
+        assert_eq!(line(3), Some((6, "BBBB:2\n".to_string())));
+        assert_eq!(line(6), Some((6, "BBBB:2\n".to_string())));
+        assert_eq!(line(7), Some((13, "CC:3".to_string())));
+        // The last line has no newline; nothing starts after it.
+        assert_eq!(line(13), Some((13, "CC:3".to_string())));
+        assert_eq!(line(14), None);
+        assert_eq!(line(17), None);
+    }
+
+    #[test]
+    fn finds_every_hash_of_a_sorted_list() {
+        let hashes: Vec<String> = (0..200u64).map(|i| format!("{:040X}", i * 7919 + 1)).collect();
+        let lines: Vec<String> = hashes.iter().enumerate().map(|(i, hash)| format!("{}:{}", hash, i + 1)).collect();
+        for (name, contents) in [
+            ("newline", lines.join("\n") + "\n"),
+            ("no-newline", lines.join("\n")),
+            ("crlf", lines.join("\r\n") + "\r\n"),
+        ] {
+            let database = sorted_list(name, &contents);
+            // The first and last lines included.
+            for (i, hash) in hashes.iter().enumerate() {
+                assert_eq!(lookup(&database, hash).unwrap(), i as u64 + 1, "{} in the {} list", hash, name);
+            }
+            assert_eq!(lookup(&database, &format!("{:040X}", 0)).unwrap(), 0);
+            assert_eq!(lookup(&database, &format!("{:040X}", 7919 * 100)).unwrap(), 0);
+            assert_eq!(lookup(&database, &"F".repeat(40)).unwrap(), 0);
+        }
+
+        let database = sorted_list("one-line", "F3BBBD66A63D4BF1747940578EC3D0103530E21D:17");
+        assert_eq!(database.count("hunter2").unwrap(), 17);
+        assert_eq!(sorted_list("empty", "").count("hunter2").unwrap(), 0);
+    }
+
+    #[test]
+    fn looks_up_range_directories() {
+        let dir = temp_dir("ranges");
+        // SHA-1 of "password" is 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8.
+        fs::write(dir.join("5BAA6.txt"), "0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n1e4c9b93f3f0682250b6cf8331b7ee68fd8:9545824\r\n").unwrap();
+        // "hunter2" hashes to F3BBB..., whose range lacks it.
+        fs::write(dir.join("F3BBB.txt"), "0000000000000000000000000000000000A:3\n").unwrap();
+        let database = BreachDatabase::open(&dir).unwrap();
+        assert_eq!(database.count("password").unwrap(), 9_545_824);
+        assert_eq!(database.count("hunter2").unwrap(), 0);
+        // No range file at all.
+        assert_eq!(database.count("correct horse battery staple").unwrap(), 0);
+    }
+
+    #[test]
+    fn reports_malformed_lines() {
+        let database = sorted_list("malformed", "F3BBBD66A63D4BF1747940578EC3D0103530E21D\n");
+        match database.count("hunter2") {
+            Err(VaultError::InvalidBreachDatabase(reason)) => assert!(reason.contains("byte 0"), "{}", reason),
+            other => panic!("expected a malformed list error, got {:?}", other),
+        }
+
+        let dir = temp_dir("malformed-range");
+        fs::write(dir.join("5BAA6.txt"), "0018A45C4D1DEF81644B54AB7F969B88D65:1\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:many\n").unwrap();
+        match BreachDatabase::open(&dir).unwrap().count("password") {
+            Err(VaultError::InvalidBreachDatabase(reason)) => assert!(reason.contains("5BAA6.txt line 2"), "{}", reason),
+            other => panic!("expected a malformed list error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! CSV exports of browsers and other password managers.
+//!
+//! Columns are recognized by their header names, which covers the exports
+//! of Chrome, Firefox, Bitwarden, LastPass and KeePassXC as well as most
+//! hand-made spreadsheets. The detected [`CsvFormat`] only decides the
+//! quirks of each exporter, such as Bitwarden's non-login items.
+
+use std::fmt;
+use std::io::Read;
+
+use crate::entry::{CustomField, FieldKind, PasswordEntry};
+use crate::error::{Result, VaultError};
+use crate::folder;
+use crate::import::{self, ImportedEntries, SkippedRecord};
+
+/// Which program wrote a CSV export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsvFormat {
+    Chrome,
+    Firefox,
+    Bitwarden,
+    LastPass,
+    KeePassXc,
+    /// Any other file with recognizable column names.
+    Generic,
+}
+
+impl fmt::Display for CsvFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CsvFormat::Chrome => "Chrome",
+            CsvFormat::Firefox => "Firefox",
+            CsvFormat::Bitwarden => "Bitwarden",
+            CsvFormat::LastPass => "LastPass",
+            CsvFormat::KeePassXc => "KeePassXC",
+            CsvFormat::Generic => "generic CSV",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Service,
+    Username,
+    Password,
+    Url,
+    Notes,
+    Folder,
+    Totp,
+    Favorite,
+    Fields,
+    Type,
+    Tags,
+    Created,
+    Modified,
+    LastUsed,
+}
+
+// Header names (lowercase) of every exporter, mapped to what they hold.
+fn column(name: &str) -> Option<Column> {
+    let column = match name {
+        "name" | "title" | "service" | "account" => Column::Service,
+        "username" | "login_username" | "login" | "user" | "user name" | "email" => Column::Username,
+        "password" | "login_password" | "pass" => Column::Password,
+        "url" | "login_uri" | "uri" | "website" | "web site" => Column::Url,
+        "note" | "notes" | "extra" | "comments" => Column::Notes,
+        "folder" | "group" | "grouping" | "collections" => Column::Folder,
+        "totp" | "login_totp" | "otp" | "otpauth" => Column::Totp,
+        "favorite" | "fav" => Column::Favorite,
+        "fields" => Column::Fields,
+        "type" => Column::Type,
+        "tags" => Column::Tags,
+        "created" | "timecreated" => Column::Created,
+        "last modified" | "modified" | "timepasswordchanged" => Column::Modified,
+        "timelastused" | "last used" => Column::LastUsed,
+        _ => return None,
+    };
+    Some(column)
+}
+
+fn detect(headers: &[String]) -> CsvFormat {
+    let has = |name: &str| headers.iter().any(|header| header == name);
+    if has("login_password") {
+        CsvFormat::Bitwarden
+    } else if has("grouping") && has("extra") {
+        CsvFormat::LastPass
+    } else if has("httprealm") || has("formactionorigin") {
+        CsvFormat::Firefox
+    } else if has("group") && has("title") {
+        CsvFormat::KeePassXc
+    } else if ["name", "url", "username", "password"].iter().all(|name| has(name)) {
+        CsvFormat::Chrome
+    } else {
+        CsvFormat::Generic
+    }
+}
+
+/// Reads a CSV export with a header row. The format is detected from the
+/// header unless given; either way the one used is returned.
+pub fn read_csv(reader: impl Read, format: Option<CsvFormat>) -> Result<(CsvFormat, ImportedEntries)> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| VaultError::ImportFailed(format!("cannot read the header row: {}", e)))?
+        .iter()
+        .map(|header| header.trim_start_matches('\u{feff}').trim().to_lowercase())
+        .collect();
+    let columns: Vec<Option<Column>> = headers.iter().map(|header| column(header)).collect();
+    if !columns.contains(&Some(Column::Password)) {
+        return Err(VaultError::ImportFailed("no password column found".to_string()));
+    }
+    let format = format.unwrap_or_else(|| detect(&headers));
+
+    let mut imported = ImportedEntries::default();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) if e.is_io_error() => return Err(VaultError::ImportFailed(e.to_string())),
+            Err(e) => {
+                let line = e.position().map_or(0, |position| position.line());
+                imported.skipped.push(SkippedRecord::new(format!("line {}", line), e.to_string()));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+        let row = Row {
+            columns: &columns,
+            record: &record,
+        };
+        match to_entry(format, &row) {
+            Ok(entry) => imported.entries.push(entry),
+            Err(reason) => imported.skipped.push(SkippedRecord::new(format!("line {}", line), reason)),
+        }
+    }
+    Ok((format, imported))
+}
+
+struct Row<'a> {
+    columns: &'a [Option<Column>],
+    record: &'a csv::StringRecord,
+}
+
+impl<'a> Row<'a> {
+    // The first non-blank cell of that kind, trimmed unless it is a
+    // password; short rows read as blank.
+    fn get(&self, wanted: Column) -> &'a str {
+        let value = self
+            .columns
+            .iter()
+            .zip(self.record.iter())
+            .find(|(column, value)| **column == Some(wanted) && !value.trim().is_empty())
+            .map_or("", |(_, value)| value);
+        if wanted == Column::Password {
+            value
+        } else {
+            value.trim()
+        }
+    }
+}
+
+fn to_entry(format: CsvFormat, row: &Row) -> std::result::Result<PasswordEntry, String> {
+    let value = |column| row.get(column);
+    let kind = value(Column::Type);
+    if format == CsvFormat::Bitwarden && !kind.is_empty() && kind != "login" {
+        return Err(format!("{} items are not imported", kind));
+    }
+    // LastPass exports secure notes as rows with this URL.
+    if format == CsvFormat::LastPass && value(Column::Url) == "http://sn" {
+        return Err("secure notes are not imported".to_string());
+    }
+    let password = value(Column::Password);
+    if password.is_empty() {
+        return Err("no password".to_string());
+    }
+
+    // Bitwarden puts several URIs in one cell.
+    let urls: Vec<String> = if format == CsvFormat::Bitwarden {
+        value(Column::Url).split(',').map(str::trim).filter(|url| !url.is_empty()).map(String::from).collect()
+    } else {
+        Some(value(Column::Url)).filter(|url| !url.is_empty()).map(String::from).into_iter().collect()
+    };
+    let service = match value(Column::Service) {
+        "" => urls.first().map(|url| import::host_of(url)).unwrap_or_default(),
+        name => name.to_string(),
+    };
+    if service.is_empty() {
+        return Err("no name or URL".to_string());
+    }
+
+    let mut entry = PasswordEntry::new(service, value(Column::Username).to_string(), password.to_string());
+    entry.urls = urls;
+    entry.notes = value(Column::Notes).to_string();
+    entry.folder = match format {
+        CsvFormat::LastPass => value(Column::Folder).replace('\\', "/"),
+        // KeePassXC paths start at the database's root group.
+        CsvFormat::KeePassXc => {
+            let path = folder::normalize(value(Column::Folder));
+            if path == "Root" {
+                String::new()
+            } else {
+                path.strip_prefix("Root/").unwrap_or(&path).to_string()
+            }
+        }
+        _ => value(Column::Folder).to_string(),
+    };
+    entry.tags = value(Column::Tags)
+        .split([',', ';'])
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect();
+    if matches!(value(Column::Favorite), "1" | "true" | "TRUE") {
+        entry.tags.push("favorite".to_string());
+    }
+    for line in value(Column::Fields).lines() {
+        if let Some((name, field)) = line.split_once(": ") {
+            entry.fields.push(CustomField {
+                name: name.to_string(),
+                kind: FieldKind::Text,
+                value: field.to_string(),
+            });
+        }
+    }
+    import::set_otp(&mut entry, value(Column::Totp));
+    if let Some(created) = import::parse_time(value(Column::Created)) {
+        entry.created = created;
+        entry.modified = created;
+    }
+    if let Some(modified) = import::parse_time(value(Column::Modified)) {
+        entry.modified = modified;
+    }
+    entry.last_used = import::parse_time(value(Column::LastUsed));
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(data: &str) -> (CsvFormat, ImportedEntries) {
+        read_csv(data.as_bytes(), None).unwrap()
+    }
+
+    fn reasons(imported: &ImportedEntries) -> Vec<(&str, &str)> {
+        imported.skipped.iter().map(|skipped| (skipped.location.as_str(), skipped.reason.as_str())).collect()
+    }
+
+    #[test]
+    fn reads_chrome_exports() {
+        let (format, imported) = read(
+            "name,url,username,password,note\n\
+             github.com,https://github.com/login,octocat,hunter2,recovery codes in the safe\n\
+             ,https://www.example.com/,alice,s3cret,\n\
+             mail,,bob,,\n",
+        );
+        assert_eq!(format, CsvFormat::Chrome);
+        assert_eq!(imported.entries.len(), 2);
+        let github = &imported.entries[0];
+        assert_eq!((github.service.as_str(), github.username.as_str(), github.password.as_str()), ("github.com", "octocat", "hunter2"));
+        assert_eq!(github.urls, ["https://github.com/login"]);
+        assert_eq!(github.notes, "recovery codes in the safe");
+        // Without a name the service comes from the URL's host.
+        assert_eq!(imported.entries[1].service, "example.com");
+        assert_eq!(reasons(&imported), [("line 4", "no password")]);
+    }
+
+    #[test]
+    fn reads_firefox_exports() {
+        let (format, imported) = read(
+            "\"url\",\"username\",\"password\",\"httpRealm\",\"formActionOrigin\",\"guid\",\"timeCreated\",\"timeLastUsed\",\"timePasswordChanged\"\n\
+             \"https://accounts.example.org\",\"alice\",\" spaced \",,\"https://accounts.example.org\",\"{1}\",\"1600000000000\",\"1700000000000\",\"1650000000000\"\n",
+        );
+        assert_eq!(format, CsvFormat::Firefox);
+        let entry = &imported.entries[0];
+        assert_eq!(entry.service, "accounts.example.org");
+        assert_eq!(entry.username, "alice");
+        // Passwords are kept as they are, blanks and all.
+        assert_eq!(entry.password, " spaced ");
+        assert_eq!(entry.created.timestamp_millis(), 1_600_000_000_000);
+        assert_eq!(entry.modified.timestamp_millis(), 1_650_000_000_000);
+        assert_eq!(entry.last_used.map(|time| time.timestamp_millis()), Some(1_700_000_000_000));
+        assert!(imported.skipped.is_empty());
+    }
+
+    #[test]
+    fn reads_bitwarden_exports() {
+        let (format, imported) = read(
+            "folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp\n\
+             Work,1,login,GitHub,,\"PIN: 1234\nRegion: eu\",0,\"https://github.com/login,https://gist.github.com\",octocat,hunter2,JBSWY3DPEHPK3PXP\n\
+             ,,note,Wifi,the code is on the router,,0,,,,\n",
+        );
+        assert_eq!(format, CsvFormat::Bitwarden);
+        assert_eq!(imported.entries.len(), 1);
+        let entry = &imported.entries[0];
+        assert_eq!(entry.service, "GitHub");
+        assert_eq!(entry.folder, "Work");
+        assert_eq!(entry.urls, ["https://github.com/login", "https://gist.github.com"]);
+        assert_eq!(entry.tags, ["favorite"]);
+        let fields: Vec<(&str, &str)> = entry.fields.iter().map(|field| (field.name.as_str(), field.value.as_str())).collect();
+        assert_eq!(fields, [("PIN", "1234"), ("Region", "eu")]);
+        assert!(entry.otp.is_some());
+        assert_eq!(reasons(&imported), [("line 4", "note items are not imported")]);
+    }
+
+    #[test]
+    fn reads_lastpass_exports() {
+        let (format, imported) = read(
+            "url,username,password,totp,extra,name,grouping,fav\n\
+             https://github.com,octocat,hunter2,,two keys,GitHub,Work\\Code,1\n\
+             http://sn,,,,NoteType:Server,Server,Work,0\n",
+        );
+        assert_eq!(format, CsvFormat::LastPass);
+        assert_eq!(imported.entries.len(), 1);
+        let entry = &imported.entries[0];
+        assert_eq!(entry.service, "GitHub");
+        assert_eq!(entry.folder, "Work/Code");
+        assert_eq!(entry.notes, "two keys");
+        assert_eq!(entry.tags, ["favorite"]);
+        assert_eq!(reasons(&imported), [("line 3", "secure notes are not imported")]);
+    }
+
+    #[test]
+    fn reads_keepassxc_exports() {
+        let (format, imported) = read(
+            "\"Group\",\"Title\",\"Username\",\"Password\",\"URL\",\"Notes\",\"TOTP\",\"Icon\",\"Last Modified\",\"Created\"\n\
+             \"Root/Work\",\"GitHub\",\"octocat\",\"hunter2\",\"https://github.com\",\"\",\"\",\"0\",\"2024-03-01T10:00:00Z\",\"2023-01-01T09:00:00Z\"\n\
+             \"Root\",\"Mail\",\"bob\",\"pw\",\"\",\"\",\"\",\"0\",\"\",\"\"\n\
+             \"Root\",\"\",\"carol\",\"pw\",\"\",\"\",\"\",\"0\",\"\",\"\"\n",
+        );
+        assert_eq!(format, CsvFormat::KeePassXc);
+        let folders: Vec<&str> = imported.entries.iter().map(|entry| entry.folder.as_str()).collect();
+        assert_eq!(folders, ["Work", ""]);
+        let github = &imported.entries[0];
+        assert_eq!(github.created.to_rfc3339(), "2023-01-01T09:00:00+00:00");
+        assert_eq!(github.modified.to_rfc3339(), "2024-03-01T10:00:00+00:00");
+        assert_eq!(reasons(&imported), [("line 4", "no name or URL")]);
+    }
+
+    #[test]
+    fn reads_generic_spreadsheets() {
+        let (format, imported) = read("Service,Login,Pass,Tags\nbank,me,pw,\"money; important\"\n");
+        assert_eq!(format, CsvFormat::Generic);
+        assert_eq!(imported.entries[0].tags, ["money", "important"]);
+
+        // A format given by the caller wins over detection.
+        let (format, _) = read_csv("name,url,username,password\n".as_bytes(), Some(CsvFormat::Generic)).unwrap();
+        assert_eq!(format, CsvFormat::Generic);
+    }
+
+    #[test]
+    fn needs_a_password_column() {
+        assert!(matches!(read_csv("name,url,username\na,b,c\n".as_bytes(), None), Err(VaultError::ImportFailed(_))));
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::folder;
+use crate::otp::OtpSecret;
+
+/// A stored credential. A service can have any number of entries, told apart
+/// by username and identified by `id`, which never changes.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PasswordEntry {
+    // Vaults written before entries had IDs get fresh ones on load.
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub service: String,
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub urls: Vec<String>,
+    /// Free text; may span several lines.
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub fields: Vec<CustomField>,
+    /// `/`-separated path such as `Work/Email`; empty for the top level.
+    #[serde(default)]
+    pub folder: String,
+    /// Compared case-insensitively; the vault keeps them without repeats.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Entries from older vaults count as created when first loaded.
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+    #[serde(default = "Utc::now")]
+    pub modified: DateTime<Utc>,
+    #[serde(default)]
+    pub last_used: Option<DateTime<Utc>>,
+    /// When the current password was set. Unset for new, imported and older
+    /// entries; see [`password_set_at`](Self::password_set_at).
+    #[serde(default)]
+    pub password_changed: Option<DateTime<Utc>>,
+    /// Secret for two-factor codes, if the service uses them.
+    #[serde(default)]
+    pub otp: Option<OtpSecret>,
+    /// Earlier passwords, newest first.
+    #[serde(default)]
+    pub history: Vec<PreviousPassword>,
+}
+
+impl PasswordEntry {
+    pub fn new(service: String, username: String, password: String) -> PasswordEntry {
+        let now = Utc::now();
+        PasswordEntry {
+            id: Uuid::new_v4(),
+            service,
+            username,
+            password,
+            urls: Vec::new(),
+            notes: String::new(),
+            fields: Vec::new(),
+            folder: String::new(),
+            tags: Vec::new(),
+            created: now,
+            modified: now,
+            last_used: None,
+            password_changed: None,
+            otp: None,
+            history: Vec::new(),
+        }
+    }
+
+    /// When the current password was set: the recorded change, or else when
+    /// the newest history item was replaced, or else the creation time.
+    pub fn password_set_at(&self) -> DateTime<Utc> {
+        self.password_changed
+            .or_else(|| self.history.first().map(|previous| previous.replaced))
+            .unwrap_or(self.created)
+    }
+
+    /// `username@service`, or just the service when there is no username.
+    pub fn label(&self) -> String {
+        if self.username.is_empty() {
+            self.service.clone()
+        } else {
+            format!("{}@{}", self.username, self.service)
+        }
+    }
+
+    /// Whether the entry is in `folder` or one of its subfolders. Every
+    /// entry is in the top level, `""`.
+    pub fn in_folder(&self, folder: &str) -> bool {
+        folder::contains(&folder::normalize(folder), &self.folder)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own.eq_ignore_ascii_case(tag.trim()))
+    }
+}
+
+/// A password an entry used to have, and when it was replaced.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PreviousPassword {
+    pub password: String,
+    pub replaced: DateTime<Utc>,
+}
+
+/// An extra named value on an entry, such as a security question or an
+/// account number.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CustomField {
+    pub name: String,
+    pub kind: FieldKind,
+    pub value: String,
+}
+
+/// How a custom field's value should be treated when entered and shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    Text,
+    /// Secret, like the password: not echoed when typed in.
+    Hidden,
+    Url,
+    Email,
+    Date,
+}
+
+impl FieldKind {
+    /// Parses `text`, `hidden`, `url`, `email` or `date` (case-insensitive).
+    pub fn from_name(name: &str) -> Option<FieldKind> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Some(FieldKind::Text),
+            "hidden" => Some(FieldKind::Hidden),
+            "url" => Some(FieldKind::Url),
+            "email" => Some(FieldKind::Email),
+            "date" => Some(FieldKind::Date),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FieldKind::Text => "text",
+            FieldKind::Hidden => "hidden",
+            FieldKind::Url => "url",
+            FieldKind::Email => "email",
+            FieldKind::Date => "date",
+        };
+        write!(f, "{}", name)
+    }
+}
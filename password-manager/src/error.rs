@@ -0,0 +1,80 @@
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while working with a vault.
+#[derive(Debug)]
+pub enum VaultError {
+    Io(io::Error),
+    WrongPassword,
+    CorruptVault(String),
+    UnsupportedVersion(u16),
+    DuplicateEntry(String),
+    NotFound(String),
+    /// A service name matched several entries; carries the service name.
+    AmbiguousEntry(String),
+    /// An `otpauth://` URI or one-time password secret could not be used.
+    InvalidOtp(String),
+    /// A new master password scored below the required minimum; carries the
+    /// estimator's explanation.
+    WeakPassword(String),
+    /// Password generator rules that cannot be satisfied.
+    InvalidPolicy(String),
+    /// A folder that does not exist, cannot be removed or cannot be renamed
+    /// as asked.
+    InvalidFolder(String),
+    /// An export file could not be read as a whole.
+    ImportFailed(String),
+    /// Key derivation parameters that are out of range, so the key cannot
+    /// be derived.
+    InvalidKdf(String),
+    /// Passwords were to be checked against breaches, but no breach list has
+    /// been set.
+    NoBreachDatabase,
+    /// A line of the breach list is not `HASH:COUNT`; carries where it is
+    /// and what it says.
+    InvalidBreachDatabase(String),
+    /// The operation needs the vault key but the vault has not been unlocked.
+    Locked,
+}
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VaultError::Io(e) => write!(f, "I/O error: {}", e),
+            VaultError::WrongPassword => write!(f, "invalid master password"),
+            VaultError::CorruptVault(reason) => write!(f, "vault is corrupted: {}", reason),
+            VaultError::UnsupportedVersion(version) => write!(f, "unsupported vault format version {}", version),
+            VaultError::DuplicateEntry(service) => write!(f, "an entry for '{}' already exists", service),
+            VaultError::NotFound(service) => write!(f, "no entry for '{}'", service),
+            VaultError::AmbiguousEntry(service) => {
+                write!(f, "'{}' has several entries; pick one by username or ID", service)
+            }
+            VaultError::InvalidOtp(reason) => write!(f, "invalid one-time password setup: {}", reason),
+            VaultError::WeakPassword(reason) => write!(f, "master password is too weak: {}", reason),
+            VaultError::InvalidPolicy(reason) => write!(f, "cannot generate a password: {}", reason),
+            VaultError::InvalidFolder(reason) => write!(f, "folder error: {}", reason),
+            VaultError::ImportFailed(reason) => write!(f, "cannot import: {}", reason),
+            VaultError::InvalidKdf(params) => write!(f, "unusable key derivation parameters: {}", params),
+            VaultError::NoBreachDatabase => write!(f, "no breach list to check passwords against"),
+            VaultError::InvalidBreachDatabase(reason) => write!(f, "malformed breach list: {}", reason),
+            VaultError::Locked => write!(f, "the vault is locked"),
+        }
+    }
+}
+
+impl std::error::Error for VaultError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VaultError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for VaultError {
+    fn from(e: io::Error) -> VaultError {
+        VaultError::Io(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, VaultError>;
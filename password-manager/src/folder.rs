@@ -0,0 +1,55 @@
+// Folders are `/`-separated paths such as `Work/Email`; the empty path is the
+// top level. Paths are normalized before they are stored, so `" Work//Email/"`
+// and `Work/Email` name the same folder.
+
+pub(crate) const SEPARATOR: char = '/';
+
+pub(crate) fn normalize(path: &str) -> String {
+    path.split(SEPARATOR)
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// True when `path` is `folder` itself or lies somewhere below it.
+pub(crate) fn contains(folder: &str, path: &str) -> bool {
+    folder.is_empty()
+        || path == folder
+        || path.strip_prefix(folder).is_some_and(|rest| rest.starts_with(SEPARATOR))
+}
+
+// `Work/Email/Old` yields `Work`, `Work/Email` and `Work/Email/Old`.
+pub(crate) fn with_ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices(SEPARATOR)
+        .map(move |(i, _)| &path[..i])
+        .chain((!path.is_empty()).then_some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize(" Work//Email/ "), "Work/Email");
+        assert_eq!(normalize("/ Work / Old Mail /"), "Work/Old Mail");
+        assert_eq!(normalize("//"), "");
+    }
+
+    #[test]
+    fn contains_only_whole_path_parts() {
+        assert!(contains("Work", "Work"));
+        assert!(contains("Work", "Work/Email/Old"));
+        assert!(contains("", "Work"));
+        assert!(!contains("Work", "Workshop"));
+        assert!(!contains("Work/Email", "Work"));
+    }
+
+    #[test]
+    fn lists_ancestors_outermost_first() {
+        assert_eq!(with_ancestors("Work/Email/Old").collect::<Vec<_>>(), ["Work", "Work/Email", "Work/Email/Old"]);
+        assert_eq!(with_ancestors("Work").collect::<Vec<_>>(), ["Work"]);
+        assert_eq!(with_ancestors("").count(), 0);
+    }
+}
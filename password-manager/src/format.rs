@@ -0,0 +1,329 @@
+// Vault file layout (all integers little-endian):
+//
+//   magic    8 bytes  "PWMVAULT"
+//   version  2 bytes  FORMAT_VERSION
+//   kdf     13 bytes  algorithm id + three u32 cost parameters
+//   salt    16 bytes
+//   verifier 32 bytes HMAC-SHA256(key, VERIFIER_LABEL)
+//   nonce   12 bytes
+//   ciphertext        AES-256-GCM over the JSON body
+//   tag     16 bytes
+//
+// Everything before the nonce is the header and is authenticated as
+// associated data. The verifier tells a wrong password apart from a
+// damaged file without having to decrypt the body.
+//
+// The body of a version 2 vault is
+// `{"folders": [...], "entries": [...], "settings": {...}}`, where vaults
+// written before settings were stored lack the last part and get the
+// defaults. Version 1 held only the entry list, so it had no room for empty
+// folders. Both are read, and vaults are always written as version 2.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha2::Sha256;
+use crypto::util::fixed_time_eq;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::entry::PasswordEntry;
+use crate::error::{Result, VaultError};
+use crate::kdf::{KdfParams, KDF_HEADER_LEN, KEY_LEN, SALT_LEN};
+use crate::manager::{DEFAULT_BACKUP_COUNT, DEFAULT_HISTORY_COUNT};
+
+pub(crate) const MAGIC: &[u8; 8] = b"PWMVAULT";
+const FORMAT_VERSION: u16 = 2;
+const ENTRY_LIST_VERSION: u16 = 1;
+const VERSION_OFFSET: usize = 8;
+const KDF_OFFSET: usize = 10;
+const SALT_OFFSET: usize = KDF_OFFSET + KDF_HEADER_LEN;
+const VERIFIER_OFFSET: usize = SALT_OFFSET + SALT_LEN;
+const VERIFIER_LEN: usize = 32;
+const HEADER_LEN: usize = VERIFIER_OFFSET + VERIFIER_LEN;
+const VERIFIER_LABEL: &[u8] = b"PasswordManager vault verifier";
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+// Everything needed to write a vault without asking for the password again.
+#[derive(Clone)]
+pub(crate) struct VaultKey {
+    pub(crate) kdf: KdfParams,
+    salt: [u8; SALT_LEN],
+    key: [u8; KEY_LEN],
+}
+
+impl VaultKey {
+    // Derives a key for a new vault (or a re-keyed one) under a fresh salt.
+    pub(crate) fn derive(password: &str, kdf: KdfParams) -> Result<VaultKey> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let key = kdf.derive_key(password, &salt)?;
+        Ok(VaultKey { kdf, salt, key })
+    }
+
+    pub(crate) fn matches_password(&self, password: &str) -> bool {
+        self.kdf.derive_key(password, &self.salt).is_ok_and(|key| fixed_time_eq(&key, &self.key))
+    }
+
+    fn verifier(&self) -> [u8; VERIFIER_LEN] {
+        let mut mac = Hmac::new(Sha256::new(), &self.key);
+        mac.input(VERIFIER_LABEL);
+        let mut verifier = [0u8; VERIFIER_LEN];
+        mac.raw_result(&mut verifier);
+        verifier
+    }
+}
+
+// Vault-wide preferences, kept in the encrypted body so that they travel
+// with the vault.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Settings {
+    pub(crate) backup_count: usize,
+    pub(crate) history_count: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            backup_count: DEFAULT_BACKUP_COUNT,
+            history_count: DEFAULT_HISTORY_COUNT,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Body<'a> {
+    folders: &'a [&'a str],
+    entries: &'a [&'a PasswordEntry],
+    settings: Settings,
+}
+
+// What a vault holds once decrypted.
+#[derive(Deserialize)]
+pub(crate) struct Contents {
+    #[serde(default)]
+    pub(crate) folders: Vec<String>,
+    pub(crate) entries: Vec<PasswordEntry>,
+    #[serde(default)]
+    pub(crate) settings: Settings,
+}
+
+pub(crate) fn encode(entries: &[&PasswordEntry], folders: &[&str], settings: Settings, key: &VaultKey) -> Vec<u8> {
+    let mut contents = Vec::with_capacity(HEADER_LEN);
+    contents.extend_from_slice(MAGIC);
+    contents.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    contents.extend_from_slice(&key.kdf.to_header());
+    contents.extend_from_slice(&key.salt);
+    contents.extend_from_slice(&key.verifier());
+
+    let plaintext = serde_json::to_vec(&Body { folders, entries, settings }).expect("entries are always serializable");
+    let sealed = seal(&plaintext, &key.key, &contents);
+    contents.extend(sealed);
+    contents
+}
+
+// Nothing is returned unless the whole file authenticates.
+pub(crate) fn decode(contents: &[u8], password: &str) -> Result<(VaultKey, Contents)> {
+    if contents.len() < HEADER_LEN || !contents.starts_with(MAGIC) {
+        return Err(VaultError::CorruptVault("not a password vault".to_string()));
+    }
+
+    let (header, sealed) = contents.split_at(HEADER_LEN);
+    let version = u16::from_le_bytes([header[VERSION_OFFSET], header[VERSION_OFFSET + 1]]);
+    if version != FORMAT_VERSION && version != ENTRY_LIST_VERSION {
+        return Err(VaultError::UnsupportedVersion(version));
+    }
+    let kdf = KdfParams::from_header(&header[KDF_OFFSET..SALT_OFFSET])
+        .ok_or_else(|| VaultError::CorruptVault("invalid key derivation parameters".to_string()))?;
+
+    let mut salt = [0u8; SALT_LEN];
+    salt.copy_from_slice(&header[SALT_OFFSET..VERIFIER_OFFSET]);
+    let key = VaultKey { kdf, salt, key: kdf.derive_key(password, &salt)? };
+    if !fixed_time_eq(&key.verifier(), &header[VERIFIER_OFFSET..]) {
+        return Err(VaultError::WrongPassword);
+    }
+
+    let plaintext = open(sealed, &key.key, header)
+        .ok_or_else(|| VaultError::CorruptVault("authentication failed".to_string()))?;
+    let invalid = |e: serde_json::Error| VaultError::CorruptVault(format!("invalid entry data: {}", e));
+    let contents = if version == ENTRY_LIST_VERSION {
+        Contents {
+            folders: Vec::new(),
+            entries: serde_json::from_slice(&plaintext).map_err(invalid)?,
+            settings: Settings::default(),
+        }
+    } else {
+        serde_json::from_slice(&plaintext).map_err(invalid)?
+    };
+    Ok((key, contents))
+}
+
+fn seal(plaintext: &[u8], key: &[u8; KEY_LEN], aad: &[u8]) -> Vec<u8> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    // The cipher appends the tag to the ciphertext.
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+        .expect("vault bodies are far below the AES-GCM size limit");
+
+    let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    sealed.extend_from_slice(&nonce);
+    sealed.extend(ciphertext);
+    sealed
+}
+
+// Returns None when the record is truncated or fails authentication.
+fn open(sealed: &[u8], key: &[u8; KEY_LEN], aad: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad }).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHEAP: KdfParams = KdfParams::Pbkdf2Sha256 { iterations: 1 };
+
+    fn sample_vault(password: &str) -> Vec<u8> {
+        let entry = PasswordEntry::new("github".into(), "octocat".into(), "hunter2".into());
+        let settings = Settings {
+            backup_count: 2,
+            history_count: 3,
+        };
+        encode(&[&entry], &["Work"], settings, &VaultKey::derive(password, CHEAP).unwrap())
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let (key, contents) = decode(&sample_vault("master"), "master").unwrap();
+        assert_eq!(key.kdf, CHEAP);
+        assert!(key.matches_password("master"));
+        assert!(!key.matches_password("Master"));
+        assert_eq!(contents.folders, ["Work"]);
+        assert_eq!(contents.entries.len(), 1);
+        assert_eq!(contents.entries[0].password, "hunter2");
+        assert_eq!((contents.settings.backup_count, contents.settings.history_count), (2, 3));
+    }
+
+    #[test]
+    fn rejects_wrong_password() {
+        assert!(matches!(decode(&sample_vault("master"), "not it"), Err(VaultError::WrongPassword)));
+    }
+
+    #[test]
+    fn every_flipped_body_byte_is_detected() {
+        let vault = sample_vault("master");
+        for i in HEADER_LEN..vault.len() {
+            let mut damaged = vault.clone();
+            damaged[i] ^= 0x01;
+            assert!(matches!(decode(&damaged, "master"), Err(VaultError::CorruptVault(_))), "byte {}", i);
+        }
+    }
+
+    #[test]
+    fn flipped_header_bytes_are_rejected() {
+        let vault = sample_vault("master");
+        // Flipped cost parameters are valid, just slow; kdf.rs checks their
+        // bounds.
+        for i in (0..HEADER_LEN).filter(|i| !(KDF_OFFSET + 1..SALT_OFFSET).contains(i)) {
+            let mut damaged = vault.clone();
+            damaged[i] ^= 0x01;
+            assert!(decode(&damaged, "master").is_err(), "byte {}", i);
+        }
+
+        // A header that still yields the right key fails on the body, as the
+        // header is authenticated along with it.
+        let mut downgraded = vault.clone();
+        downgraded[VERSION_OFFSET..KDF_OFFSET].copy_from_slice(&ENTRY_LIST_VERSION.to_le_bytes());
+        assert!(matches!(decode(&downgraded, "master"), Err(VaultError::CorruptVault(_))));
+
+        let mut future = vault;
+        future[VERSION_OFFSET..KDF_OFFSET].copy_from_slice(&99u16.to_le_bytes());
+        assert!(matches!(decode(&future, "master"), Err(VaultError::UnsupportedVersion(99))));
+    }
+
+    #[test]
+    fn rejects_truncated_and_foreign_files() {
+        let vault = sample_vault("master");
+        for len in [0, 8, HEADER_LEN - 1, HEADER_LEN, HEADER_LEN + NONCE_LEN + TAG_LEN - 1, vault.len() - 1] {
+            assert!(matches!(decode(&vault[..len], "master"), Err(VaultError::CorruptVault(_))), "length {}", len);
+        }
+        assert!(matches!(decode(b"service|user|0a0b0c", "master"), Err(VaultError::CorruptVault(_))));
+    }
+
+    #[test]
+    fn rejects_unsafe_kdf_parameters_before_deriving() {
+        let mut vault = sample_vault("master");
+        // Argon2id with 64 GiB of memory.
+        let header = KdfParams::Argon2id { memory_kib: 64 * 1024 * 1024, iterations: 1, parallelism: 1 }.to_header();
+        vault[KDF_OFFSET..SALT_OFFSET].copy_from_slice(&header);
+        assert!(matches!(decode(&vault, "master"), Err(VaultError::CorruptVault(_))));
+    }
+
+    #[test]
+    fn reads_version_1_entry_lists() {
+        let key = VaultKey::derive("master", CHEAP).unwrap();
+        let entry = PasswordEntry::new("mail".into(), String::new(), "secret".into());
+        let mut vault = Vec::new();
+        vault.extend_from_slice(MAGIC);
+        vault.extend_from_slice(&ENTRY_LIST_VERSION.to_le_bytes());
+        vault.extend_from_slice(&key.kdf.to_header());
+        vault.extend_from_slice(&key.salt);
+        vault.extend_from_slice(&key.verifier());
+        let body = serde_json::to_vec(&[&entry]).unwrap();
+        let sealed = seal(&body, &key.key, &vault);
+        vault.extend(sealed);
+
+        let (_, contents) = decode(&vault, "master").unwrap();
+        assert!(contents.folders.is_empty());
+        assert_eq!(contents.entries[0].service, "mail");
+        assert_eq!(contents.settings, Settings::default());
+    }
+
+    #[test]
+    fn bodies_without_settings_get_the_defaults() {
+        let contents: Contents = serde_json::from_str(r#"{"folders": [], "entries": []}"#).unwrap();
+        assert_eq!(contents.settings, Settings::default());
+        let contents: Contents = serde_json::from_str(r#"{"entries": [], "settings": {"history_count": 0}}"#).unwrap();
+        assert_eq!((contents.settings.backup_count, contents.settings.history_count), (DEFAULT_BACKUP_COUNT, 0));
+    }
+
+    // Vaults sealed with rust-crypto's AES-GCM, which wrote them before, must
+    // still open, and the other way round.
+    #[test]
+    fn matches_the_previous_aes_gcm_implementation() {
+        use crypto::aead::{AeadDecryptor, AeadEncryptor};
+        use crypto::aes::KeySize;
+        use crypto::aes_gcm::AesGcm;
+
+        let key = [7u8; KEY_LEN];
+        let nonce = [9u8; NONCE_LEN];
+        let (plaintext, aad) = (b"{\"entries\": []}".as_slice(), b"header".as_slice());
+        let mut ciphertext = vec![0u8; plaintext.len()];
+        let mut tag = [0u8; TAG_LEN];
+        AesGcm::new(KeySize::KeySize256, &key, &nonce, aad).encrypt(plaintext, &mut ciphertext, &mut tag);
+        let old_sealed = [&nonce[..], &ciphertext, &tag].concat();
+        assert_eq!(open(&old_sealed, &key, aad).as_deref(), Some(plaintext));
+
+        let sealed = seal(plaintext, &key, aad);
+        assert_eq!(sealed.len(), NONCE_LEN + plaintext.len() + TAG_LEN);
+        let (nonce, rest) = sealed.split_at(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+        let mut decrypted = vec![0u8; ciphertext.len()];
+        assert!(AesGcm::new(KeySize::KeySize256, &key, nonce, aad).decrypt(ciphertext, &mut decrypted, tag));
+        assert_eq!(decrypted, plaintext);
+
+        assert!(open(&sealed, &key, b"other header").is_none());
+        assert!(open(&sealed[..NONCE_LEN + TAG_LEN - 1], &key, aad).is_none());
+    }
+}
@@ -0,0 +1,334 @@
+//! Random password generation under site-specific rules.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::error::{Result, VaultError};
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+pub(crate) const SYMBOLS: &str = "!@#$%^&*";
+
+/// Characters that are easily confused with one another in many fonts.
+pub const AMBIGUOUS: &str = "Il1|O0o";
+
+/// Rules for [`PasswordPolicy::generate`]. The required characters of
+/// each class are drawn first, the rest uniformly from the whole allowed
+/// alphabet, and the result is shuffled so the required ones can be
+/// anywhere. Only characters that would break the repeat limit are
+/// replaced afterwards.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub min_lowercase: usize,
+    pub min_uppercase: usize,
+    pub min_digits: usize,
+    pub min_symbols: usize,
+    /// Extra characters to allow on top of the enabled classes.
+    pub include: String,
+    /// Characters never to use, whatever their class.
+    pub exclude: String,
+    /// Leave out [`AMBIGUOUS`] characters.
+    pub avoid_ambiguous: bool,
+    /// Most times the same character may appear in a row.
+    pub max_repeats: Option<usize>,
+}
+
+impl Default for PasswordPolicy {
+    /// 16 characters with at least one of each class.
+    fn default() -> PasswordPolicy {
+        PasswordPolicy {
+            length: 16,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            min_lowercase: 1,
+            min_uppercase: 1,
+            min_digits: 1,
+            min_symbols: 1,
+            include: String::new(),
+            exclude: String::new(),
+            avoid_ambiguous: false,
+            max_repeats: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// The default policy with a different length.
+    pub fn with_length(length: usize) -> PasswordPolicy {
+        PasswordPolicy {
+            length,
+            ..PasswordPolicy::default()
+        }
+    }
+
+    /// Generates a password, or fails with [`VaultError::InvalidPolicy`] if
+    /// no password can satisfy the rules.
+    pub fn generate(&self) -> Result<String> {
+        let classes = self.classes()?;
+        let mut alphabet: Vec<char> = classes.iter().flat_map(|(set, _)| set.iter().copied()).collect();
+        alphabet.extend(self.include.chars().filter(|&c| self.allows(c)));
+        alphabet.sort_unstable();
+        alphabet.dedup();
+        if alphabet.is_empty() {
+            return Err(invalid("no characters left to choose from"));
+        }
+        if self.length == 0 {
+            return Err(invalid("length must be positive"));
+        }
+        if self.max_repeats == Some(0) {
+            return Err(invalid("max repeats must be at least 1"));
+        }
+
+        let mut needs: Vec<usize> = classes.iter().map(|(_, min)| *min).collect();
+        if !self.completable(&classes, &needs, self.length, None, &alphabet) {
+            return Err(invalid(&format!(
+                "no password of length {} keeps to at most {} repeats with these characters",
+                self.length,
+                self.max_repeats.unwrap_or(0)
+            )));
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut candidate: Vec<char> = Vec::with_capacity(self.length);
+        for (set, min) in &classes {
+            candidate.extend((0..*min).map(|_| set[rng.gen_range(0..set.len())]));
+        }
+        while candidate.len() < self.length {
+            candidate.push(alphabet[rng.gen_range(0..alphabet.len())]);
+        }
+        // A Fisher-Yates shuffle.
+        candidate.shuffle(&mut rng);
+
+        // Keeps each character unless the rest could then no longer be
+        // filled, e.g. a third '0' in a row with a limit of two, and draws
+        // one that keeps it possible instead. Without a repeat limit every
+        // character is kept.
+        let mut password: Vec<char> = Vec::with_capacity(self.length);
+        for (i, &c) in candidate.iter().enumerate() {
+            let left = self.length - i - 1;
+            let fits = |c: char| {
+                let mut needs = needs.clone();
+                take(&classes, &mut needs, c);
+                let run = password.iter().rev().take_while(|&&previous| previous == c).count() + 1;
+                self.completable(&classes, &needs, left, Some((c, run)), &alphabet)
+            };
+            let c = if fits(c) {
+                c
+            } else {
+                let options: Vec<char> = alphabet.iter().copied().filter(|&c| fits(c)).collect();
+                *options.choose(&mut rng).ok_or_else(|| invalid("the rules cannot be met at this length"))?
+            };
+            take(&classes, &mut needs, c);
+            password.push(c);
+        }
+        Ok(password.into_iter().collect())
+    }
+
+    // The enabled classes with exclusions applied, paired with their minimum
+    // counts.
+    fn classes(&self) -> Result<Vec<(Vec<char>, usize)>> {
+        let all = [
+            ("lowercase", self.lowercase, LOWERCASE, self.min_lowercase),
+            ("uppercase", self.uppercase, UPPERCASE, self.min_uppercase),
+            ("digits", self.digits, DIGITS, self.min_digits),
+            ("symbols", self.symbols, SYMBOLS, self.min_symbols),
+        ];
+
+        let mut classes = Vec::new();
+        let mut required = 0;
+        for (name, enabled, chars, min) in all {
+            let set: Vec<char> = chars.chars().filter(|&c| self.allows(c)).collect();
+            if min > 0 && (!enabled || set.is_empty()) {
+                return Err(invalid(&format!("at least {} {} required, but none are allowed", min, name)));
+            }
+            if enabled {
+                classes.push((set, min));
+                required += min;
+            }
+        }
+        if required > self.length {
+            return Err(invalid(&format!(
+                "the minimum counts add up to {}, more than the length {}",
+                required, self.length
+            )));
+        }
+        Ok(classes)
+    }
+
+    fn allows(&self, c: char) -> bool {
+        let ambiguous = self.avoid_ambiguous && AMBIGUOUS.contains(c);
+        !self.exclude.contains(c) && !ambiguous
+    }
+
+    // Whether `left` more characters can still bring every class up to its
+    // remaining need without a run longer than the limit, after a password
+    // so far ending in `last` (a character and how often it is repeated
+    // there). Classes of two or more characters can always alternate, so
+    // only the whole alphabet and classes of a single character can run
+    // out of room.
+    fn completable(&self, classes: &[(Vec<char>, usize)], needs: &[usize], left: usize, last: Option<(char, usize)>, alphabet: &[char]) -> bool {
+        if needs.iter().sum::<usize>() > left {
+            return false;
+        }
+        let Some(max) = self.max_repeats else {
+            return true;
+        };
+        let run = |c: char| last.filter(|&(previous, _)| previous == c).map_or(0, |(_, run)| run);
+        if last.is_some_and(|(_, run)| run > max) {
+            return false;
+        }
+        if let [only] = alphabet {
+            return run(*only) + left <= max;
+        }
+        // A single character needed `need` more times fits `max - run`
+        // right away, then `max` after each other character.
+        classes.iter().zip(needs).all(|((set, _), &need)| match set.as_slice() {
+            [single] if need > 0 => left - need >= need.saturating_sub(max - run(*single)).div_ceil(max),
+            _ => true,
+        })
+    }
+}
+
+// Counts `c` towards the need of its class.
+fn take(classes: &[(Vec<char>, usize)], needs: &mut [usize], c: char) {
+    if let Some(i) = classes.iter().position(|(set, _)| set.contains(&c)) {
+        needs[i] = needs[i].saturating_sub(1);
+    }
+}
+
+fn invalid(reason: &str) -> VaultError {
+    VaultError::InvalidPolicy(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(length: usize) -> PasswordPolicy {
+        PasswordPolicy {
+            min_lowercase: 0,
+            min_uppercase: 0,
+            min_digits: 0,
+            min_symbols: 0,
+            ..PasswordPolicy::with_length(length)
+        }
+    }
+
+    fn longest_run(password: &str) -> usize {
+        let chars: Vec<char> = password.chars().collect();
+        chars.chunk_by(|a, b| a == b).map(|run| run.len()).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn meets_per_class_minimums() {
+        for _ in 0..200 {
+            let password = PasswordPolicy::default().generate().unwrap();
+            assert_eq!(password.chars().count(), 16);
+            for class in [LOWERCASE, UPPERCASE, DIGITS, SYMBOLS] {
+                assert!(password.chars().any(|c| class.contains(c)), "{}", password);
+            }
+        }
+        // Minimums that take up the whole length.
+        let all_digits = PasswordPolicy { min_digits: 8, ..policy(8) };
+        for _ in 0..200 {
+            assert!(all_digits.generate().unwrap().chars().all(|c| c.is_ascii_digit()));
+        }
+        let mixed = PasswordPolicy { min_uppercase: 3, min_symbols: 3, ..policy(6) };
+        let password = mixed.generate().unwrap();
+        assert_eq!(password.chars().filter(|c| c.is_ascii_uppercase()).count(), 3, "{}", password);
+    }
+
+    #[test]
+    fn keeps_to_include_and_exclude_sets() {
+        let only = PasswordPolicy {
+            lowercase: false,
+            uppercase: false,
+            symbols: false,
+            exclude: "23456789".into(),
+            include: "-_".into(),
+            ..policy(40)
+        };
+        let password = only.generate().unwrap();
+        assert!(password.chars().all(|c| "01-_".contains(c)), "{}", password);
+        for c in ['0', '1', '-', '_'] {
+            assert!(password.contains(c), "{}", password);
+        }
+        // Exclusions win over includes.
+        let excluded = PasswordPolicy { include: "~".into(), exclude: "~".into(), ..policy(200) };
+        assert!(!excluded.generate().unwrap().contains('~'));
+    }
+
+    #[test]
+    fn leaves_out_ambiguous_characters() {
+        let clear = PasswordPolicy { avoid_ambiguous: true, ..policy(500) };
+        let password = clear.generate().unwrap();
+        assert!(!password.chars().any(|c| AMBIGUOUS.contains(c)), "{}", password);
+    }
+
+    #[test]
+    fn keeps_to_the_repeat_limit() {
+        let binary = PasswordPolicy {
+            lowercase: false,
+            uppercase: false,
+            symbols: false,
+            exclude: "23456789".into(),
+            max_repeats: Some(1),
+            ..policy(20)
+        };
+        for _ in 0..50 {
+            let password = binary.generate().unwrap();
+            assert!(password == "01010101010101010101" || password == "10101010101010101010", "{}", password);
+        }
+        // Six 7s in eight characters fit only as 77x77x77 with other
+        // characters in between.
+        let sevens = PasswordPolicy {
+            exclude: "012345689".into(),
+            min_digits: 6,
+            max_repeats: Some(2),
+            ..policy(8)
+        };
+        for _ in 0..50 {
+            let password = sevens.generate().unwrap();
+            assert_eq!(password.matches('7').count(), 6, "{}", password);
+            assert!(longest_run(&password) <= 2, "{}", password);
+        }
+        let limited = PasswordPolicy { max_repeats: Some(1), ..PasswordPolicy::with_length(64) };
+        for _ in 0..50 {
+            assert_eq!(longest_run(&limited.generate().unwrap()), 1);
+        }
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_policies() {
+        let only_zero = PasswordPolicy {
+            lowercase: false,
+            uppercase: false,
+            symbols: false,
+            exclude: "123456789".into(),
+            max_repeats: Some(3),
+            ..policy(4)
+        };
+        for policy in [
+            PasswordPolicy { max_repeats: Some(0), ..policy(8) },
+            PasswordPolicy { length: 0, ..policy(0) },
+            PasswordPolicy { min_digits: 5, min_symbols: 5, ..policy(8) },
+            PasswordPolicy { digits: false, min_digits: 1, ..policy(8) },
+            PasswordPolicy { exclude: DIGITS.into(), min_digits: 1, ..policy(8) },
+            PasswordPolicy { lowercase: false, uppercase: false, digits: false, symbols: false, ..policy(8) },
+            only_zero.clone(),
+            PasswordPolicy { exclude: "012345689".into(), min_digits: 7, max_repeats: Some(2), ..policy(8) },
+        ] {
+            assert!(matches!(policy.generate(), Err(VaultError::InvalidPolicy(_))), "{:?}", policy);
+        }
+        let only_zero = PasswordPolicy { length: 3, ..only_zero };
+        assert_eq!(only_zero.generate().unwrap(), "000");
+    }
+}
@@ -0,0 +1,130 @@
+//! Bringing in entries exported from other password managers.
+//!
+//! Each importer turns an export into [`ImportedEntries`]; records it cannot
+//! use are kept with the reason rather than failing the whole import.
+//! [`PasswordManager::import`](crate::PasswordManager::import) then adds the
+//! entries, leaving out any that would duplicate an existing one, and can
+//! do a dry run that only reports what would happen.
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::audit::EntryRef;
+use crate::entry::{CustomField, FieldKind, PasswordEntry};
+use crate::otp::OtpSecret;
+
+/// What an importer read from an export.
+#[derive(Clone, Default)]
+pub struct ImportedEntries {
+    pub entries: Vec<PasswordEntry>,
+    /// Folders to create even if no entry ends up in them.
+    pub folders: Vec<String>,
+    pub skipped: Vec<SkippedRecord>,
+    pub unmapped: Vec<UnmappedField>,
+}
+
+/// A record of the export that was not imported.
+#[derive(Clone, Debug, Serialize)]
+pub struct SkippedRecord {
+    /// Where in the export it is, such as `line 12` or an item title.
+    pub location: String,
+    pub reason: String,
+}
+
+/// Part of an imported item that no entry field can hold, such as a
+/// passkey or an attached file. The rest of the item is still imported.
+#[derive(Clone, Debug, Serialize)]
+pub struct UnmappedField {
+    /// The item's title in the export.
+    pub item: String,
+    /// What was left behind, such as `passkey` or `attachment scan.pdf`.
+    pub field: String,
+}
+
+/// An imported entry left out because the vault (or an earlier record of
+/// the same export) already has an entry for that service and username.
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateRecord {
+    pub label: String,
+    /// The entry already holding the service and username.
+    pub existing: Uuid,
+    pub same_password: bool,
+}
+
+/// Outcome of [`PasswordManager::import`](crate::PasswordManager::import).
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportReport {
+    /// Nothing was changed; `imported` lists what would have been added.
+    pub dry_run: bool,
+    pub imported: Vec<EntryRef>,
+    pub duplicates: Vec<DuplicateRecord>,
+    pub skipped: Vec<SkippedRecord>,
+    pub unmapped: Vec<UnmappedField>,
+}
+
+impl SkippedRecord {
+    pub(crate) fn new(location: impl Into<String>, reason: impl Into<String>) -> SkippedRecord {
+        SkippedRecord {
+            location: location.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl UnmappedField {
+    pub(crate) fn new(item: impl Into<String>, field: impl Into<String>) -> UnmappedField {
+        UnmappedField {
+            item: item.into(),
+            field: field.into(),
+        }
+    }
+}
+
+// A service name for entries that only come with a URL:
+// "https://accounts.example.com/login" becomes "accounts.example.com".
+pub(crate) fn host_of(url: &str) -> String {
+    let url = url.trim();
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    let host = host.split(':').next().unwrap_or(host);
+    host.strip_prefix("www.").unwrap_or(host).to_lowercase()
+}
+
+// Accepts milliseconds since the epoch (Firefox), RFC 3339 and
+// "YYYY-MM-DD HH:MM:SS" in UTC.
+pub(crate) fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(millis) = value.parse::<i64>() {
+        return Utc.timestamp_millis_opt(millis).single();
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+// An `otpauth://` URI or a bare base32 secret. Anything else is kept as a
+// hidden custom field so that nothing is lost.
+pub(crate) fn set_otp(entry: &mut PasswordEntry, value: &str) {
+    let value = value.trim();
+    if value.is_empty() {
+        return;
+    }
+    let otp = if value.starts_with("otpauth:") {
+        OtpSecret::from_uri(value)
+    } else {
+        OtpSecret::totp(value)
+    };
+    match otp {
+        Ok(otp) => entry.otp = Some(otp),
+        Err(_) => entry.fields.push(CustomField {
+            name: "TOTP".to_string(),
+            kind: FieldKind::Hidden,
+            value: value.to_string(),
+        }),
+    }
+}
@@ -0,0 +1,1339 @@
+//! KeePass KDBX 4 databases, as written by KeePassXC and KeePass 2.
+//!
+//! Reading supports the Argon2d, Argon2id and AES key derivations, AES-256
+//! and ChaCha20 encryption, gzip compression and both inner stream ciphers
+//! for protected values. Databases are written as KDBX 4.0 with Argon2id.
+//! Key files are not supported.
+//!
+//! Groups map to folders (the root group itself is not one), strings other
+//! than the standard five to custom fields, and the `otp` string to the
+//! entry's two-factor secret. KDBX history keeps whole snapshots of an
+//! entry; only the passwords in them are carried over. Attachments are
+//! reported as unmapped.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, TimeZone, Utc};
+use crypto::aes::{self, KeySize};
+use crypto::aessafe::AesSafe256Encryptor;
+use crypto::blockmodes::PkcsPadding;
+use crypto::buffer::{BufferResult, ReadBuffer, RefReadBuffer, RefWriteBuffer, WriteBuffer};
+use crypto::chacha20::ChaCha20;
+use crypto::digest::Digest;
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::salsa20::Salsa20;
+use crypto::sha2::{Sha256, Sha512};
+use crypto::symmetriccipher::{BlockEncryptor, SynchronousStreamCipher};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use quick_xml::events::Event;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use uuid::Uuid;
+
+use crate::entry::{CustomField, FieldKind, PasswordEntry, PreviousPassword};
+use crate::error::{Result, VaultError};
+use crate::folder;
+use crate::import::{self, ImportedEntries, SkippedRecord, UnmappedField};
+use crate::kdf::{self, MAX_ITERATIONS};
+use crate::otp::{OtpKind, OtpSecret};
+
+const SIGNATURE: [u8; 8] = [0x03, 0xd9, 0xa2, 0x9a, 0x67, 0xfb, 0x4b, 0xb5];
+const VERSION_4_0: u32 = 0x0004_0000;
+
+const CIPHER_AES256: [u8; 16] = uuid_bytes(0x31c1f2e6_bf71_4350_be58_05216afc5aff);
+const CIPHER_CHACHA20: [u8; 16] = uuid_bytes(0xd6038a2b_8b6f_4cb5_a524_339a31dbb59a);
+const KDF_AES: [u8; 16] = uuid_bytes(0xc9d9f39a_628a_4460_bf74_0d08c18a4fea);
+const KDF_ARGON2D: [u8; 16] = uuid_bytes(0xef636ddf_8c29_444b_91f7_a9a403e30a0c);
+const KDF_ARGON2ID: [u8; 16] = uuid_bytes(0x9e298b19_56db_4773_b23d_fc3ec6f0a1e6);
+
+// Outer header field IDs.
+const END_OF_HEADER: u8 = 0;
+const CIPHER_ID: u8 = 2;
+const COMPRESSION_FLAGS: u8 = 3;
+const MASTER_SEED: u8 = 4;
+const ENCRYPTION_IV: u8 = 7;
+const KDF_PARAMETERS: u8 = 11;
+
+// Inner header field IDs and inner stream ciphers.
+const INNER_STREAM_ID: u8 = 1;
+const INNER_STREAM_KEY: u8 = 2;
+const SALSA20: u32 = 2;
+const CHACHA20: u32 = 3;
+const SALSA20_NONCE: [u8; 8] = [0xe8, 0x30, 0x09, 0x4b, 0x97, 0x20, 0x5d, 0x2a];
+
+const BLOCK_SIZE: usize = 1024 * 1024;
+// Seconds from 0001-01-01, where KDBX times count from, to the Unix epoch.
+const EPOCH_OFFSET: i64 = 62_135_596_800;
+
+// Our own extras, kept in the entry's CustomData so a round trip through
+// KeePass loses nothing.
+const FIELD_KIND_PREFIX: &str = "PasswordManager.FieldKind.";
+const FIELD_NAME_PREFIX: &str = "PasswordManager.FieldName.";
+const REPLACED_KEY: &str = "PasswordManager.Replaced";
+
+// Strings the reader takes to mean something other than a custom field.
+const RESERVED_STRINGS: [&str; 8] = ["Title", "UserName", "Password", "URL", "Notes", "otp", "TOTP Seed", "TOTP Settings"];
+const EXTRA_URL_PREFIX: &str = "KP2A_URL";
+
+const fn uuid_bytes(value: u128) -> [u8; 16] {
+    value.to_be_bytes()
+}
+
+/// How [`write_kdbx`] encrypts a database. Keys are always derived with
+/// Argon2id; the default costs match [`KdfParams::default_for`](crate::KdfParams::default_for).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdbxOptions {
+    pub cipher: KdbxCipher,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KdbxCipher {
+    Aes256,
+    ChaCha20,
+}
+
+impl Default for KdbxOptions {
+    fn default() -> KdbxOptions {
+        KdbxOptions {
+            cipher: KdbxCipher::Aes256,
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+/// Whether `data` starts like a KeePass database of any version.
+pub fn is_kdbx(data: &[u8]) -> bool {
+    data.starts_with(&SIGNATURE)
+}
+
+/// Decrypts a KDBX 4 database. A wrong password is reported as
+/// [`VaultError::WrongPassword`].
+pub fn read_kdbx(data: &[u8], password: &str) -> Result<ImportedEntries> {
+    let mut reader = ByteReader::new(data);
+    if reader.take(8)? != SIGNATURE {
+        return Err(failed("not a KeePass database"));
+    }
+    let version = reader.u32()?;
+    if version >> 16 != 4 {
+        return Err(failed(&format!("KDBX {}.{} is not supported, only KDBX 4", version >> 16, version & 0xffff)));
+    }
+    let header = OuterHeader::read(&mut reader)?;
+    let header_bytes = &data[..reader.pos];
+    let header_sha = reader.take(32)?;
+    let header_mac = reader.take(32)?;
+    if sha256(&[header_bytes]) != header_sha {
+        return Err(failed("header checksum mismatch"));
+    }
+
+    let keys = Keys::derive(password, &header.master_seed, &header.kdf)?;
+    if keys.header_mac(header_bytes) != header_mac {
+        return Err(VaultError::WrongPassword);
+    }
+    let encrypted = read_blocks(&mut reader, &keys)?;
+    let mut payload = decrypt(header.cipher, &keys.cipher_key, &header.iv, &encrypted)?;
+    if header.compressed {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&payload[..])
+            .read_to_end(&mut decompressed)
+            .map_err(|e| failed(&format!("cannot decompress: {}", e)))?;
+        payload = decompressed;
+    }
+
+    let mut inner = ByteReader::new(&payload);
+    let mut stream = read_inner_header(&mut inner)?;
+    let document = parse_xml(&payload[inner.pos..], &mut stream)?;
+    Ok(to_entries(&document))
+}
+
+/// Encrypts `entries` into a new KDBX 4.0 database. `folders` become
+/// groups, including ones without entries.
+///
+/// Panics if the Argon2 costs in `options` are out of range.
+pub fn write_kdbx(entries: &[&PasswordEntry], folders: &[&str], password: &str, options: &KdbxOptions) -> Vec<u8> {
+    let params = argon2::Params::new(options.memory_kib, options.iterations, options.parallelism, Some(32))
+        .expect("valid Argon2 parameters");
+
+    let kdf = Kdf::Argon2 {
+        algorithm: argon2::Algorithm::Argon2id,
+        version: argon2::Version::V0x13,
+        salt: random_bytes(32),
+        params,
+    };
+    let header = OuterHeader {
+        cipher: options.cipher,
+        compressed: true,
+        master_seed: random_bytes(32),
+        iv: random_bytes(match options.cipher {
+            KdbxCipher::Aes256 => 16,
+            KdbxCipher::ChaCha20 => 12,
+        }),
+        kdf,
+    };
+    let mut out = Vec::new();
+    out.extend_from_slice(&SIGNATURE);
+    out.extend_from_slice(&VERSION_4_0.to_le_bytes());
+    header.write(&mut out);
+    let keys = Keys::derive(password, &header.master_seed, &header.kdf).expect("parameters were checked above");
+    let header_sha = sha256(&[&out]);
+    let header_mac = keys.header_mac(&out);
+    out.extend_from_slice(&header_sha);
+    out.extend_from_slice(&header_mac);
+
+    let stream_key = random_bytes(64);
+    let mut payload = Vec::new();
+    write_field(&mut payload, INNER_STREAM_ID, &CHACHA20.to_le_bytes());
+    write_field(&mut payload, INNER_STREAM_KEY, &stream_key);
+    write_field(&mut payload, END_OF_HEADER, &[]);
+    let mut stream = InnerStream::new(CHACHA20, &stream_key).expect("ChaCha20 is supported");
+    payload.extend(to_xml(entries, folders, &mut stream).into_bytes());
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(&payload).expect("writing to memory");
+    let compressed = gzip.finish().expect("writing to memory");
+    let encrypted = encrypt(header.cipher, &keys.cipher_key, &header.iv, &compressed);
+    write_blocks(&mut out, &encrypted, &keys);
+    out
+}
+
+fn failed(reason: &str) -> VaultError {
+    VaultError::ImportFailed(reason.to_string())
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut sha = Sha256::new();
+    parts.iter().for_each(|part| sha.input(part));
+    let mut out = [0u8; 32];
+    sha.result(&mut out);
+    out
+}
+
+fn sha512(parts: &[&[u8]]) -> [u8; 64] {
+    let mut sha = Sha512::new();
+    parts.iter().for_each(|part| sha.input(part));
+    let mut out = [0u8; 64];
+    sha.result(&mut out);
+    out
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = Hmac::new(Sha256::new(), key);
+    parts.iter().for_each(|part| mac.input(part));
+    let mut out = [0u8; 32];
+    mac.raw_result(&mut out);
+    out
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> ByteReader<'a> {
+        ByteReader { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len()).ok_or_else(|| failed("file is truncated"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().expect("4 bytes")))
+    }
+
+    // A header field: one-byte ID, 32-bit length, data.
+    fn field(&mut self) -> Result<(u8, &'a [u8])> {
+        let id = self.u8()?;
+        let len = self.u32()? as usize;
+        Ok((id, self.take(len)?))
+    }
+}
+
+fn write_field(out: &mut Vec<u8>, id: u8, data: &[u8]) {
+    out.push(id);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+enum Kdf {
+    Aes {
+        rounds: u64,
+        seed: Vec<u8>,
+    },
+    Argon2 {
+        algorithm: argon2::Algorithm,
+        version: argon2::Version,
+        salt: Vec<u8>,
+        params: argon2::Params,
+    },
+}
+
+struct OuterHeader {
+    cipher: KdbxCipher,
+    compressed: bool,
+    master_seed: Vec<u8>,
+    iv: Vec<u8>,
+    kdf: Kdf,
+}
+
+impl OuterHeader {
+    fn read(reader: &mut ByteReader) -> Result<OuterHeader> {
+        let (mut cipher, mut compressed, mut master_seed, mut iv, mut kdf) = (None, false, None, None, None);
+        loop {
+            let (id, data) = reader.field()?;
+            match id {
+                END_OF_HEADER => break,
+                CIPHER_ID if data == CIPHER_AES256 => cipher = Some(KdbxCipher::Aes256),
+                CIPHER_ID if data == CIPHER_CHACHA20 => cipher = Some(KdbxCipher::ChaCha20),
+                CIPHER_ID => return Err(failed("unsupported cipher (only AES-256 and ChaCha20 are)")),
+                COMPRESSION_FLAGS => compressed = data.first().is_some_and(|&flag| flag != 0),
+                MASTER_SEED => master_seed = Some(data.to_vec()),
+                ENCRYPTION_IV => iv = Some(data.to_vec()),
+                KDF_PARAMETERS => kdf = Some(read_kdf(data)?),
+                _ => {}
+            }
+        }
+        let missing = |name: &str| failed(&format!("header has no {}", name));
+        Ok(OuterHeader {
+            cipher: cipher.ok_or_else(|| missing("cipher"))?,
+            compressed,
+            master_seed: master_seed.ok_or_else(|| missing("master seed"))?,
+            iv: iv.ok_or_else(|| missing("encryption IV"))?,
+            kdf: kdf.ok_or_else(|| missing("key derivation parameters"))?,
+        })
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        let cipher = match self.cipher {
+            KdbxCipher::Aes256 => CIPHER_AES256,
+            KdbxCipher::ChaCha20 => CIPHER_CHACHA20,
+        };
+        write_field(out, CIPHER_ID, &cipher);
+        write_field(out, COMPRESSION_FLAGS, &u32::from(self.compressed).to_le_bytes());
+        write_field(out, MASTER_SEED, &self.master_seed);
+        write_field(out, ENCRYPTION_IV, &self.iv);
+        write_field(out, KDF_PARAMETERS, &write_kdf(&self.kdf));
+        write_field(out, END_OF_HEADER, b"\r\n\r\n");
+    }
+}
+
+// KDF parameters are a "variant dictionary": typed, named values.
+const VARIANT_VERSION: u16 = 0x0100;
+const VARIANT_U32: u8 = 0x04;
+const VARIANT_U64: u8 = 0x05;
+const VARIANT_BYTES: u8 = 0x42;
+
+fn read_kdf(data: &[u8]) -> Result<Kdf> {
+    let mut reader = ByteReader::new(data);
+    let version = u16::from_le_bytes(reader.take(2)?.try_into().expect("2 bytes"));
+    if version >> 8 != VARIANT_VERSION >> 8 {
+        return Err(failed("unsupported KDF parameter format"));
+    }
+    let mut values: BTreeMap<String, &[u8]> = BTreeMap::new();
+    loop {
+        let kind = reader.u8()?;
+        if kind == 0 {
+            break;
+        }
+        let key_len = reader.u32()? as usize;
+        let key = String::from_utf8_lossy(reader.take(key_len)?).into_owned();
+        let value_len = reader.u32()? as usize;
+        values.insert(key, reader.take(value_len)?);
+    }
+
+    let bytes = |key: &str| values.get(key).copied().ok_or_else(|| failed(&format!("KDF parameter {} is missing", key)));
+    let number = |key: &str| -> Result<u64> {
+        match bytes(key)? {
+            value if value.len() == 4 => Ok(u64::from(u32::from_le_bytes(value.try_into().expect("4 bytes")))),
+            value if value.len() == 8 => Ok(u64::from_le_bytes(value.try_into().expect("8 bytes"))),
+            _ => Err(failed(&format!("KDF parameter {} is not a number", key))),
+        }
+    };
+    // The header is only authenticated once the key is derived, so costs
+    // are held to the vault's own ceilings first.
+    let uuid = bytes("$UUID")?;
+    if uuid == KDF_AES {
+        let rounds = number("R")?;
+        if rounds > u64::from(MAX_ITERATIONS) {
+            return Err(failed("AES-KDF rounds are out of range"));
+        }
+        return Ok(Kdf::Aes {
+            rounds,
+            seed: bytes("S")?.to_vec(),
+        });
+    }
+    let algorithm = if uuid == KDF_ARGON2D {
+        argon2::Algorithm::Argon2d
+    } else if uuid == KDF_ARGON2ID {
+        argon2::Algorithm::Argon2id
+    } else {
+        return Err(failed("unsupported key derivation function"));
+    };
+    if values.get("K").is_some_and(|secret| !secret.is_empty()) || values.get("A").is_some_and(|data| !data.is_empty()) {
+        return Err(failed("Argon2 secret keys and associated data are not supported"));
+    }
+    let version = match number("V")? {
+        0x10 => argon2::Version::V0x10,
+        0x13 => argon2::Version::V0x13,
+        _ => return Err(failed("unsupported Argon2 version")),
+    };
+    let too_large = || failed("Argon2 parameters are out of range");
+    let memory_kib = u32::try_from(number("M")? / 1024).map_err(|_| too_large())?;
+    let iterations = u32::try_from(number("I")?).map_err(|_| too_large())?;
+    let parallelism = u32::try_from(number("P")?).map_err(|_| too_large())?;
+    if !kdf::argon2_is_usable(memory_kib, iterations, parallelism) {
+        return Err(too_large());
+    }
+    let params = argon2::Params::new(memory_kib, iterations, parallelism, Some(32)).map_err(|_| too_large())?;
+    Ok(Kdf::Argon2 {
+        algorithm,
+        version,
+        salt: bytes("S")?.to_vec(),
+        params,
+    })
+}
+
+fn write_kdf(kdf: &Kdf) -> Vec<u8> {
+    let mut out = VARIANT_VERSION.to_le_bytes().to_vec();
+    let mut put = |kind: u8, key: &str, value: &[u8]| {
+        out.push(kind);
+        out.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        out.extend_from_slice(key.as_bytes());
+        out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        out.extend_from_slice(value);
+    };
+    match kdf {
+        Kdf::Aes { rounds, seed } => {
+            put(VARIANT_BYTES, "$UUID", &KDF_AES);
+            put(VARIANT_U64, "R", &rounds.to_le_bytes());
+            put(VARIANT_BYTES, "S", seed);
+        }
+        Kdf::Argon2 { algorithm, version, salt, params } => {
+            let uuid = match algorithm {
+                argon2::Algorithm::Argon2d => KDF_ARGON2D,
+                _ => KDF_ARGON2ID,
+            };
+            put(VARIANT_BYTES, "$UUID", &uuid);
+            put(VARIANT_BYTES, "S", salt);
+            put(VARIANT_U32, "P", &params.p_cost().to_le_bytes());
+            put(VARIANT_U64, "M", &(u64::from(params.m_cost()) * 1024).to_le_bytes());
+            put(VARIANT_U64, "I", &u64::from(params.t_cost()).to_le_bytes());
+            put(VARIANT_U32, "V", &(*version as u32).to_le_bytes());
+        }
+    }
+    out.push(0);
+    out
+}
+
+struct Keys {
+    cipher_key: [u8; 32],
+    hmac_key: [u8; 64],
+}
+
+impl Keys {
+    fn derive(password: &str, master_seed: &[u8], kdf: &Kdf) -> Result<Keys> {
+        let composite = sha256(&[&sha256(&[password.as_bytes()])]);
+        let mut transformed = [0u8; 32];
+        match kdf {
+            Kdf::Aes { rounds, seed } => {
+                let seed: &[u8; 32] = seed.as_slice().try_into().map_err(|_| failed("AES-KDF seed must be 32 bytes"))?;
+                let aes = AesSafe256Encryptor::new(seed);
+                transformed = composite;
+                let mut block = [0u8; 16];
+                for _ in 0..*rounds {
+                    for half in transformed.chunks_exact_mut(16) {
+                        aes.encrypt_block(half, &mut block);
+                        half.copy_from_slice(&block);
+                    }
+                }
+                transformed = sha256(&[&transformed]);
+            }
+            Kdf::Argon2 { algorithm, version, salt, params } => {
+                argon2::Argon2::new(*algorithm, *version, params.clone())
+                    .hash_password_into(&composite, salt, &mut transformed)
+                    .map_err(|e| failed(&format!("key derivation failed: {}", e)))?;
+            }
+        }
+        Ok(Keys {
+            cipher_key: sha256(&[master_seed, &transformed]),
+            hmac_key: sha512(&[master_seed, &transformed, &[1]]),
+        })
+    }
+
+    fn block_key(&self, index: u64) -> [u8; 64] {
+        sha512(&[&index.to_le_bytes(), &self.hmac_key])
+    }
+
+    fn header_mac(&self, header: &[u8]) -> [u8; 32] {
+        hmac_sha256(&self.block_key(u64::MAX), &[header])
+    }
+}
+
+// The encrypted payload comes in blocks, each with its own HMAC, ending
+// with an empty block.
+fn read_blocks(reader: &mut ByteReader, keys: &Keys) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    for index in 0u64.. {
+        let mac = reader.take(32)?;
+        let len_bytes = reader.take(4)?;
+        let len = u32::from_le_bytes(len_bytes.try_into().expect("4 bytes")) as usize;
+        let block = reader.take(len)?;
+        if hmac_sha256(&keys.block_key(index), &[&index.to_le_bytes(), len_bytes, block]) != mac {
+            return Err(failed(&format!("block {} fails authentication", index)));
+        }
+        if len == 0 {
+            break;
+        }
+        data.extend_from_slice(block);
+    }
+    Ok(data)
+}
+
+fn write_blocks(out: &mut Vec<u8>, data: &[u8], keys: &Keys) {
+    let blocks = data.chunks(BLOCK_SIZE).chain(std::iter::once(&[][..]));
+    for (index, block) in (0u64..).zip(blocks) {
+        let len = (block.len() as u32).to_le_bytes();
+        out.extend_from_slice(&hmac_sha256(&keys.block_key(index), &[&index.to_le_bytes(), &len, block]));
+        out.extend_from_slice(&len);
+        out.extend_from_slice(block);
+    }
+}
+
+fn decrypt(cipher: KdbxCipher, key: &[u8; 32], iv: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    match cipher {
+        KdbxCipher::ChaCha20 => {
+            let mut out = vec![0u8; data.len()];
+            chacha20(key, iv)?.process(data, &mut out);
+            Ok(out)
+        }
+        KdbxCipher::Aes256 => {
+            if iv.len() != 16 {
+                return Err(failed("AES IV must be 16 bytes"));
+            }
+            let mut decryptor = aes::cbc_decryptor(KeySize::KeySize256, key, iv, PkcsPadding);
+            run_buffered(data, |input, output| decryptor.decrypt(input, output, true))
+                .map_err(|_| failed("cannot decrypt the database"))
+        }
+    }
+}
+
+fn encrypt(cipher: KdbxCipher, key: &[u8; 32], iv: &[u8], data: &[u8]) -> Vec<u8> {
+    match cipher {
+        KdbxCipher::ChaCha20 => {
+            let mut out = vec![0u8; data.len()];
+            ChaCha20::new(key, iv).process(data, &mut out);
+            out
+        }
+        KdbxCipher::Aes256 => {
+            let mut encryptor = aes::cbc_encryptor(KeySize::KeySize256, key, iv, PkcsPadding);
+            run_buffered(data, |input, output| encryptor.encrypt(input, output, true)).expect("encryption with padding cannot fail")
+        }
+    }
+}
+
+fn chacha20(key: &[u8], nonce: &[u8]) -> Result<ChaCha20> {
+    if nonce.len() != 12 {
+        return Err(failed("ChaCha20 nonce must be 12 bytes"));
+    }
+    Ok(ChaCha20::new(key, nonce))
+}
+
+// Drives rust-crypto's buffer-based block cipher modes over all of `data`.
+fn run_buffered<E>(
+    data: &[u8],
+    mut step: impl FnMut(&mut RefReadBuffer, &mut RefWriteBuffer) -> std::result::Result<BufferResult, E>,
+) -> std::result::Result<Vec<u8>, E> {
+    let mut out = Vec::with_capacity(data.len() + 16);
+    let mut input = RefReadBuffer::new(data);
+    let mut buffer = [0u8; 4096];
+    loop {
+        let mut output = RefWriteBuffer::new(&mut buffer);
+        let result = step(&mut input, &mut output)?;
+        out.extend_from_slice(output.take_read_buffer().take_remaining());
+        if let BufferResult::BufferUnderflow = result {
+            return Ok(out);
+        }
+    }
+}
+
+// Protected values in the XML are XORed with one continuous key stream, in
+// document order.
+enum InnerStream {
+    ChaCha20(ChaCha20),
+    Salsa20(Salsa20),
+}
+
+impl InnerStream {
+    fn new(id: u32, key: &[u8]) -> Result<InnerStream> {
+        match id {
+            CHACHA20 => {
+                let hash = sha512(&[key]);
+                Ok(InnerStream::ChaCha20(ChaCha20::new(&hash[..32], &hash[32..44])))
+            }
+            SALSA20 => Ok(InnerStream::Salsa20(Salsa20::new(&sha256(&[key]), &SALSA20_NONCE))),
+            _ => Err(failed("unsupported protected value cipher")),
+        }
+    }
+
+    fn apply(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0u8; data.len()];
+        match self {
+            InnerStream::ChaCha20(cipher) => cipher.process(data, &mut out),
+            InnerStream::Salsa20(cipher) => cipher.process(data, &mut out),
+        }
+        out
+    }
+}
+
+fn read_inner_header(reader: &mut ByteReader) -> Result<InnerStream> {
+    let (mut id, mut key) = (None, None);
+    loop {
+        match reader.field()? {
+            (END_OF_HEADER, _) => break,
+            (INNER_STREAM_ID, data) if data.len() == 4 => id = Some(u32::from_le_bytes(data.try_into().expect("4 bytes"))),
+            (INNER_STREAM_KEY, data) => key = Some(data),
+            // Attachments (3) are not imported.
+            _ => {}
+        }
+    }
+    match (id, key) {
+        (Some(id), Some(key)) => InnerStream::new(id, key),
+        _ => Err(failed("inner header has no stream cipher")),
+    }
+}
+
+// Just enough of a DOM for the KeePass XML schema.
+#[derive(Default)]
+struct Node {
+    name: String,
+    protected: bool,
+    text: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn text_of(&self, name: &str) -> &str {
+        self.child(name).map_or("", |child| child.text.as_str())
+    }
+
+    // Children with that name in this group and all groups below it, but
+    // not the snapshots inside entries.
+    fn descendants_named<'a>(&'a self, name: &'a str) -> Vec<&'a Node> {
+        let mut found: Vec<&Node> = self.children_named(name).collect();
+        for group in self.children_named("Group") {
+            found.extend(group.descendants_named(name));
+        }
+        found
+    }
+}
+
+// Builds the tree, decrypting protected values as they are met so the key
+// stream stays in step.
+fn parse_xml(xml: &[u8], stream: &mut InnerStream) -> Result<Node> {
+    let invalid = |e: &dyn std::fmt::Display| failed(&format!("invalid XML: {}", e));
+    let mut reader = quick_xml::Reader::from_reader(xml);
+    let mut stack = vec![Node::default()];
+    let mut buf = Vec::new();
+    loop {
+        let event = reader.read_event_into(&mut buf).map_err(|e| invalid(&e))?;
+        match event {
+            Event::Start(ref start) | Event::Empty(ref start) => {
+                let mut node = Node {
+                    name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+                    ..Node::default()
+                };
+                for attribute in start.attributes() {
+                    let attribute = attribute.map_err(|e| invalid(&e))?;
+                    if attribute.key.as_ref() == b"Protected" {
+                        node.protected = attribute.unescape_value().map_err(|e| invalid(&e))?.eq_ignore_ascii_case("true");
+                    }
+                }
+                if matches!(event, Event::Start(_)) {
+                    stack.push(node);
+                } else {
+                    stack.last_mut().expect("root").children.push(node);
+                }
+            }
+            Event::Text(ref text) => {
+                let text = text.unescape().map_err(|e| invalid(&e))?;
+                stack.last_mut().expect("root").text.push_str(&text);
+            }
+            Event::CData(ref data) => {
+                stack.last_mut().expect("root").text.push_str(&String::from_utf8_lossy(data));
+            }
+            Event::End(_) => {
+                let mut node = stack.pop().expect("balanced by the parser");
+                if node.protected {
+                    let cipher = BASE64.decode(node.text.trim()).map_err(|e| invalid(&e))?;
+                    node.text = String::from_utf8(stream.apply(&cipher)).map_err(|e| invalid(&e))?;
+                }
+                stack.last_mut().ok_or_else(|| failed("invalid XML: unbalanced tags"))?.children.push(node);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    let document = stack.pop().filter(|_| stack.is_empty()).ok_or_else(|| failed("invalid XML: unclosed tags"))?;
+    if document.child("KeePassFile").is_none() {
+        return Err(failed("not a KeePass XML document"));
+    }
+    Ok(document)
+}
+
+fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(bytes) = BASE64.decode(text) {
+        if let Ok(bytes) = <[u8; 8]>::try_from(bytes.as_slice()) {
+            return Utc.timestamp_opt(i64::from_le_bytes(bytes) - EPOCH_OFFSET, 0).single();
+        }
+    }
+    // KDBX 3 wrote times as text.
+    DateTime::parse_from_rfc3339(text).ok().map(|time| time.with_timezone(&Utc))
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    BASE64.encode((time.timestamp() + EPOCH_OFFSET).to_le_bytes())
+}
+
+fn to_entries(document: &Node) -> ImportedEntries {
+    let file = document.child("KeePassFile").expect("checked while parsing");
+    let meta = file.child("Meta");
+    let recycle_bin = meta
+        .filter(|meta| !meta.text_of("RecycleBinEnabled").eq_ignore_ascii_case("false"))
+        .map(|meta| meta.text_of("RecycleBinUUID").trim().to_string())
+        .filter(|uuid| !uuid.is_empty() && uuid != "AAAAAAAAAAAAAAAAAAAAAA==");
+
+    let mut imported = ImportedEntries::default();
+    if let Some(root) = file.child("Root").and_then(|root| root.child("Group")) {
+        read_group(root, "", recycle_bin.as_deref(), &mut imported);
+    }
+    imported
+}
+
+fn read_group(group: &Node, path: &str, recycle_bin: Option<&str>, imported: &mut ImportedEntries) {
+    for entry in group.children_named("Entry") {
+        let title = entry_strings(entry).get("Title").map(|(value, _)| value.clone()).unwrap_or_default();
+        match read_entry(entry, path, &mut imported.unmapped) {
+            Ok(read) => {
+                for attachment in entry.children_named("Binary") {
+                    imported.unmapped.push(UnmappedField::new(&title, format!("attachment {}", attachment.text_of("Key"))));
+                }
+                imported.entries.push(read);
+            }
+            Err(reason) => imported.skipped.push(SkippedRecord::new(format!("entry '{}'", title), reason)),
+        }
+    }
+    for subgroup in group.children_named("Group") {
+        let name = subgroup.text_of("Name").trim();
+        if recycle_bin == Some(subgroup.text_of("UUID").trim()) {
+            for entry in subgroup.descendants_named("Entry") {
+                let title = entry_strings(entry).get("Title").map(|(value, _)| value.clone()).unwrap_or_default();
+                imported.skipped.push(SkippedRecord::new(format!("entry '{}'", title), "in the recycle bin"));
+            }
+            continue;
+        }
+        let subpath = folder::normalize(&format!("{}/{}", path, name));
+        imported.folders.push(subpath.clone());
+        read_group(subgroup, &subpath, recycle_bin, imported);
+    }
+}
+
+// Every <String> of an entry: key to (value, protected).
+fn entry_strings(entry: &Node) -> BTreeMap<String, (String, bool)> {
+    entry
+        .children_named("String")
+        .map(|string| {
+            let value = string.child("Value");
+            let protected = value.is_some_and(|value| value.protected);
+            (string.text_of("Key").to_string(), (value.map_or(String::new(), |value| value.text.clone()), protected))
+        })
+        .collect()
+}
+
+fn custom_data(entry: &Node) -> BTreeMap<String, String> {
+    entry
+        .child("CustomData")
+        .map(|data| {
+            data.children_named("Item")
+                .map(|item| (item.text_of("Key").to_string(), item.text_of("Value").to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// KeePassDX and Keepass2Android keep further URLs as KP2A_URL, KP2A_URL_1,
+// KP2A_URL_2 and so on.
+fn is_extra_url(key: &str) -> bool {
+    key.strip_prefix(EXTRA_URL_PREFIX)
+        .is_some_and(|rest| rest.is_empty() || rest.strip_prefix('_').is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())))
+}
+
+fn modified_time(entry: &Node) -> Option<DateTime<Utc>> {
+    entry.child("Times").and_then(|times| parse_time(times.text_of("LastModificationTime")))
+}
+
+fn read_entry(node: &Node, path: &str, unmapped: &mut Vec<UnmappedField>) -> std::result::Result<PasswordEntry, String> {
+    let mut strings = entry_strings(node);
+    let mut take = |key: &str| strings.remove(key).map(|(value, _)| value).unwrap_or_default();
+    let (title, username, password, url, notes) = (take("Title"), take("UserName"), take("Password"), take("URL"), take("Notes"));
+    let otp_uri = take("otp");
+    let (legacy_seed, legacy_settings) = (take("TOTP Seed"), take("TOTP Settings"));
+
+    let mut urls: Vec<String> = Some(url).filter(|url| !url.trim().is_empty()).into_iter().collect();
+    let extra_urls: Vec<String> = strings.keys().filter(|key| is_extra_url(key)).cloned().collect();
+    for key in extra_urls {
+        let (url, _) = strings.remove(&key).expect("key was just listed");
+        urls.push(url);
+    }
+    let service = match title.trim() {
+        "" => urls.first().map(|url| import::host_of(url)).unwrap_or_default(),
+        title => title.to_string(),
+    };
+    if service.is_empty() {
+        return Err("no title or URL".to_string());
+    }
+
+    let mut entry = PasswordEntry::new(service, username, password);
+    if let Some(id) = BASE64.decode(node.text_of("UUID").trim()).ok().and_then(|bytes| Uuid::from_slice(&bytes).ok()) {
+        entry.id = id;
+    }
+    entry.urls = urls;
+    entry.notes = notes;
+    entry.folder = path.to_string();
+    entry.tags = node.text_of("Tags").split([';', ',']).map(str::trim).filter(|tag| !tag.is_empty()).map(String::from).collect();
+
+    let data = custom_data(node);
+    for (key, (value, protected)) in strings {
+        let kind = data
+            .get(&format!("{}{}", FIELD_KIND_PREFIX, key))
+            .and_then(|kind| FieldKind::from_name(kind))
+            .unwrap_or(if protected { FieldKind::Hidden } else { FieldKind::Text });
+        let name = data.get(&format!("{}{}", FIELD_NAME_PREFIX, key)).cloned().unwrap_or(key);
+        entry.fields.push(CustomField { name, kind, value });
+    }
+    if !otp_uri.is_empty() {
+        match OtpSecret::from_uri(&otp_uri) {
+            Ok(otp) => entry.otp = Some(otp),
+            Err(_) => entry.fields.push(CustomField {
+                name: "otp".to_string(),
+                kind: FieldKind::Hidden,
+                value: otp_uri,
+            }),
+        }
+    } else if !legacy_seed.is_empty() {
+        entry.otp = legacy_otp(&legacy_seed, &legacy_settings);
+        if entry.otp.is_none() {
+            // Codes from a good seed with the wrong settings would just be
+            // wrong, so the seed is only kept as a field.
+            if OtpSecret::totp(&legacy_seed).is_ok() {
+                unmapped.push(UnmappedField::new(&title, format!("TOTP settings \"{}\"", legacy_settings)));
+            }
+            entry.fields.push(CustomField {
+                name: "TOTP Seed".to_string(),
+                kind: FieldKind::Hidden,
+                value: legacy_seed,
+            });
+        }
+    }
+
+    if let Some(times) = node.child("Times") {
+        if let Some(created) = parse_time(times.text_of("CreationTime")) {
+            entry.created = created;
+        }
+        if let Some(modified) = parse_time(times.text_of("LastModificationTime")) {
+            entry.modified = modified;
+        }
+        entry.last_used = parse_time(times.text_of("LastAccessTime"));
+    }
+    entry.history = read_history(node, &entry);
+    Ok(entry)
+}
+
+// KeePassXC before 2.6 stored "TOTP Seed" with "TOTP Settings" such as
+// "30;6" (period and digits). Settings we cannot make codes for, like
+// Steam's "30;S" or a zero period, give None.
+fn legacy_otp(seed: &str, settings: &str) -> Option<OtpSecret> {
+    let mut otp = OtpSecret::totp(seed).ok()?;
+    let mut parts = settings.split(';').map(str::trim).filter(|part| !part.is_empty());
+    if let Some(period) = parts.next() {
+        otp.kind = OtpKind::Totp { period: period.parse().ok()? };
+    }
+    if let Some(digits) = parts.next() {
+        otp.digits = digits.parse().ok()?;
+    }
+    otp.validate().ok()?;
+    Some(otp)
+}
+
+// Snapshots are kept oldest first. A password counts as replaced when the
+// next newer snapshot (or the entry itself) was saved, unless we recorded
+// the exact time on export.
+fn read_history(node: &Node, entry: &PasswordEntry) -> Vec<PreviousPassword> {
+    let Some(history) = node.child("History") else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<&Node> = history.children_named("Entry").collect();
+    snapshots.sort_by_key(|snapshot| modified_time(snapshot));
+
+    let mut previous = Vec::new();
+    let mut newer_password = entry.password.as_str();
+    let mut newer_saved = entry.modified;
+    let mut passwords: Vec<(String, DateTime<Utc>, Option<DateTime<Utc>>)> = snapshots
+        .iter()
+        .map(|snapshot| {
+            let password = entry_strings(snapshot).remove("Password").map(|(value, _)| value).unwrap_or_default();
+            let saved = modified_time(snapshot).unwrap_or(entry.created);
+            let replaced = custom_data(snapshot).get(REPLACED_KEY).and_then(|time| DateTime::parse_from_rfc3339(time).ok()).map(|time| time.with_timezone(&Utc));
+            (password, saved, replaced)
+        })
+        .collect();
+    passwords.reverse();
+    for (password, saved, replaced) in &passwords {
+        if password != newer_password {
+            previous.push(PreviousPassword {
+                password: password.clone(),
+                replaced: replaced.unwrap_or(newer_saved),
+            });
+        }
+        newer_password = password;
+        newer_saved = *saved;
+    }
+    previous
+}
+
+fn to_xml(entries: &[&PasswordEntry], folders: &[&str], stream: &mut InnerStream) -> String {
+    let mut xml = Xml {
+        out: String::new(),
+        stream,
+    };
+    xml.out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<KeePassFile>\n<Meta>\n");
+    xml.element("Generator", "password-manager");
+    xml.element("DatabaseName", "Passwords");
+    xml.out.push_str("<MemoryProtection>\n");
+    for (name, protect) in [("ProtectTitle", false), ("ProtectUserName", false), ("ProtectPassword", true), ("ProtectURL", false), ("ProtectNotes", false)] {
+        xml.element(name, if protect { "True" } else { "False" });
+    }
+    xml.out.push_str("</MemoryProtection>\n");
+    xml.element("RecycleBinEnabled", "False");
+    xml.out.push_str("</Meta>\n<Root>\n");
+    write_group(&mut xml, "Root", "", entries, folders);
+    xml.out.push_str("<DeletedObjects/>\n</Root>\n</KeePassFile>\n");
+    xml.out
+}
+
+struct Xml<'a> {
+    out: String,
+    stream: &'a mut InnerStream,
+}
+
+impl Xml<'_> {
+    fn element(&mut self, name: &str, text: &str) {
+        self.out.push_str(&format!("<{0}>{1}</{0}>\n", name, escape(text)));
+    }
+
+    fn string(&mut self, key: &str, value: &str, protected: bool) {
+        self.out.push_str("<String>\n");
+        self.element("Key", key);
+        if protected {
+            let encrypted = BASE64.encode(self.stream.apply(value.as_bytes()));
+            self.out.push_str(&format!("<Value Protected=\"True\">{}</Value>\n", encrypted));
+        } else {
+            self.element("Value", value);
+        }
+        self.out.push_str("</String>\n");
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\r' => escaped.push_str("&#13;"),
+            // Characters XML 1.0 cannot hold at all are dropped.
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_group(xml: &mut Xml, name: &str, path: &str, entries: &[&PasswordEntry], folders: &[&str]) {
+    xml.out.push_str("<Group>\n");
+    xml.element("UUID", &BASE64.encode(Uuid::new_v4().as_bytes()));
+    xml.element("Name", name);
+    xml.element("IconID", if path.is_empty() { "48" } else { "0" });
+    xml.element("IsExpanded", "True");
+    for entry in entries.iter().filter(|entry| entry.folder == path) {
+        write_entry(xml, entry, None);
+    }
+    let children = folders.iter().filter(|folder| match folder.rsplit_once('/') {
+        Some((parent, _)) => parent == path,
+        None => path.is_empty() && !folder.is_empty(),
+    });
+    for child in children {
+        let child_name = child.rsplit('/').next().unwrap_or(child);
+        write_group(xml, child_name, child, entries, folders);
+    }
+    xml.out.push_str("</Group>\n");
+}
+
+// With `snapshot` set, writes the history item for `entry.history[index]`
+// instead of the entry itself.
+fn write_entry(xml: &mut Xml, entry: &PasswordEntry, snapshot: Option<usize>) {
+    xml.out.push_str("<Entry>\n");
+    xml.element("UUID", &BASE64.encode(entry.id.as_bytes()));
+    xml.element("IconID", "0");
+    xml.element("Tags", &entry.tags.join(";"));
+
+    let (password, modified) = match snapshot {
+        // The previous password was set when the one before it was replaced.
+        Some(index) => (
+            entry.history[index].password.as_str(),
+            entry.history.get(index + 1).map_or(entry.created, |older| older.replaced),
+        ),
+        None => (entry.password.as_str(), entry.modified),
+    };
+    xml.out.push_str("<Times>\n");
+    xml.element("CreationTime", &format_time(entry.created));
+    xml.element("LastModificationTime", &format_time(modified));
+    if let Some(last_used) = entry.last_used.filter(|_| snapshot.is_none()) {
+        xml.element("LastAccessTime", &format_time(last_used));
+    }
+    xml.element("Expires", "False");
+    xml.out.push_str("</Times>\n");
+
+    xml.string("Title", &entry.service, false);
+    xml.string("UserName", &entry.username, false);
+    xml.string("Password", password, true);
+    xml.string("URL", entry.urls.first().map_or("", String::as_str), false);
+    for (i, url) in entry.urls.iter().enumerate().skip(1) {
+        xml.string(&format!("{}_{}", EXTRA_URL_PREFIX, i), url, false);
+    }
+    xml.string("Notes", &entry.notes, false);
+    // KeePass keys must be unique, and some have a meaning of their own, so
+    // such fields get a free key like "Title (2)" and keep their name in
+    // CustomData.
+    let mut data: Vec<(String, String)> = Vec::new();
+    let mut keys: Vec<String> = Vec::new();
+    for field in &entry.fields {
+        let mut key = field.name.clone();
+        let mut n = 1;
+        while RESERVED_STRINGS.contains(&key.as_str()) || is_extra_url(&key) || keys.contains(&key) {
+            n += 1;
+            key = format!("{} ({})", field.name, n);
+        }
+        xml.string(&key, &field.value, field.kind == FieldKind::Hidden);
+        if key != field.name {
+            data.push((format!("{}{}", FIELD_NAME_PREFIX, key), field.name.clone()));
+        }
+        if !matches!(field.kind, FieldKind::Text | FieldKind::Hidden) {
+            data.push((format!("{}{}", FIELD_KIND_PREFIX, key), field.kind.to_string()));
+        }
+        keys.push(key);
+    }
+    if let Some(otp) = &entry.otp {
+        xml.string("otp", &otp.to_uri(&entry.label()), true);
+    }
+
+    if let Some(index) = snapshot {
+        data.push((REPLACED_KEY.to_string(), entry.history[index].replaced.to_rfc3339()));
+    }
+    if !data.is_empty() {
+        xml.out.push_str("<CustomData>\n");
+        for (key, value) in data {
+            xml.out.push_str("<Item>\n");
+            xml.element("Key", &key);
+            xml.element("Value", &value);
+            xml.out.push_str("</Item>\n");
+        }
+        xml.out.push_str("</CustomData>\n");
+    }
+
+    if snapshot.is_none() && !entry.history.is_empty() {
+        xml.out.push_str("<History>\n");
+        for index in (0..entry.history.len()).rev() {
+            write_entry(xml, entry, Some(index));
+        }
+        xml.out.push_str("</History>\n");
+    }
+    xml.out.push_str("</Entry>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Both written by tests/fixtures/make_kdbx.py, independently of this module.
+    const ARGON2_CHACHA20: &[u8] = include_bytes!("../tests/fixtures/argon2id-chacha20.kdbx");
+    const AESKDF_AES256: &[u8] = include_bytes!("../tests/fixtures/aeskdf-aes256.kdbx");
+    const PASSWORD: &str = "fixture-password";
+
+    fn cheap(cipher: KdbxCipher) -> KdbxOptions {
+        KdbxOptions {
+            cipher,
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    fn time(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    fn find<'a>(imported: &'a ImportedEntries, service: &str) -> &'a PasswordEntry {
+        imported.entries.iter().find(|entry| entry.service == service).unwrap_or_else(|| panic!("no entry {}", service))
+    }
+
+    fn field<'a>(entry: &'a PasswordEntry, name: &str) -> &'a CustomField {
+        entry.fields.iter().find(|field| field.name == name).unwrap_or_else(|| panic!("no field {}", name))
+    }
+
+    fn check_fixture(imported: &ImportedEntries) {
+        let mut services: Vec<&str> = imported.entries.iter().map(|entry| entry.service.as_str()).collect();
+        services.sort_unstable();
+        assert_eq!(services, ["GitHub", "Mail", "Old TOTP", "example.org"]);
+        assert_eq!(imported.folders, ["Work", "Work/Email", "Work/Empty", "Legacy"]);
+        let mut skipped: Vec<&str> = imported.skipped.iter().map(|skipped| skipped.reason.as_str()).collect();
+        skipped.sort_unstable();
+        assert_eq!(skipped, ["in the recycle bin", "no title or URL"]);
+
+        let github = find(imported, "GitHub");
+        assert_eq!(github.id, Uuid::parse_str("6f0a0d4e-3c1e-4c9a-9a57-2f3c1d7e8b01").unwrap());
+        assert_eq!(github.username, "octocat");
+        assert_eq!(github.password, "current <Pa55>");
+        assert_eq!(github.urls, ["https://github.com/login"]);
+        assert_eq!(github.notes, "Main account\nwith 2FA");
+        assert_eq!(github.folder, "");
+        assert_eq!(github.tags, ["work", "dev"]);
+        assert_eq!(github.created, time("2020-01-01T12:00:00Z"));
+        assert_eq!(github.modified, time("2023-05-01T12:00:00Z"));
+        assert_eq!(github.last_used, Some(time("2024-01-02T12:00:00Z")));
+        let otp = github.otp.as_ref().unwrap();
+        assert_eq!((otp.secret.as_str(), otp.digits), ("JBSWY3DPEHPK3PXP", 8));
+        let recovery = field(github, "Recovery codes");
+        assert_eq!((recovery.kind, recovery.value.as_str()), (FieldKind::Hidden, "1111 2222 3333"));
+        assert_eq!(field(github, "Account ID").kind, FieldKind::Text);
+        assert_eq!(field(github, "Birthday").kind, FieldKind::Date);
+        let history: Vec<(&str, DateTime<Utc>)> = github.history.iter().map(|old| (old.password.as_str(), old.replaced)).collect();
+        assert_eq!(history, [("older-pw", time("2023-05-01T12:00:00Z")), ("oldest-pw", time("2021-03-01T12:00:00Z"))]);
+
+        let mail = find(imported, "Mail");
+        assert_eq!(mail.password, "  spaced out  ");
+        assert_eq!(mail.folder, "Work/Email");
+        assert_eq!(mail.urls, ["https://webmail.example.com", "https://mail.example.com"]);
+        assert!(mail.fields.is_empty());
+
+        assert_eq!(find(imported, "example.org").username, "anon");
+
+        let legacy = find(imported, "Old TOTP");
+        assert_eq!(legacy.folder, "Legacy");
+        let otp = legacy.otp.as_ref().unwrap();
+        assert_eq!((otp.secret.as_str(), otp.digits, otp.kind), ("GEZDGNBVGY3TQOJQ", 8, OtpKind::Totp { period: 60 }));
+        assert!(legacy.fields.is_empty());
+    }
+
+    #[test]
+    fn reads_argon2id_chacha20_fixture() {
+        check_fixture(&read_kdbx(ARGON2_CHACHA20, PASSWORD).unwrap());
+    }
+
+    #[test]
+    fn reads_aeskdf_aes256_fixture() {
+        check_fixture(&read_kdbx(AESKDF_AES256, PASSWORD).unwrap());
+    }
+
+    #[test]
+    fn rejects_wrong_password_and_damage() {
+        assert!(matches!(read_kdbx(ARGON2_CHACHA20, "not it"), Err(VaultError::WrongPassword)));
+        let mut damaged = AESKDF_AES256.to_vec();
+        let last = damaged.len() - 40;
+        damaged[last] ^= 1;
+        assert!(matches!(read_kdbx(&damaged, PASSWORD), Err(VaultError::ImportFailed(_))));
+        assert!(matches!(read_kdbx(b"not a database", PASSWORD), Err(VaultError::ImportFailed(_))));
+    }
+
+    #[test]
+    fn rejects_costly_kdf_parameters() {
+        let argon2 = |memory_kib, iterations| Kdf::Argon2 {
+            algorithm: argon2::Algorithm::Argon2id,
+            version: argon2::Version::V0x13,
+            salt: vec![0; 32],
+            params: argon2::Params::new(memory_kib, iterations, 1, Some(32)).unwrap(),
+        };
+        let aes = |rounds| Kdf::Aes { rounds, seed: vec![0; 32] };
+        for kdf in [argon2(u32::MAX, 1), argon2(4 * 1024 * 1024 + 1, 1), argon2(64, u32::MAX), aes(u64::MAX), aes(u64::from(MAX_ITERATIONS) + 1)] {
+            assert!(matches!(read_kdf(&write_kdf(&kdf)), Err(VaultError::ImportFailed(_))));
+        }
+        assert!(read_kdf(&write_kdf(&argon2(64 * 1024, 3))).is_ok());
+        assert!(read_kdf(&write_kdf(&aes(60_000))).is_ok());
+    }
+
+    #[test]
+    fn fixture_survives_write_and_read() {
+        let original = read_kdbx(ARGON2_CHACHA20, PASSWORD).unwrap();
+        let entries: Vec<&PasswordEntry> = original.entries.iter().collect();
+        let folders: Vec<&str> = original.folders.iter().map(String::as_str).collect();
+        for cipher in [KdbxCipher::Aes256, KdbxCipher::ChaCha20] {
+            let written = write_kdbx(&entries, &folders, "another password", &cheap(cipher));
+            let mut again = read_kdbx(&written, "another password").unwrap();
+            again.skipped = original.skipped.clone();
+            check_fixture(&again);
+        }
+    }
+
+    #[test]
+    fn every_field_round_trips() {
+        let mut entry = PasswordEntry::new("Bank".into(), "jo".into(), "p&ss<word>\t\"quoted\"".into());
+        entry.urls = vec!["https://bank.example".into(), "https://m.bank.example".into(), "https://app.bank.example".into()];
+        entry.notes = "line one\r\nline two".into();
+        entry.folder = "Finance/Banks".into();
+        entry.tags = vec!["money".into(), "2fa".into()];
+        entry.last_used = Some(time("2024-06-01T08:30:00Z"));
+        entry.created = time("2019-02-03T04:05:06Z");
+        entry.modified = time("2024-05-06T07:08:09Z");
+        entry.otp = Some(OtpSecret::from_uri("otpauth://hotp/Bank:jo?secret=GEZDGNBVGY3TQOJQ&counter=7&algorithm=SHA256").unwrap());
+        for (name, kind, value) in [
+            ("PIN", FieldKind::Hidden, "1234"),
+            ("Branch", FieldKind::Text, "Main St"),
+            ("Support", FieldKind::Url, "https://help.bank.example"),
+            ("Contact", FieldKind::Email, "help@bank.example"),
+            ("Opened", FieldKind::Date, "2019-02-03"),
+        ] {
+            entry.fields.push(CustomField {
+                name: name.into(),
+                kind,
+                value: value.into(),
+            });
+        }
+        entry.history = vec![
+            PreviousPassword {
+                password: "second".into(),
+                replaced: time("2024-05-06T07:08:09Z"),
+            },
+            PreviousPassword {
+                password: "first".into(),
+                replaced: time("2021-01-01T00:00:00Z"),
+            },
+        ];
+        let plain = PasswordEntry::new("Plain".into(), String::new(), "x".into());
+
+        let written = write_kdbx(&[&entry, &plain], &["Finance", "Finance/Banks", "Archive"], PASSWORD, &cheap(KdbxCipher::ChaCha20));
+        let read = read_kdbx(&written, PASSWORD).unwrap();
+        assert_eq!(read.folders, ["Finance", "Finance/Banks", "Archive"]);
+        assert!(read.skipped.is_empty());
+        let back = find(&read, "Bank");
+        assert_eq!(back.id, entry.id);
+        assert_eq!((back.username.as_str(), back.password.as_str()), ("jo", entry.password.as_str()));
+        assert_eq!(back.urls, entry.urls);
+        assert_eq!(back.notes, entry.notes);
+        assert_eq!(back.folder, entry.folder);
+        assert_eq!(back.tags, entry.tags);
+        assert_eq!((back.created, back.modified, back.last_used), (entry.created, entry.modified, entry.last_used));
+        assert_eq!(back.otp, entry.otp);
+        let fields = |entry: &PasswordEntry| {
+            let mut fields: Vec<(String, FieldKind, String)> =
+                entry.fields.iter().map(|field| (field.name.clone(), field.kind, field.value.clone())).collect();
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            fields
+        };
+        assert_eq!(fields(back), fields(&entry));
+        let history = |entry: &PasswordEntry| -> Vec<(String, DateTime<Utc>)> {
+            entry.history.iter().map(|old| (old.password.clone(), old.replaced)).collect()
+        };
+        assert_eq!(history(back), history(&entry));
+
+        let plain_back = find(&read, "Plain");
+        assert_eq!((plain_back.folder.as_str(), plain_back.last_used, plain_back.history.len()), ("", None, 0));
+    }
+
+    #[test]
+    fn reserved_and_repeated_field_names_round_trip() {
+        let mut entry = PasswordEntry::new("Passport".into(), String::new(), String::new());
+        for (name, kind, value) in [
+            ("Title", FieldKind::Text, "Dr"),
+            ("Phone", FieldKind::Text, "555-0100"),
+            ("Phone", FieldKind::Text, "555-0199"),
+            ("Title (2)", FieldKind::Text, "taken"),
+            ("otp", FieldKind::Hidden, "not a URI"),
+            ("Notes", FieldKind::Email, "notes@example.com"),
+            ("KP2A_URL_1", FieldKind::Url, "https://example.com"),
+        ] {
+            entry.fields.push(CustomField {
+                name: name.into(),
+                kind,
+                value: value.into(),
+            });
+        }
+
+        let written = write_kdbx(&[&entry], &[], PASSWORD, &cheap(KdbxCipher::Aes256));
+        let back = &read_kdbx(&written, PASSWORD).unwrap().entries[0];
+        assert_eq!((back.service.as_str(), back.notes.as_str(), back.otp.as_ref()), ("Passport", "", None));
+        assert!(back.urls.is_empty());
+        let fields = |entry: &PasswordEntry| {
+            let mut fields: Vec<(String, FieldKind, String)> =
+                entry.fields.iter().map(|field| (field.name.clone(), field.kind, field.value.clone())).collect();
+            fields.sort_by(|a, b| (&a.0, &a.2).cmp(&(&b.0, &b.2)));
+            fields
+        };
+        assert_eq!(fields(back), fields(&entry));
+    }
+
+    #[test]
+    fn unusable_legacy_totp_settings_are_reported() {
+        for settings in ["30;S", "0;6", "30;10"] {
+            // The writer never produces these strings, so the XML is built
+            // by hand.
+            let xml = format!(
+                "<KeePassFile><Root><Group><Entry><String><Key>Title</Key><Value>Steam</Value></String>\
+                 <String><Key>TOTP Seed</Key><Value>GEZDGNBVGY3TQOJQ</Value></String>\
+                 <String><Key>TOTP Settings</Key><Value>{}</Value></String></Entry></Group></Root></KeePassFile>",
+                settings
+            );
+            let mut stream = InnerStream::new(CHACHA20, &[0; 64]).unwrap();
+            let read = to_entries(&parse_xml(xml.as_bytes(), &mut stream).unwrap());
+            let steam = &read.entries[0];
+            assert_eq!(steam.otp, None, "{}", settings);
+            assert_eq!(field(steam, "TOTP Seed").value, "GEZDGNBVGY3TQOJQ");
+            assert_eq!(read.unmapped.len(), 1);
+            assert_eq!(read.unmapped[0].field, format!("TOTP settings \"{}\"", settings));
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Derivation of the vault key from the master password.
+//!
+//! The algorithm and its cost parameters are stored in the vault header next
+//! to a per-vault random salt, so they can be tuned per machine with
+//! [`KdfParams::benchmark`] and changed whenever the vault is re-keyed.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crypto::hmac::Hmac;
+use crypto::pbkdf2::pbkdf2;
+use crypto::scrypt::{scrypt, ScryptParams};
+use crypto::sha2::Sha256;
+
+use crate::error::{Result, VaultError};
+
+pub(crate) const KDF_HEADER_LEN: usize = 13;
+pub(crate) const SALT_LEN: usize = 16;
+pub(crate) const KEY_LEN: usize = 32;
+
+/// How long unlocking should take when parameters are picked by benchmark.
+pub const DEFAULT_UNLOCK_TIME: Duration = Duration::from_millis(1000);
+
+// Ceilings on cost parameters. Vault headers are read before they can be
+// authenticated and imported files come from anywhere, so anything above
+// these is taken for damage or an attack rather than a slow machine.
+pub(crate) const MAX_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+// Argon2 passes times memory, i.e. 16 passes over the largest allowed memory.
+const MAX_ARGON2_WORK_KIB: u64 = 16 * MAX_MEMORY_KIB as u64;
+// PBKDF2 iterations, and AES-KDF rounds in KeePass databases.
+pub(crate) const MAX_ITERATIONS: u32 = 100_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KdfAlgorithm {
+    Argon2id,
+    Scrypt,
+    Pbkdf2Sha256,
+}
+
+impl KdfAlgorithm {
+    /// Parses `argon2id`, `scrypt` or `pbkdf2` (case-insensitive).
+    pub fn from_name(name: &str) -> Option<KdfAlgorithm> {
+        match name.to_ascii_lowercase().as_str() {
+            "argon2id" | "argon2" => Some(KdfAlgorithm::Argon2id),
+            "scrypt" => Some(KdfAlgorithm::Scrypt),
+            "pbkdf2" | "pbkdf2-sha256" => Some(KdfAlgorithm::Pbkdf2Sha256),
+            _ => None,
+        }
+    }
+}
+
+/// A key derivation algorithm together with its cost parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KdfParams {
+    Argon2id { memory_kib: u32, iterations: u32, parallelism: u32 },
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    Pbkdf2Sha256 { iterations: u32 },
+}
+
+impl KdfParams {
+    /// Reasonable fixed parameters for when benchmarking is not wanted.
+    pub fn default_for(algorithm: KdfAlgorithm) -> KdfParams {
+        match algorithm {
+            KdfAlgorithm::Argon2id => KdfParams::Argon2id { memory_kib: 64 * 1024, iterations: 3, parallelism: 1 },
+            KdfAlgorithm::Scrypt => KdfParams::Scrypt { log_n: 17, r: 8, p: 1 },
+            KdfAlgorithm::Pbkdf2Sha256 => KdfParams::Pbkdf2Sha256 { iterations: 600_000 },
+        }
+    }
+
+    pub fn algorithm(&self) -> KdfAlgorithm {
+        match self {
+            KdfParams::Argon2id { .. } => KdfAlgorithm::Argon2id,
+            KdfParams::Scrypt { .. } => KdfAlgorithm::Scrypt,
+            KdfParams::Pbkdf2Sha256 { .. } => KdfAlgorithm::Pbkdf2Sha256,
+        }
+    }
+
+    // Whether the KDF can run with these parameters without panicking and
+    // within the ceilings above.
+    fn is_usable(&self) -> bool {
+        match *self {
+            KdfParams::Argon2id { memory_kib, iterations, parallelism } => argon2_is_usable(memory_kib, iterations, parallelism),
+            // rust-crypto asserts N < 2^(16r); scrypt needs 128 * r * N bytes.
+            KdfParams::Scrypt { log_n, r, p } => {
+                (1..=24).contains(&log_n)
+                    && (1..=32).contains(&r)
+                    && (1..=16).contains(&p)
+                    && u32::from(log_n) < 16 * r
+                    && (128 * u64::from(r)) << log_n <= u64::from(MAX_MEMORY_KIB) * 1024
+            }
+            KdfParams::Pbkdf2Sha256 { iterations } => (1..=MAX_ITERATIONS).contains(&iterations),
+        }
+    }
+
+    pub(crate) fn derive_key(&self, password: &str, salt: &[u8]) -> Result<[u8; KEY_LEN]> {
+        if !self.is_usable() {
+            return Err(VaultError::InvalidKdf(self.to_string()));
+        }
+        let mut key = [0u8; KEY_LEN];
+        match *self {
+            KdfParams::Argon2id { memory_kib, iterations, parallelism } => {
+                let params = argon2::Params::new(memory_kib, iterations, parallelism, Some(KEY_LEN))
+                    .map_err(|e| VaultError::InvalidKdf(e.to_string()))?;
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(password.as_bytes(), salt, &mut key)
+                    .map_err(|e| VaultError::InvalidKdf(e.to_string()))?;
+            }
+            KdfParams::Scrypt { log_n, r, p } => {
+                scrypt(password.as_bytes(), salt, &ScryptParams::new(log_n, r, p), &mut key);
+            }
+            KdfParams::Pbkdf2Sha256 { iterations } => {
+                let mut mac = Hmac::new(Sha256::new(), password.as_bytes());
+                pbkdf2(&mut mac, salt, iterations, &mut key);
+            }
+        }
+        Ok(key)
+    }
+
+    /// Starts from cheap parameters and doubles the cost until a single
+    /// derivation takes at least `target` on this machine, or the cost
+    /// reaches the most a vault accepts.
+    pub fn benchmark(algorithm: KdfAlgorithm, target: Duration) -> KdfParams {
+        let salt = [0u8; SALT_LEN];
+        let mut params = match algorithm {
+            KdfAlgorithm::Argon2id => KdfParams::Argon2id { memory_kib: 8 * 1024, iterations: 3, parallelism: 1 },
+            KdfAlgorithm::Scrypt => KdfParams::Scrypt { log_n: 12, r: 8, p: 1 },
+            KdfAlgorithm::Pbkdf2Sha256 => KdfParams::Pbkdf2Sha256 { iterations: 10_000 },
+        };
+
+        loop {
+            let started = Instant::now();
+            if params.derive_key("benchmark", &salt).is_err() || started.elapsed() >= target {
+                return params;
+            }
+
+            let next = match params {
+                KdfParams::Argon2id { memory_kib, iterations, parallelism } if memory_kib < 1024 * 1024 => {
+                    KdfParams::Argon2id { memory_kib: memory_kib * 2, iterations, parallelism }
+                }
+                KdfParams::Argon2id { memory_kib, iterations, parallelism } => {
+                    KdfParams::Argon2id { memory_kib, iterations: iterations + 1, parallelism }
+                }
+                KdfParams::Scrypt { log_n, r, p } if log_n < 20 => KdfParams::Scrypt { log_n: log_n + 1, r, p },
+                KdfParams::Scrypt { log_n, r, p } => KdfParams::Scrypt { log_n, r, p: p + 1 },
+                KdfParams::Pbkdf2Sha256 { iterations } => KdfParams::Pbkdf2Sha256 { iterations: iterations.saturating_mul(2) },
+            };
+            if !next.is_usable() {
+                return params;
+            }
+            params = next;
+        }
+    }
+
+    pub(crate) fn to_header(self) -> [u8; KDF_HEADER_LEN] {
+        let (id, a, b, c) = match self {
+            KdfParams::Argon2id { memory_kib, iterations, parallelism } => (1u8, memory_kib, iterations, parallelism),
+            KdfParams::Scrypt { log_n, r, p } => (2u8, log_n as u32, r, p),
+            KdfParams::Pbkdf2Sha256 { iterations } => (3u8, iterations, 0, 0),
+        };
+        let mut header = [0u8; KDF_HEADER_LEN];
+        header[0] = id;
+        header[1..5].copy_from_slice(&a.to_le_bytes());
+        header[5..9].copy_from_slice(&b.to_le_bytes());
+        header[9..13].copy_from_slice(&c.to_le_bytes());
+        header
+    }
+
+    // The header is read before it can be authenticated, so parameters that
+    // would make the KDF panic or exhaust memory are rejected here.
+    pub(crate) fn from_header(header: &[u8]) -> Option<KdfParams> {
+        if header.len() != KDF_HEADER_LEN {
+            return None;
+        }
+        let field = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        let (a, b, c) = (field(1), field(5), field(9));
+        let params = match header[0] {
+            1 => KdfParams::Argon2id { memory_kib: a, iterations: b, parallelism: c },
+            2 => KdfParams::Scrypt { log_n: u8::try_from(a).ok()?, r: b, p: c },
+            3 => KdfParams::Pbkdf2Sha256 { iterations: a },
+            _ => return None,
+        };
+        Some(params).filter(KdfParams::is_usable)
+    }
+}
+
+// Argon2 parameters the argon2 crate accepts and that stay within the
+// ceilings above; shared with the importers, which read Argon2 settings
+// from files of unknown origin.
+pub(crate) fn argon2_is_usable(memory_kib: u32, iterations: u32, parallelism: u32) -> bool {
+    argon2::Params::new(memory_kib, iterations, parallelism, Some(KEY_LEN)).is_ok()
+        && memory_kib <= MAX_MEMORY_KIB
+        && u64::from(memory_kib) * u64::from(iterations) <= MAX_ARGON2_WORK_KIB
+}
+
+impl fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KdfParams::Argon2id { memory_kib, iterations, parallelism } => {
+                write!(f, "argon2id (m={} KiB, t={}, p={})", memory_kib, iterations, parallelism)
+            }
+            KdfParams::Scrypt { log_n, r, p } => write!(f, "scrypt (N=2^{}, r={}, p={})", log_n, r, p),
+            KdfParams::Pbkdf2Sha256 { iterations } => write!(f, "pbkdf2-sha256 ({} iterations)", iterations),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(id: u8, a: u32, b: u32, c: u32) -> [u8; KDF_HEADER_LEN] {
+        let mut header = [0u8; KDF_HEADER_LEN];
+        header[0] = id;
+        header[1..5].copy_from_slice(&a.to_le_bytes());
+        header[5..9].copy_from_slice(&b.to_le_bytes());
+        header[9..13].copy_from_slice(&c.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn headers_round_trip() {
+        for algorithm in [KdfAlgorithm::Argon2id, KdfAlgorithm::Scrypt, KdfAlgorithm::Pbkdf2Sha256] {
+            let params = KdfParams::default_for(algorithm);
+            assert_eq!(params.algorithm(), algorithm);
+            assert_eq!(KdfParams::from_header(&params.to_header()), Some(params));
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_headers() {
+        for (id, a, b, c) in [
+            // Argon2id: over 4 GiB of memory, no passes, no lanes.
+            (1, 4 * 1024 * 1024 + 1, 1, 1),
+            (1, 64, 0, 1),
+            (1, 64, 1, 0),
+            // Argon2id: a thousand passes over a gigabyte.
+            (1, 1024 * 1024, 1000, 1),
+            // scrypt: N out of range, r or p zero or too large.
+            (2, 0, 8, 1),
+            (2, 25, 8, 1),
+            (2, 256 + 14, 8, 1),
+            (2, 14, 0, 1),
+            (2, 14, 33, 1),
+            (2, 14, 8, 0),
+            (2, 14, 8, 17),
+            // scrypt: N of 2^(16r) or more, which rust-crypto asserts on,
+            // and 64 GiB of memory.
+            (2, 20, 1, 1),
+            (2, 16, 1, 1),
+            (2, 24, 32, 16),
+            // PBKDF2 without iterations or with too many, and an unknown
+            // algorithm.
+            (3, 0, 0, 0),
+            (3, MAX_ITERATIONS + 1, 0, 0),
+            (4, 1, 1, 1),
+        ] {
+            assert_eq!(KdfParams::from_header(&header(id, a, b, c)), None, "{} {} {} {}", id, a, b, c);
+        }
+        // The limits themselves are allowed.
+        let largest = KdfParams::Argon2id { memory_kib: 4 * 1024 * 1024, iterations: 1, parallelism: 1 };
+        assert_eq!(KdfParams::from_header(&largest.to_header()), Some(largest));
+        let largest = KdfParams::Scrypt { log_n: 22, r: 8, p: 16 };
+        assert_eq!(KdfParams::from_header(&largest.to_header()), Some(largest));
+        let smallest_r = KdfParams::Scrypt { log_n: 15, r: 1, p: 1 };
+        assert_eq!(KdfParams::from_header(&smallest_r.to_header()), Some(smallest_r));
+        let largest = KdfParams::Pbkdf2Sha256 { iterations: MAX_ITERATIONS };
+        assert_eq!(KdfParams::from_header(&largest.to_header()), Some(largest));
+        assert_eq!(KdfParams::from_header(&header(3, 1, 0, 0)[..12]), None);
+    }
+
+    #[test]
+    fn keys_depend_on_password_salt_and_parameters() {
+        let salt = [7u8; SALT_LEN];
+        for params in [
+            KdfParams::Argon2id { memory_kib: 64, iterations: 1, parallelism: 1 },
+            KdfParams::Scrypt { log_n: 4, r: 8, p: 1 },
+            KdfParams::Pbkdf2Sha256 { iterations: 2 },
+        ] {
+            let key = params.derive_key("master", &salt).unwrap();
+            assert_eq!(params.derive_key("master", &salt).unwrap(), key);
+            assert_ne!(params.derive_key("Master", &salt).unwrap(), key);
+            assert_ne!(params.derive_key("master", &[8u8; SALT_LEN]).unwrap(), key);
+        }
+        let pbkdf2 = |iterations| KdfParams::Pbkdf2Sha256 { iterations }.derive_key("master", &salt).unwrap();
+        assert_ne!(pbkdf2(1), pbkdf2(2));
+    }
+
+    #[test]
+    fn refuses_to_derive_with_unusable_parameters() {
+        let salt = [7u8; SALT_LEN];
+        for params in [
+            KdfParams::Scrypt { log_n: 20, r: 1, p: 1 },
+            KdfParams::Scrypt { log_n: 0, r: 8, p: 1 },
+            KdfParams::Argon2id { memory_kib: 1, iterations: 1, parallelism: 1 },
+            KdfParams::Pbkdf2Sha256 { iterations: 0 },
+        ] {
+            assert!(matches!(params.derive_key("master", &salt), Err(VaultError::InvalidKdf(_))), "{}", params);
+        }
+    }
+
+    #[test]
+    fn benchmarks_stay_within_the_ceilings() {
+        for algorithm in [KdfAlgorithm::Argon2id, KdfAlgorithm::Scrypt, KdfAlgorithm::Pbkdf2Sha256] {
+            let params = KdfParams::benchmark(algorithm, Duration::ZERO);
+            assert_eq!(KdfParams::from_header(&params.to_header()), Some(params));
+        }
+    }
+
+    #[test]
+    fn parses_algorithm_names() {
+        assert_eq!(KdfAlgorithm::from_name("Argon2ID"), Some(KdfAlgorithm::Argon2id));
+        assert_eq!(KdfAlgorithm::from_name("pbkdf2-sha256"), Some(KdfAlgorithm::Pbkdf2Sha256));
+        assert_eq!(KdfAlgorithm::from_name("bcrypt"), None);
+    }
+}
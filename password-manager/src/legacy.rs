@@ -0,0 +1,178 @@
+// Reading of the pre-container format: an unsalted SHA-256 of the master
+// password in master.hash and a `service|username|hexpassword` line per
+// entry, where the password was XORed with the master password.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+use crate::entry::PasswordEntry;
+use crate::error::{Result, VaultError};
+use crate::format::MAGIC;
+
+const LEGACY_MASTER_HASH: &str = "master.hash";
+const LEGACY_BACKUP_SUFFIX: &str = ".legacy.bak";
+
+/// Outcome of upgrading a legacy install. Lines that could not be decrypted
+/// are left in `vault_backup`, the copy of the old passwords file.
+pub struct LegacyMigration {
+    pub migrated: usize,
+    pub skipped: usize,
+    pub vault_backup: PathBuf,
+}
+
+pub(crate) fn master_hash_path(vault: &Path) -> PathBuf {
+    vault.with_file_name(LEGACY_MASTER_HASH)
+}
+
+pub(crate) fn backup_path(original: &Path) -> PathBuf {
+    let mut name = original.as_os_str().to_owned();
+    name.push(LEGACY_BACKUP_SUFFIX);
+    PathBuf::from(name)
+}
+
+// A legacy install has master.hash next to a pipe-separated passwords file
+// (or no passwords file at all if nothing was ever saved).
+pub(crate) fn needs_migration(vault: &Path) -> bool {
+    if !master_hash_path(vault).exists() {
+        return false;
+    }
+    match fs::read(vault) {
+        Ok(contents) => !contents.starts_with(MAGIC),
+        Err(_) => true,
+    }
+}
+
+fn hash_password(password: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input_str(password);
+    hasher.result_str()
+}
+
+fn decrypt(encrypted: &str, key: &str) -> Option<String> {
+    let key_bytes = key.as_bytes();
+    if !encrypted.len().is_multiple_of(2) || key_bytes.is_empty() {
+        return None;
+    }
+    let mut result = Vec::new();
+    for i in (0..encrypted.len()).step_by(2) {
+        let byte = u8::from_str_radix(encrypted.get(i..i + 2)?, 16).ok()?;
+        result.push(byte ^ key_bytes[(i / 2) % key_bytes.len()]);
+    }
+    String::from_utf8(result).ok()
+}
+
+// Checks the password against master.hash and decrypts every readable line.
+// Returns the entries and the number of lines that had to be skipped.
+pub(crate) fn read(vault: &Path, password: &str) -> Result<(Vec<PasswordEntry>, usize)> {
+    let master_hash = master_hash_path(vault);
+    let stored_hash = match BufReader::new(File::open(&master_hash)?).lines().next() {
+        Some(line) => line?,
+        None => return Err(VaultError::CorruptVault(format!("{} is empty", master_hash.display()))),
+    };
+    if stored_hash.trim() != hash_password(password) {
+        return Err(VaultError::WrongPassword);
+    }
+
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    let file = match File::open(vault) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok((entries, skipped)),
+        Err(e) => return Err(e.into()),
+    };
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let parts: Vec<&str> = line.split('|').collect();
+        let decrypted = match parts.as_slice() {
+            [_, _, encrypted] => decrypt(encrypted, password),
+            _ => None,
+        };
+        match decrypted {
+            Some(decrypted) => entries.push(PasswordEntry::new(
+                parts[0].to_string(),
+                parts[1].to_string(),
+                decrypted,
+            )),
+            None => skipped += 1,
+        }
+    }
+    Ok((entries, skipped))
+}
+
+// Copies the passwords file and master.hash to *.legacy.bak.
+pub(crate) fn back_up(vault: &Path) -> Result<()> {
+    for original in [vault.to_path_buf(), master_hash_path(vault)] {
+        if original.exists() {
+            fs::copy(&original, backup_path(&original))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The old XOR scheme, hex-encoded.
+    fn encrypt(plain: &str, key: &str) -> String {
+        plain.bytes().zip(key.bytes().cycle()).map(|(byte, key)| format!("{:02x}", byte ^ key)).collect()
+    }
+
+    fn legacy_install(name: &str, password: &str, lines: &[String]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("legacy-test-{}-{}", name, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let vault = dir.join("passwords.dat");
+        fs::write(master_hash_path(&vault), format!("{}\n", hash_password(password))).unwrap();
+        fs::write(&vault, lines.join("\n")).unwrap();
+        vault
+    }
+
+    #[test]
+    fn detects_legacy_installs() {
+        let vault = legacy_install("detect", "master", &[format!("github|octocat|{}", encrypt("pw", "master"))]);
+        assert!(needs_migration(&vault));
+        fs::remove_file(&vault).unwrap();
+        assert!(needs_migration(&vault));
+        fs::write(&vault, [&MAGIC[..], b"rest"].concat()).unwrap();
+        assert!(!needs_migration(&vault));
+        fs::remove_file(master_hash_path(&vault)).unwrap();
+        assert!(!needs_migration(&vault));
+    }
+
+    #[test]
+    fn reads_entries_and_skips_bad_lines() {
+        let lines = [
+            format!("github|octocat|{}", encrypt("hunter2", "master")),
+            String::new(),
+            format!("mail||{}", encrypt("a longer password than the key", "master")),
+            "no separators".to_string(),
+            "odd|hex|abc".to_string(),
+            "not|hex|zz".to_string(),
+        ];
+        let vault = legacy_install("read", "master", &lines);
+        assert!(matches!(read(&vault, "wrong"), Err(VaultError::WrongPassword)));
+
+        let (entries, skipped) = read(&vault, "master").unwrap();
+        assert_eq!(skipped, 3);
+        let read: Vec<(&str, &str, &str)> =
+            entries.iter().map(|entry| (entry.service.as_str(), entry.username.as_str(), entry.password.as_str())).collect();
+        assert_eq!(read, [("github", "octocat", "hunter2"), ("mail", "", "a longer password than the key")]);
+    }
+
+    #[test]
+    fn backs_up_both_files() {
+        let vault = legacy_install("backup", "master", &[format!("a|b|{}", encrypt("c", "master"))]);
+        back_up(&vault).unwrap();
+        for original in [vault.clone(), master_hash_path(&vault)] {
+            assert_eq!(fs::read(backup_path(&original)).unwrap(), fs::read(&original).unwrap());
+        }
+        assert!(backup_path(&vault).to_string_lossy().ends_with("passwords.dat.legacy.bak"));
+    }
+}
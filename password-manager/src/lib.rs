@@ -0,0 +1,58 @@
+//! An encrypted password vault.
+//!
+//! A vault is a single file holding every [`PasswordEntry`], encrypted with
+//! AES-256-GCM under a key derived from the master password (see [`kdf`]).
+//! [`PasswordManager`] is the entry point:
+//!
+//! ```no_run
+//! use password_manager::{PasswordManager, PasswordPolicy};
+//!
+//! let mut manager = PasswordManager::open("passwords.dat");
+//! manager.unlock("correct horse battery staple")?;
+//! let id = manager.add_entry("github".into(), "octocat".into(), PasswordManager::generate_password(&PasswordPolicy::with_length(20))?)?;
+//! assert_eq!(manager.find_entry("github", Some("octocat"))?.id, id);
+//! manager.save_to_file()?;
+//! # Ok::<(), password_manager::VaultError>(())
+//! ```
+
+pub mod audit;
+pub mod bitwarden;
+pub mod breach;
+pub mod csv_import;
+mod entry;
+mod error;
+mod folder;
+mod format;
+pub mod generator;
+pub mod import;
+pub mod kdbx;
+pub mod kdf;
+mod legacy;
+mod manager;
+pub mod onepassword;
+pub mod otp;
+pub mod pass;
+pub mod passphrase;
+pub mod search;
+mod storage;
+pub mod strength;
+
+pub use audit::{AuditOptions, AuditReport};
+pub use bitwarden::read_bitwarden_json;
+pub use breach::{BreachDatabase, BreachFinding};
+pub use csv_import::{read_csv, CsvFormat};
+pub use entry::{CustomField, FieldKind, PasswordEntry, PreviousPassword};
+pub use error::{Result, VaultError};
+pub use generator::PasswordPolicy;
+pub use import::{DuplicateRecord, ImportReport, ImportedEntries, SkippedRecord, UnmappedField};
+pub use kdbx::{read_kdbx, write_kdbx, KdbxCipher, KdbxOptions};
+pub use kdf::{KdfAlgorithm, KdfParams, DEFAULT_UNLOCK_TIME};
+pub use legacy::LegacyMigration;
+pub use manager::{PasswordManager, DEFAULT_BACKUP_COUNT, DEFAULT_HISTORY_COUNT, DEFAULT_MIN_MASTER_SCORE};
+pub use onepassword::read_1pux;
+pub use otp::{OtpAlgorithm, OtpCode, OtpKind, OtpSecret};
+pub use pass::{read_pass_store, write_pass_store, Gpg, GpgCommand};
+pub use passphrase::{Passphrase, PassphrasePolicy, Wordlist};
+pub use search::{SearchField, SearchResult, SortOrder};
+pub use strength::Strength;
+pub use uuid::Uuid;
@@ -0,0 +1,1106 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::audit::{self, AuditOptions, AuditReport, EntryRef};
+use crate::breach::{BreachDatabase, BreachFinding};
+use crate::entry::{PasswordEntry, PreviousPassword};
+use crate::error::{Result, VaultError};
+use crate::folder;
+use crate::format::{self, Settings, VaultKey};
+use crate::generator::PasswordPolicy;
+use crate::import::{DuplicateRecord, ImportReport, ImportedEntries};
+use crate::kdbx::{self, KdbxOptions};
+use crate::kdf::KdfParams;
+use crate::legacy::{self, LegacyMigration};
+use crate::otp::OtpCode;
+use crate::pass::{self, Gpg};
+use crate::passphrase::{Passphrase, PassphrasePolicy, Wordlist};
+use crate::search::{self, SearchResult, SortOrder};
+use crate::storage;
+use crate::strength::{self, Strength};
+
+/// How many rotating backups are kept unless configured otherwise.
+pub const DEFAULT_BACKUP_COUNT: usize = 5;
+
+/// How many previous passwords each entry keeps unless configured otherwise.
+pub const DEFAULT_HISTORY_COUNT: usize = 10;
+
+/// Lowest [`Strength::score`] accepted for a new master password.
+pub const DEFAULT_MIN_MASTER_SCORE: u8 = 2;
+
+/// An open vault file and, once unlocked, its decrypted entries.
+pub struct PasswordManager {
+    entries: HashMap<Uuid, PasswordEntry>,
+    // Every folder, including empty ones and the parents of nested ones.
+    folders: BTreeSet<String>,
+    key: Option<VaultKey>,
+    path: PathBuf,
+    backup_count: usize,
+    history_count: usize,
+    min_master_score: u8,
+    breach_database: Option<BreachDatabase>,
+}
+
+impl PasswordManager {
+    /// Points the manager at the vault file at `path`. Nothing is read until
+    /// [`unlock`](Self::unlock) is called.
+    pub fn open(path: impl Into<PathBuf>) -> PasswordManager {
+        PasswordManager {
+            entries: HashMap::new(),
+            folders: BTreeSet::new(),
+            key: None,
+            path: path.into(),
+            backup_count: DEFAULT_BACKUP_COUNT,
+            history_count: DEFAULT_HISTORY_COUNT,
+            min_master_score: DEFAULT_MIN_MASTER_SCORE,
+            breach_database: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn vault_exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// True when the vault is still in the pre-container format and should
+    /// be upgraded with [`migrate_legacy`](Self::migrate_legacy).
+    pub fn needs_migration(&self) -> bool {
+        legacy::needs_migration(&self.path)
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Key derivation parameters of the unlocked vault.
+    pub fn kdf_params(&self) -> Option<KdfParams> {
+        self.key.as_ref().map(|key| key.kdf)
+    }
+
+    pub fn backup_count(&self) -> usize {
+        self.backup_count
+    }
+
+    /// Number of rotating backups to keep; 0 disables backups. The count is
+    /// saved in the vault, and unlocking loads the saved one.
+    pub fn set_backup_count(&mut self, count: usize) {
+        self.backup_count = count;
+    }
+
+    pub fn history_count(&self) -> usize {
+        self.history_count
+    }
+
+    /// Number of previous passwords kept per entry; 0 disables the history.
+    /// Longer histories are trimmed the next time the password changes.
+    /// Saved in the vault like the [backup count](Self::set_backup_count).
+    pub fn set_history_count(&mut self, count: usize) {
+        self.history_count = count;
+    }
+
+    pub fn min_master_score(&self) -> u8 {
+        self.min_master_score
+    }
+
+    /// Minimum strength score (0-4) for new master passwords; 0 accepts
+    /// anything but an empty password.
+    pub fn set_min_master_score(&mut self, score: u8) {
+        self.min_master_score = score;
+    }
+
+    /// A local breach list to check new master passwords against and to use
+    /// in [`check_breaches`](Self::check_breaches).
+    pub fn set_breach_database(&mut self, database: Option<BreachDatabase>) {
+        self.breach_database = database;
+    }
+
+    /// Fails with [`VaultError::WeakPassword`] unless `password` may become
+    /// the master password: it must reach the minimum score and, if a breach
+    /// list is set, must not be in it.
+    pub fn check_master_password(&self, password: &str) -> Result<Strength> {
+        let strength = strength::estimate(password, &[]);
+        if password.is_empty() || strength.score < self.min_master_score {
+            let reason = match strength.warning {
+                Some(ref warning) => format!("{} (score {}/4)", warning, strength.score),
+                None => format!("score {}/4, at least {} required", strength.score, self.min_master_score),
+            };
+            return Err(VaultError::WeakPassword(reason));
+        }
+        if let Some(ref database) = self.breach_database {
+            let count = database.count(password)?;
+            if count > 0 {
+                return Err(VaultError::WeakPassword(format!("it has been seen {} times in data breaches", count)));
+            }
+        }
+        Ok(strength)
+    }
+
+    fn unlocked_key(&self) -> Result<&VaultKey> {
+        self.key.as_ref().ok_or(VaultError::Locked)
+    }
+
+    /// Decrypts the vault file. Nothing is loaded unless the whole file
+    /// authenticates.
+    pub fn unlock(&mut self, master_password: &str) -> Result<()> {
+        let contents = fs::read(&self.path)?;
+        self.load_from_bytes(&contents, master_password)
+    }
+
+    fn load_from_bytes(&mut self, contents: &[u8], master_password: &str) -> Result<()> {
+        let (key, contents) = format::decode(contents, master_password)?;
+
+        let mut by_id = HashMap::new();
+        for entry in contents.entries {
+            if by_id.contains_key(&entry.id) {
+                return Err(VaultError::CorruptVault(format!("duplicate entry ID {}", entry.id)));
+            }
+            by_id.insert(entry.id, entry);
+        }
+
+        self.entries = by_id;
+        self.backup_count = contents.settings.backup_count;
+        self.history_count = contents.settings.history_count;
+        self.folders = BTreeSet::new();
+        for path in contents.folders {
+            self.add_folder(&folder::normalize(&path));
+        }
+        let entry_folders: Vec<String> = self.entries.values().map(|entry| entry.folder.clone()).collect();
+        entry_folders.iter().for_each(|path| self.add_folder(path));
+        self.key = Some(key);
+        Ok(())
+    }
+
+    /// Generates a fresh salt, derives the vault key with `kdf` and writes
+    /// the vault, so the master password is fixed from now on. The password
+    /// must pass [`check_master_password`](Self::check_master_password).
+    pub fn setup_master_password(&mut self, password: &str, kdf: KdfParams) -> Result<()> {
+        self.check_master_password(password)?;
+        self.key = Some(VaultKey::derive(password, kdf)?);
+        self.save_to_file()
+    }
+
+    /// Re-encrypts the vault (including any unsaved changes) under a key
+    /// derived from `new_password` with a fresh salt. The re-keyed file
+    /// replaces the old one in a single rename, and the in-memory key only
+    /// changes once that has succeeded.
+    pub fn change_master_password(&mut self, old_password: &str, new_password: &str, kdf: KdfParams) -> Result<()> {
+        if !self.unlocked_key()?.matches_password(old_password) {
+            return Err(VaultError::WrongPassword);
+        }
+        self.check_master_password(new_password)?;
+
+        let key = VaultKey::derive(new_password, kdf)?;
+        self.write(&key)?;
+        self.key = Some(key);
+        Ok(())
+    }
+
+    /// One-time upgrade of a master.hash + XOR passwords file install. The
+    /// originals are copied to `*.legacy.bak` before the new vault replaces
+    /// them, and master.hash is only removed once the vault is written.
+    pub fn migrate_legacy(&mut self, password: &str, kdf: KdfParams) -> Result<LegacyMigration> {
+        let (entries, skipped) = legacy::read(&self.path, password)?;
+        legacy::back_up(&self.path)?;
+
+        // The existing password is kept even if it is weak, or the vault
+        // could not be opened at all; it can be changed afterwards.
+        self.entries = entries.into_iter().map(|entry| (entry.id, entry)).collect();
+        self.key = Some(VaultKey::derive(password, kdf)?);
+        // The old passwords file is not a vault, so rotating it into the
+        // backups would only take up a slot; the *.legacy.bak copies keep it.
+        self.save_without_backup()?;
+        fs::remove_file(legacy::master_hash_path(&self.path))?;
+        Ok(LegacyMigration {
+            migrated: self.entries.len(),
+            skipped,
+            vault_backup: legacy::backup_path(&self.path),
+        })
+    }
+
+    /// Creates a new entry and returns its ID. A service may hold several
+    /// entries, but only one per username.
+    pub fn add_entry(&mut self, service: String, username: String, password: String) -> Result<Uuid> {
+        self.insert_entry(PasswordEntry::new(service, username, password))
+    }
+
+    /// Stores a complete entry under its own ID, e.g. one built with
+    /// [`PasswordEntry::new`] and filled in further.
+    pub fn insert_entry(&mut self, mut entry: PasswordEntry) -> Result<Uuid> {
+        if self.entries.contains_key(&entry.id) || self.clashes(&entry) {
+            return Err(VaultError::DuplicateEntry(entry.label()));
+        }
+        self.file_entry(&mut entry);
+        let id = entry.id;
+        self.entries.insert(id, entry);
+        Ok(id)
+    }
+
+    /// Replaces the stored entry with the same ID by `entry`, typically a
+    /// modified copy of what [`get_entry`](Self::get_entry) returned. The
+    /// creation time is kept and the modification time set to now; a changed
+    /// password moves the old one into the entry's history and records the
+    /// time of the change.
+    pub fn update_entry(&mut self, mut entry: PasswordEntry) -> Result<()> {
+        if self.clashes(&entry) {
+            return Err(VaultError::DuplicateEntry(entry.label()));
+        }
+        if !self.entries.contains_key(&entry.id) {
+            return Err(VaultError::NotFound(entry.id.to_string()));
+        }
+        self.file_entry(&mut entry);
+        let stored = self.entries.get_mut(&entry.id).expect("checked above");
+        let now = Utc::now();
+        entry.password_changed = stored.password_changed;
+        if entry.password != stored.password {
+            entry.password_changed = Some(now);
+            entry.history.insert(
+                0,
+                PreviousPassword {
+                    password: stored.password.clone(),
+                    replaced: now,
+                },
+            );
+        }
+        entry.history.truncate(self.history_count);
+        entry.created = stored.created;
+        entry.modified = now;
+        *stored = entry;
+        Ok(())
+    }
+
+    /// Makes the `index`-th previous password (0 is the newest) current
+    /// again. The password it replaces goes into the history, so a restore
+    /// can itself be undone.
+    pub fn restore_password(&mut self, id: Uuid, index: usize) -> Result<()> {
+        let mut entry = self.get_entry(id)?.clone();
+        let previous = entry
+            .history
+            .get(index)
+            .ok_or_else(|| VaultError::NotFound(format!("{} history #{}", entry.label(), index + 1)))?;
+        entry.password = previous.password.clone();
+        entry.history.remove(index);
+        self.update_entry(entry)
+    }
+
+    // Normalizes the entry's folder and tags and makes sure its folder
+    // exists.
+    fn file_entry(&mut self, entry: &mut PasswordEntry) {
+        entry.folder = folder::normalize(&entry.folder);
+        let mut tags: Vec<String> = Vec::new();
+        for tag in entry.tags.drain(..) {
+            let tag = tag.trim();
+            if !tag.is_empty() && !tags.iter().any(|seen| seen.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+        entry.tags = tags;
+        self.add_folder(&entry.folder);
+    }
+
+    fn add_folder(&mut self, path: &str) {
+        for folder in folder::with_ancestors(path) {
+            if !self.folders.contains(folder) {
+                self.folders.insert(folder.to_string());
+            }
+        }
+    }
+
+    /// Every folder path, sorted, so parents come right before their
+    /// subfolders.
+    pub fn folders(&self) -> Vec<&str> {
+        self.folders.iter().map(String::as_str).collect()
+    }
+
+    /// Creates `path` and any missing parent folders and returns the path
+    /// as stored (see [`PasswordEntry::folder`]).
+    pub fn create_folder(&mut self, path: &str) -> Result<String> {
+        let path = folder::normalize(path);
+        if path.is_empty() {
+            return Err(VaultError::InvalidFolder("a folder needs a name".to_string()));
+        }
+        self.add_folder(&path);
+        Ok(path)
+    }
+
+    /// Moves an entry into `folder` (created if needed); `""` moves it to the
+    /// top level. Like renaming folders, this does not count as modifying
+    /// the entry.
+    pub fn move_entry(&mut self, id: Uuid, folder: &str) -> Result<()> {
+        let path = folder::normalize(folder);
+        let entry = self.entries.get_mut(&id).ok_or_else(|| VaultError::NotFound(id.to_string()))?;
+        entry.folder = path.clone();
+        self.add_folder(&path);
+        Ok(())
+    }
+
+    /// Renames or moves a folder together with its subfolders and entries.
+    /// Renaming onto an existing folder merges the two.
+    pub fn rename_folder(&mut self, from: &str, to: &str) -> Result<()> {
+        let from = self.existing_folder(from)?;
+        let to = folder::normalize(to);
+        if to.is_empty() {
+            return Err(VaultError::InvalidFolder("a folder needs a name".to_string()));
+        }
+        if folder::contains(&from, &to) {
+            return Err(VaultError::InvalidFolder(format!("cannot move '{}' into itself", from)));
+        }
+
+        let renamed = |path: &str| format!("{}{}", to, &path[from.len()..]);
+        let moved: Vec<String> = self.folders.iter().filter(|path| folder::contains(&from, path)).cloned().collect();
+        for path in moved {
+            self.folders.remove(&path);
+            self.add_folder(&renamed(&path));
+        }
+        for entry in self.entries.values_mut().filter(|entry| folder::contains(&from, &entry.folder)) {
+            entry.folder = renamed(&entry.folder);
+        }
+        Ok(())
+    }
+
+    /// Removes an empty folder. Folders that still hold entries or
+    /// subfolders are left alone.
+    pub fn delete_folder(&mut self, path: &str) -> Result<()> {
+        let path = self.existing_folder(path)?;
+        let has_subfolders = self.folders.iter().any(|other| *other != path && folder::contains(&path, other));
+        if has_subfolders || self.entries.values().any(|entry| entry.folder == path) {
+            return Err(VaultError::InvalidFolder(format!("'{}' is not empty", path)));
+        }
+        self.folders.remove(&path);
+        Ok(())
+    }
+
+    fn existing_folder(&self, path: &str) -> Result<String> {
+        let path = folder::normalize(path);
+        if path.is_empty() || !self.folders.contains(&path) {
+            return Err(VaultError::InvalidFolder(format!("no folder '{}'", path)));
+        }
+        Ok(path)
+    }
+
+    /// Every tag in use with the number of entries carrying it, sorted by
+    /// name. Tags differing only in case are counted together under the
+    /// spelling seen first.
+    pub fn tags(&self) -> Vec<(String, usize)> {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for tag in self.list_services().into_iter().flat_map(|entry| &entry.tags) {
+            match counts.iter_mut().find(|(seen, _)| seen.eq_ignore_ascii_case(tag)) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((tag.clone(), 1)),
+            }
+        }
+        counts.sort_by_key(|(tag, _)| tag.to_lowercase());
+        counts
+    }
+
+    // Another entry already has the same service and username.
+    fn clashes(&self, entry: &PasswordEntry) -> bool {
+        self.entries
+            .values()
+            .any(|other| other.id != entry.id && other.service == entry.service && other.username == entry.username)
+    }
+
+    pub fn get_entry(&self, id: Uuid) -> Result<&PasswordEntry> {
+        self.entries.get(&id).ok_or_else(|| VaultError::NotFound(id.to_string()))
+    }
+
+    /// Like [`get_entry`](Self::get_entry), but records the access as the
+    /// entry's last use.
+    pub fn use_entry(&mut self, id: Uuid) -> Result<&PasswordEntry> {
+        let entry = self.entries.get_mut(&id).ok_or_else(|| VaultError::NotFound(id.to_string()))?;
+        entry.last_used = Some(Utc::now());
+        Ok(entry)
+    }
+
+    /// The entry's current two-factor code. For HOTP this advances the
+    /// stored counter, so the vault should be saved afterwards.
+    pub fn one_time_code(&mut self, id: Uuid) -> Result<OtpCode> {
+        let entry = self.entries.get_mut(&id).ok_or_else(|| VaultError::NotFound(id.to_string()))?;
+        let label = entry.label();
+        let otp = entry
+            .otp
+            .as_mut()
+            .ok_or_else(|| VaultError::InvalidOtp(format!("{} has no one-time password", label)))?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let code = otp.generate(now)?;
+        entry.last_used = Some(Utc::now());
+        Ok(code)
+    }
+
+    /// Entries for `service`, optionally narrowed down to one username,
+    /// sorted by username.
+    pub fn find_entries(&self, service: &str, username: Option<&str>) -> Vec<&PasswordEntry> {
+        let mut found: Vec<&PasswordEntry> = self
+            .entries
+            .values()
+            .filter(|entry| entry.service == service && username.is_none_or(|username| entry.username == username))
+            .collect();
+        found.sort_by(|a, b| a.username.cmp(&b.username));
+        found
+    }
+
+    /// The single entry for `service` (and `username`, if given). Fails with
+    /// [`VaultError::AmbiguousEntry`] when the service has several entries
+    /// and no username was given.
+    pub fn find_entry(&self, service: &str, username: Option<&str>) -> Result<&PasswordEntry> {
+        match self.find_entries(service, username).as_slice() {
+            [] => Err(VaultError::NotFound(service.to_string())),
+            [entry] => Ok(entry),
+            _ => Err(VaultError::AmbiguousEntry(service.to_string())),
+        }
+    }
+
+    /// Removes the entry and hands it back.
+    pub fn delete_entry(&mut self, id: Uuid) -> Result<PasswordEntry> {
+        self.entries.remove(&id).ok_or_else(|| VaultError::NotFound(id.to_string()))
+    }
+
+    /// All entries, sorted by service name and then username.
+    pub fn list_services(&self) -> Vec<&PasswordEntry> {
+        let mut entries: Vec<&PasswordEntry> = self.entries.values().collect();
+        entries.sort_by(|a, b| (&a.service, &a.username).cmp(&(&b.service, &b.username)));
+        entries
+    }
+
+    /// All entries in the given order.
+    pub fn list_sorted(&self, order: SortOrder) -> Vec<&PasswordEntry> {
+        let mut entries: Vec<&PasswordEntry> = self.entries.values().collect();
+        search::sort(&mut entries, order);
+        entries
+    }
+
+    /// Entries matching every word of `query` in their service, username,
+    /// URLs, tags, folder or notes, best matches first. See [`search`](crate::search).
+    pub fn search(&self, query: &str) -> Vec<SearchResult<'_>> {
+        search::search(&self.list_services(), query)
+    }
+
+    /// Adds the entries read by an importer. Entries for a service and
+    /// username the vault already has (or that appear twice in the export)
+    /// are left out and reported as duplicates. With `dry_run` the vault is
+    /// not touched and the report says what would have happened.
+    pub fn import(&mut self, imported: ImportedEntries, dry_run: bool) -> ImportReport {
+        let mut report = ImportReport {
+            dry_run,
+            imported: Vec::new(),
+            duplicates: Vec::new(),
+            skipped: imported.skipped,
+            unmapped: imported.unmapped,
+        };
+        if !dry_run {
+            imported.folders.iter().for_each(|path| self.add_folder(&folder::normalize(path)));
+        }
+        // Entries accepted so far in a dry run, which are never inserted.
+        let mut accepted: Vec<PasswordEntry> = Vec::new();
+        for mut entry in imported.entries {
+            // Exports that carry their own IDs may reuse one already taken.
+            if self.entries.contains_key(&entry.id) || accepted.iter().any(|other| other.id == entry.id) {
+                entry.id = Uuid::new_v4();
+            }
+            let existing = self
+                .entries
+                .values()
+                .chain(&accepted)
+                .find(|other| other.service == entry.service && other.username == entry.username);
+            if let Some(existing) = existing {
+                report.duplicates.push(DuplicateRecord {
+                    label: entry.label(),
+                    existing: existing.id,
+                    same_password: existing.password == entry.password,
+                });
+                continue;
+            }
+            report.imported.push(EntryRef {
+                id: entry.id,
+                label: entry.label(),
+            });
+            if dry_run {
+                accepted.push(entry);
+            } else {
+                self.insert_entry(entry).expect("duplicates were checked above");
+            }
+        }
+        report
+    }
+
+    /// The whole vault as a KeePass KDBX 4 database encrypted with
+    /// `password`, which need not be the master password.
+    pub fn export_kdbx(&self, password: &str, options: &KdbxOptions) -> Result<Vec<u8>> {
+        self.unlocked_key()?;
+        Ok(kdbx::write_kdbx(&self.list_services(), &self.folders(), password, options))
+    }
+
+    /// Writes the whole vault into a new `pass` store at `store`, each entry
+    /// encrypted for `recipients`. Returns how many entries were written.
+    pub fn export_pass(&self, store: &Path, recipients: &[String], gpg: &dyn Gpg) -> Result<usize> {
+        self.unlocked_key()?;
+        pass::write_pass_store(&self.list_services(), &self.folders(), store, recipients, gpg)
+    }
+
+    /// Checks every entry for weak, reused and old passwords, missing
+    /// usernames and likely duplicates.
+    pub fn audit(&self, options: &AuditOptions) -> AuditReport {
+        audit::audit(&self.list_services(), options, Utc::now())
+    }
+
+    /// Entry passwords found in the breach list set with
+    /// [`set_breach_database`](Self::set_breach_database).
+    pub fn check_breaches(&self) -> Result<Vec<BreachFinding>> {
+        let database = self.breach_database.as_ref().ok_or(VaultError::NoBreachDatabase)?;
+        database.check_entries(&self.list_services())
+    }
+
+    /// Encrypts and writes the vault, rotating the previous file into the
+    /// backups first.
+    pub fn save_to_file(&self) -> Result<()> {
+        self.write(self.unlocked_key()?)
+    }
+
+    /// Like [`save_to_file`](Self::save_to_file), but without rotating a
+    /// backup. Meant for bookkeeping such as last-use times, which should
+    /// not push real backups out of the rotation.
+    pub fn save_without_backup(&self) -> Result<()> {
+        self.write_in_place(self.unlocked_key()?)
+    }
+
+    // Every overwrite of the vault goes through one of these two.
+    fn write(&self, key: &VaultKey) -> Result<()> {
+        storage::rotate_backups(&self.path, self.backup_count)?;
+        self.write_in_place(key)
+    }
+
+    fn write_in_place(&self, key: &VaultKey) -> Result<()> {
+        let settings = Settings {
+            backup_count: self.backup_count,
+            history_count: self.history_count,
+        };
+        let contents = format::encode(&self.list_services(), &self.folders(), settings, key);
+        storage::write_atomically(&self.path, &contents)?;
+        Ok(())
+    }
+
+    /// Backups of this vault, newest first.
+    pub fn list_backups(&self) -> Result<Vec<PathBuf>> {
+        storage::list_backups(&self.path)
+    }
+
+    /// Replaces the vault with `backup`. The backup is fully unlocked with
+    /// `master_password` (the password that was current when it was taken)
+    /// first, and the vault being replaced is itself backed up.
+    pub fn restore_backup(&mut self, backup: &Path, master_password: &str) -> Result<()> {
+        let contents = fs::read(backup)?;
+        let mut restored = PasswordManager::open(self.path.clone());
+        restored.load_from_bytes(&contents, master_password)?;
+
+        storage::rotate_backups(&self.path, self.backup_count)?;
+        storage::write_atomically(&self.path, &contents)?;
+        // The backup's own settings come back with it; the manager's options,
+        // such as the breach list, stay as they are.
+        self.entries = restored.entries;
+        self.folders = restored.folders;
+        self.key = restored.key;
+        self.backup_count = restored.backup_count;
+        self.history_count = restored.history_count;
+        Ok(())
+    }
+
+    /// A random password following `policy`; see [`PasswordPolicy`].
+    pub fn generate_password(policy: &PasswordPolicy) -> Result<String> {
+        policy.generate()
+    }
+
+    /// A random passphrase of words from `wordlist`; see
+    /// [`PassphrasePolicy`].
+    pub fn generate_passphrase(policy: &PassphrasePolicy, wordlist: &Wordlist) -> Result<Passphrase> {
+        policy.generate(wordlist)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHEAP: KdfParams = KdfParams::Pbkdf2Sha256 { iterations: 1 };
+
+    fn temp_vault(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("manager-test-{}-{}", name, Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("passwords.dat")
+    }
+
+    fn new_vault(name: &str, password: &str) -> PasswordManager {
+        let mut manager = PasswordManager::open(temp_vault(name));
+        manager.set_min_master_score(0);
+        manager.setup_master_password(password, CHEAP).unwrap();
+        manager.add_entry("github".into(), "octocat".into(), "hunter2".into()).unwrap();
+        manager.save_to_file().unwrap();
+        manager
+    }
+
+    fn unlock(path: &Path, password: &str) -> Result<PasswordManager> {
+        let mut manager = PasswordManager::open(path);
+        manager.unlock(password)?;
+        Ok(manager)
+    }
+
+    #[test]
+    fn change_master_password_rekeys_the_vault() {
+        let mut manager = new_vault("rekey", "old password");
+        let before = fs::read(manager.path()).unwrap();
+        let new_kdf = KdfParams::Pbkdf2Sha256 { iterations: 2 };
+
+        assert!(matches!(manager.change_master_password("not it", "new password", new_kdf), Err(VaultError::WrongPassword)));
+        manager.set_min_master_score(4);
+        assert!(matches!(manager.change_master_password("old password", "password", new_kdf), Err(VaultError::WeakPassword(_))));
+        assert_eq!(fs::read(manager.path()).unwrap(), before);
+
+        manager.set_min_master_score(0);
+        manager.change_master_password("old password", "new password", new_kdf).unwrap();
+        assert_eq!(manager.kdf_params(), Some(new_kdf));
+        assert!(matches!(unlock(manager.path(), "old password"), Err(VaultError::WrongPassword)));
+        let reopened = unlock(manager.path(), "new password").unwrap();
+        assert_eq!(reopened.kdf_params(), Some(new_kdf));
+        assert_eq!(reopened.find_entry("github", None).unwrap().password, "hunter2");
+
+        // Later saves use the new key too.
+        manager.add_entry("mail".into(), String::new(), "x".into()).unwrap();
+        manager.save_to_file().unwrap();
+        assert_eq!(unlock(manager.path(), "new password").unwrap().list_services().len(), 2);
+    }
+
+    #[test]
+    fn change_master_password_needs_an_unlocked_vault() {
+        let manager = new_vault("locked", "old password");
+        let mut locked = PasswordManager::open(manager.path());
+        assert!(matches!(locked.change_master_password("old password", "new", CHEAP), Err(VaultError::Locked)));
+    }
+
+    #[test]
+    fn unlock_rejects_scrypt_headers_that_would_panic() {
+        let manager = new_vault("bad-scrypt", "master");
+        let mut vault = fs::read(manager.path()).unwrap();
+        // Header: magic, version, then the KDF; N = 2^20 with r = 1 breaks
+        // scrypt's N < 2^(16r) rule.
+        let kdf = 8 + 2;
+        vault[kdf..kdf + 13].copy_from_slice(&KdfParams::Scrypt { log_n: 20, r: 1, p: 1 }.to_header());
+        fs::write(manager.path(), &vault).unwrap();
+        assert!(matches!(unlock(manager.path(), "master"), Err(VaultError::CorruptVault(_))));
+
+        let mut manager = PasswordManager::open(temp_vault("bad-scrypt-setup"));
+        manager.set_min_master_score(0);
+        let setup = manager.setup_master_password("master", KdfParams::Scrypt { log_n: 20, r: 1, p: 1 });
+        assert!(matches!(setup, Err(VaultError::InvalidKdf(_))));
+        assert!(!manager.vault_exists());
+    }
+
+    #[test]
+    fn saves_rotate_backups_that_can_be_restored() {
+        let mut manager = new_vault("restore", "master");
+        // Backups are told apart by the millisecond they were taken.
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        manager.add_entry("mail".into(), String::new(), "x".into()).unwrap();
+        manager.save_to_file().unwrap();
+        let backups = manager.list_backups().unwrap();
+        assert_eq!(backups.len(), 2);
+        manager.save_without_backup().unwrap();
+        assert_eq!(manager.list_backups().unwrap(), backups);
+
+        // The newest backup is the vault before "mail" was added.
+        let current = fs::read(manager.path()).unwrap();
+        assert!(matches!(manager.restore_backup(&backups[0], "not it"), Err(VaultError::WrongPassword)));
+        assert_eq!(fs::read(manager.path()).unwrap(), current);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        manager.restore_backup(&backups[0], "master").unwrap();
+        assert!(manager.find_entry("mail", None).is_err());
+        assert_eq!(unlock(manager.path(), "master").unwrap().list_services().len(), 1);
+        // The replaced vault became a backup itself.
+        let after = manager.list_backups().unwrap();
+        assert_eq!(after.len(), 3);
+        assert_eq!(fs::read(&after[0]).unwrap(), current);
+
+        manager.set_backup_count(1);
+        manager.save_to_file().unwrap();
+        assert_eq!(manager.list_backups().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn migrates_legacy_installs() {
+        let vault = temp_vault("migrate");
+        // Passwords XORed with "master" and hex-encoded, as the old version
+        // stored them.
+        let xor = |plain: &str| -> String {
+            plain.bytes().zip(b"master".iter().cycle()).map(|(byte, key)| format!("{:02x}", byte ^ key)).collect()
+        };
+        let original = format!("github|octocat|{}\nbroken line\nmail|me|{}\n", xor("hunter2"), xor("letmein"));
+        fs::write(&vault, &original).unwrap();
+        // SHA-256 of "master", as the old version wrote it.
+        let hash = "fc613b4dfd6736a7bd268c8a0e74ed0d1c04a959f59dd74ef2874983fd443fc9";
+        fs::write(legacy::master_hash_path(&vault), hash).unwrap();
+
+        let mut manager = PasswordManager::open(&vault);
+        assert!(manager.needs_migration());
+        assert!(matches!(manager.migrate_legacy("wrong", CHEAP), Err(VaultError::WrongPassword)));
+        assert_eq!(fs::read_to_string(&vault).unwrap(), original);
+
+        let migration = manager.migrate_legacy("master", CHEAP).unwrap();
+        assert_eq!((migration.migrated, migration.skipped), (2, 1));
+        assert_eq!(fs::read_to_string(&migration.vault_backup).unwrap(), original);
+        assert_eq!(fs::read_to_string(legacy::backup_path(&legacy::master_hash_path(&vault))).unwrap(), hash);
+        assert!(!legacy::master_hash_path(&vault).exists());
+        assert!(!manager.needs_migration());
+        assert!(manager.list_backups().unwrap().is_empty());
+
+        let mut reopened = PasswordManager::open(&vault);
+        reopened.unlock("master").unwrap();
+        assert_eq!(reopened.kdf_params(), Some(CHEAP));
+        assert_eq!(reopened.find_entry("github", None).unwrap().password, "hunter2");
+        assert_eq!(reopened.find_entry("mail", Some("me")).unwrap().password, "letmein");
+    }
+
+    #[test]
+    fn breach_checks_need_a_breach_list() {
+        let mut manager = new_vault("breaches", "master");
+        assert!(matches!(manager.check_breaches(), Err(VaultError::NoBreachDatabase)));
+
+        // SHA-1 of "hunter2".
+        let list = manager.path().with_file_name("pwned.txt");
+        fs::write(&list, "0000000000000000000000000000000000000000:1\nF3BBBD66A63D4BF1747940578EC3D0103530E21D:17\n").unwrap();
+        manager.set_breach_database(Some(BreachDatabase::open(&list).unwrap()));
+        let findings = manager.check_breaches().unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!((findings[0].entry.label.as_str(), findings[0].count), ("octocat@github", 17));
+    }
+
+    #[test]
+    fn master_passwords_must_reach_the_minimum_score() {
+        let mut manager = PasswordManager::open(temp_vault("min-score"));
+        manager.set_min_master_score(3);
+        assert!(matches!(manager.check_master_password(""), Err(VaultError::WeakPassword(_))));
+        assert!(matches!(manager.check_master_password("password"), Err(VaultError::WeakPassword(_))));
+        assert!(matches!(manager.setup_master_password("qwerty123", CHEAP), Err(VaultError::WeakPassword(_))));
+        assert!(!manager.vault_exists());
+
+        assert!(manager.check_master_password("correct horse battery staple").unwrap().score >= 3);
+        manager.set_min_master_score(0);
+        assert!(manager.check_master_password("password").is_ok());
+        assert!(matches!(manager.check_master_password(""), Err(VaultError::WeakPassword(_))));
+    }
+
+    fn export(data: &str) -> ImportedEntries {
+        crate::csv_import::read_csv(data.as_bytes(), None).unwrap().1
+    }
+
+    #[test]
+    fn dry_run_imports_leave_the_vault_alone() {
+        let mut manager = new_vault("import-dry-run", "master");
+        let before = fs::read(manager.path()).unwrap();
+        let mut imported = export("name,url,username,password,folder\nmail,,bob,pw,Personal/Mail\nbank,,me,pw2,\n");
+        imported.folders.push("Archive".to_string());
+
+        let report = manager.import(imported.clone(), true);
+        assert!(report.dry_run);
+        let labels: Vec<&str> = report.imported.iter().map(|entry| entry.label.as_str()).collect();
+        assert_eq!(labels, ["bob@mail", "me@bank"]);
+        assert_eq!(manager.list_services().len(), 1);
+        assert!(manager.folders().is_empty());
+        assert_eq!(fs::read(manager.path()).unwrap(), before);
+
+        let report = manager.import(imported, false);
+        assert!(!report.dry_run);
+        assert_eq!(report.imported.len(), 2);
+        assert_eq!(manager.list_services().len(), 3);
+        assert_eq!(manager.folders(), ["Archive", "Personal", "Personal/Mail"]);
+    }
+
+    #[test]
+    fn imports_report_duplicates() {
+        let mut manager = new_vault("import-duplicates", "master");
+        let imported = export(
+            "name,url,username,password\n\
+             github,,octocat,hunter2\n\
+             github,,octocat,changed\n\
+             mail,,bob,pw\n\
+             mail,,bob,other\n",
+        );
+        let report = manager.import(imported, false);
+        let existing = manager.find_entry("github", Some("octocat")).unwrap().id;
+        let duplicates: Vec<(&str, bool)> =
+            report.duplicates.iter().map(|duplicate| (duplicate.label.as_str(), duplicate.same_password)).collect();
+        assert_eq!(duplicates, [("octocat@github", true), ("octocat@github", false), ("bob@mail", false)]);
+        assert!(report.duplicates[..2].iter().all(|duplicate| duplicate.existing == existing));
+        assert_eq!(report.duplicates[2].existing, report.imported[0].id);
+        assert_eq!(manager.find_entry("github", Some("octocat")).unwrap().password, "hunter2");
+        assert_eq!(manager.find_entry("mail", Some("bob")).unwrap().password, "pw");
+    }
+
+    #[test]
+    fn password_changes_reset_the_audit_age_without_history() {
+        let mut manager = new_vault("password-age", "master");
+        manager.set_history_count(0);
+        let mut entry = PasswordEntry::new("mail".into(), "bob".into(), "9xQ!w2Lp#Zr7vTk3".into());
+        entry.created = Utc::now() - chrono::Duration::days(400);
+        let id = manager.insert_entry(entry).unwrap();
+        let old = |manager: &PasswordManager| manager.audit(&AuditOptions::default()).old.iter().any(|finding| finding.entry.id == id);
+        assert!(old(&manager));
+
+        // Edits that keep the password do not count as a change.
+        let mut entry = manager.get_entry(id).unwrap().clone();
+        entry.notes = "new notes".into();
+        manager.update_entry(entry).unwrap();
+        assert!(old(&manager));
+
+        let mut entry = manager.get_entry(id).unwrap().clone();
+        entry.password = "Tz8$kW3m!Qp6rN2v".into();
+        manager.update_entry(entry).unwrap();
+        assert!(manager.get_entry(id).unwrap().history.is_empty());
+        assert!(manager.get_entry(id).unwrap().password_changed.is_some());
+        assert!(!old(&manager));
+
+        // The time is kept in the vault.
+        manager.save_to_file().unwrap();
+        let reopened = unlock(manager.path(), "master").unwrap();
+        assert_eq!(reopened.get_entry(id).unwrap().password_changed, manager.get_entry(id).unwrap().password_changed);
+    }
+
+    #[test]
+    fn retention_settings_are_saved_in_the_vault() {
+        let mut manager = new_vault("settings", "master");
+        let reopened = unlock(manager.path(), "master").unwrap();
+        assert_eq!((reopened.backup_count(), reopened.history_count()), (DEFAULT_BACKUP_COUNT, DEFAULT_HISTORY_COUNT));
+
+        manager.set_backup_count(1);
+        manager.set_history_count(2);
+        manager.save_to_file().unwrap();
+        let mut reopened = PasswordManager::open(manager.path());
+        reopened.set_history_count(7);
+        reopened.unlock("master").unwrap();
+        assert_eq!((reopened.backup_count(), reopened.history_count()), (1, 2));
+    }
+
+    #[test]
+    fn history_keeps_the_newest_passwords_up_to_the_limit() {
+        let mut manager = new_vault("history-limit", "master");
+        manager.set_history_count(2);
+        let id = manager.find_entry("github", Some("octocat")).unwrap().id;
+        for password in ["second", "third", "fourth"] {
+            let mut entry = manager.get_entry(id).unwrap().clone();
+            entry.password = password.into();
+            manager.update_entry(entry).unwrap();
+        }
+        let history = |manager: &PasswordManager| -> Vec<String> {
+            manager.get_entry(id).unwrap().history.iter().map(|previous| previous.password.clone()).collect()
+        };
+        assert_eq!(history(&manager), ["third", "second"]);
+
+        // Restoring swaps the chosen password with the current one.
+        manager.restore_password(id, 1).unwrap();
+        assert_eq!(manager.get_entry(id).unwrap().password, "second");
+        assert_eq!(history(&manager), ["fourth", "third"]);
+        assert!(matches!(manager.restore_password(id, 2), Err(VaultError::NotFound(_))));
+
+        // With the history off, a restore leaves nothing behind.
+        manager.set_history_count(0);
+        manager.restore_password(id, 0).unwrap();
+        assert_eq!(manager.get_entry(id).unwrap().password, "fourth");
+        assert!(history(&manager).is_empty());
+    }
+
+    #[test]
+    fn finds_entries_by_id_or_by_service_and_username() {
+        let mut manager = new_vault("find", "master");
+        let octocat = manager.find_entry("github", None).unwrap().id;
+        let hubot = manager.add_entry("github".into(), "hubot".into(), "beep".into()).unwrap();
+
+        assert_eq!(manager.get_entry(hubot).unwrap().username, "hubot");
+        assert_eq!(manager.find_entry("github", Some("hubot")).unwrap().id, hubot);
+        assert_eq!(manager.find_entry("github", Some("octocat")).unwrap().id, octocat);
+        assert!(matches!(manager.find_entry("github", None), Err(VaultError::AmbiguousEntry(_))));
+        let usernames: Vec<&str> = manager.find_entries("github", None).iter().map(|entry| entry.username.as_str()).collect();
+        assert_eq!(usernames, ["hubot", "octocat"]);
+
+        // Service names are matched exactly.
+        assert!(matches!(manager.find_entry("GitHub", None), Err(VaultError::NotFound(_))));
+        assert!(matches!(manager.find_entry("github", Some("nobody")), Err(VaultError::NotFound(_))));
+        assert!(matches!(manager.get_entry(Uuid::new_v4()), Err(VaultError::NotFound(_))));
+    }
+
+    #[test]
+    fn insert_refuses_clashing_entries() {
+        let mut manager = new_vault("insert", "master");
+        let existing = manager.find_entry("github", None).unwrap().clone();
+
+        // Same service and username under a new ID.
+        let clash = PasswordEntry::new("github".into(), "octocat".into(), "other".into());
+        assert!(matches!(manager.insert_entry(clash), Err(VaultError::DuplicateEntry(label)) if label == "octocat@github"));
+        // An ID already in use, even for a different account.
+        let mut reused_id = PasswordEntry::new("mail".into(), "bob".into(), "pw".into());
+        reused_id.id = existing.id;
+        assert!(matches!(manager.insert_entry(reused_id), Err(VaultError::DuplicateEntry(_))));
+        assert_eq!(manager.list_services().len(), 1);
+        assert_eq!(manager.get_entry(existing.id).unwrap().password, "hunter2");
+
+        // Another username or another service is fine, and the entry is
+        // stored under its own ID with its folder and tags tidied up.
+        let mut entry = PasswordEntry::new("github".into(), "hubot".into(), "beep".into());
+        entry.folder = "/Work//Code/".into();
+        entry.tags = vec!["ci".into(), " CI ".into(), "".into(), "bots".into()];
+        let id = entry.id;
+        assert_eq!(manager.insert_entry(entry).unwrap(), id);
+        manager.add_entry("gitlab".into(), "octocat".into(), "x".into()).unwrap();
+        let stored = manager.get_entry(id).unwrap();
+        assert_eq!(stored.folder, "Work/Code");
+        assert_eq!(stored.tags, ["ci", "bots"]);
+        assert_eq!(manager.folders(), ["Work", "Work/Code"]);
+        assert_eq!(manager.list_services().len(), 3);
+    }
+
+    #[test]
+    fn update_keeps_what_was_not_changed() {
+        let mut manager = new_vault("update", "master");
+        let mut entry = PasswordEntry::new("mail".into(), "bob".into(), "first".into());
+        entry.urls = vec!["https://mail.example.com".into()];
+        entry.notes = "two lines\nof notes".into();
+        entry.folder = "Personal".into();
+        entry.tags = vec!["email".into()];
+        entry.created = Utc::now() - chrono::Duration::days(30);
+        entry.modified = entry.created;
+        let id = manager.insert_entry(entry).unwrap();
+        let before = manager.get_entry(id).unwrap().clone();
+
+        // A caller cannot move the creation time.
+        let mut edited = before.clone();
+        edited.notes = "new notes".into();
+        edited.created = Utc::now();
+        manager.update_entry(edited).unwrap();
+        let after = manager.get_entry(id).unwrap();
+        assert_eq!(after.notes, "new notes");
+        assert_eq!((&after.service, &after.username, &after.password), (&before.service, &before.username, &before.password));
+        assert_eq!((&after.urls, &after.folder, &after.tags), (&before.urls, &before.folder, &before.tags));
+        assert_eq!(after.created, before.created);
+        assert!(after.modified > before.modified);
+        assert!(after.history.is_empty());
+
+        let mut edited = after.clone();
+        edited.password = "second".into();
+        manager.update_entry(edited).unwrap();
+        let after = manager.get_entry(id).unwrap();
+        assert_eq!(after.password, "second");
+        assert_eq!(after.history.len(), 1);
+        assert_eq!(after.history[0].password, "first");
+        assert_eq!(after.history[0].replaced, after.modified);
+        assert_eq!(after.notes, "new notes");
+
+        // Renaming onto another account or updating a deleted entry fails
+        // without touching the vault.
+        let mut clash = after.clone();
+        clash.service = "github".into();
+        clash.username = "octocat".into();
+        assert!(matches!(manager.update_entry(clash), Err(VaultError::DuplicateEntry(_))));
+        let gone = manager.delete_entry(id).unwrap();
+        assert!(matches!(manager.update_entry(gone), Err(VaultError::NotFound(_))));
+        assert_eq!(manager.list_services().len(), 1);
+    }
+
+    #[test]
+    fn folders_nest_and_survive_a_reload() {
+        let mut manager = new_vault("folders", "master");
+        assert_eq!(manager.create_folder(" Work//Email/Old ").unwrap(), "Work/Email/Old");
+        assert!(matches!(manager.create_folder(" / "), Err(VaultError::InvalidFolder(_))));
+        let github = manager.find_entry("github", None).unwrap().id;
+        manager.move_entry(github, "Code/Open Source").unwrap();
+        manager.create_folder("Empty").unwrap();
+        let folders = ["Code", "Code/Open Source", "Empty", "Work", "Work/Email", "Work/Email/Old"];
+        assert_eq!(manager.folders(), folders);
+
+        manager.save_to_file().unwrap();
+        let reopened = unlock(manager.path(), "master").unwrap();
+        assert_eq!(reopened.folders(), folders);
+        assert_eq!(reopened.get_entry(github).unwrap().folder, "Code/Open Source");
+
+        manager.move_entry(github, "").unwrap();
+        assert_eq!(manager.get_entry(github).unwrap().folder, "");
+        assert!(matches!(manager.move_entry(Uuid::new_v4(), "Work"), Err(VaultError::NotFound(_))));
+    }
+
+    #[test]
+    fn renaming_a_folder_moves_its_subtree() {
+        let mut manager = new_vault("rename-folder", "master");
+        let mail = manager.add_entry("mail".into(), "bob".into(), "pw".into()).unwrap();
+        let old = manager.add_entry("archive".into(), "bob".into(), "pw".into()).unwrap();
+        let shop = manager.add_entry("shop".into(), "bob".into(), "pw".into()).unwrap();
+        manager.move_entry(mail, "Work/Email").unwrap();
+        manager.move_entry(old, "Work/Email/Old").unwrap();
+        manager.move_entry(shop, "Workshop").unwrap();
+
+        manager.rename_folder("Work/Email", "Personal/Mail").unwrap();
+        assert_eq!(manager.folders(), ["Personal", "Personal/Mail", "Personal/Mail/Old", "Work", "Workshop"]);
+        assert_eq!(manager.get_entry(mail).unwrap().folder, "Personal/Mail");
+        assert_eq!(manager.get_entry(old).unwrap().folder, "Personal/Mail/Old");
+        // A folder whose name merely starts the same is left alone.
+        assert_eq!(manager.get_entry(shop).unwrap().folder, "Workshop");
+
+        assert!(matches!(manager.rename_folder("Personal", "Personal/Inner"), Err(VaultError::InvalidFolder(_))));
+        assert!(matches!(manager.rename_folder("Missing", "Other"), Err(VaultError::InvalidFolder(_))));
+        assert!(matches!(manager.rename_folder("Personal", ""), Err(VaultError::InvalidFolder(_))));
+        // Renaming onto an existing folder merges the two.
+        manager.rename_folder("Personal", "Work").unwrap();
+        assert_eq!(manager.folders(), ["Work", "Work/Mail", "Work/Mail/Old", "Workshop"]);
+        assert_eq!(manager.get_entry(old).unwrap().folder, "Work/Mail/Old");
+    }
+
+    #[test]
+    fn only_empty_folders_are_deleted() {
+        let mut manager = new_vault("delete-folder", "master");
+        let github = manager.find_entry("github", None).unwrap().id;
+        manager.move_entry(github, "Work/Code").unwrap();
+        manager.create_folder("Work/Empty").unwrap();
+
+        assert!(matches!(manager.delete_folder("Work"), Err(VaultError::InvalidFolder(_))));
+        assert!(matches!(manager.delete_folder("Work/Code"), Err(VaultError::InvalidFolder(_))));
+        assert!(matches!(manager.delete_folder("Nowhere"), Err(VaultError::InvalidFolder(_))));
+        manager.delete_folder("Work/Empty").unwrap();
+        assert_eq!(manager.folders(), ["Work", "Work/Code"]);
+
+        manager.move_entry(github, "").unwrap();
+        manager.delete_folder("Work/Code").unwrap();
+        manager.delete_folder("Work").unwrap();
+        assert!(manager.folders().is_empty());
+    }
+
+    #[test]
+    fn entries_filter_by_folder_and_tag() {
+        let mut manager = new_vault("filter", "master");
+        let mut mail = PasswordEntry::new("mail".into(), "bob".into(), "pw".into());
+        mail.folder = "Work/Email".into();
+        mail.tags = vec!["Important".into(), "email".into()];
+        manager.insert_entry(mail).unwrap();
+        let mut bank = PasswordEntry::new("bank".into(), "me".into(), "pw".into());
+        bank.folder = "Personal".into();
+        bank.tags = vec!["important".into()];
+        manager.insert_entry(bank).unwrap();
+
+        let in_folder = |folder: &str| -> Vec<String> {
+            manager.list_services().iter().filter(|entry| entry.in_folder(folder)).map(|entry| entry.service.clone()).collect()
+        };
+        assert_eq!(in_folder("Work"), ["mail"]);
+        assert_eq!(in_folder(" Work/Email/ "), ["mail"]);
+        assert_eq!(in_folder("Personal"), ["bank"]);
+        assert_eq!(in_folder(""), ["bank", "github", "mail"]);
+        assert!(in_folder("Wor").is_empty());
+
+        let tagged = |tag: &str| -> Vec<String> {
+            manager.list_services().iter().filter(|entry| entry.has_tag(tag)).map(|entry| entry.service.clone()).collect()
+        };
+        assert_eq!(tagged("IMPORTANT"), ["bank", "mail"]);
+        assert_eq!(tagged("email"), ["mail"]);
+        // Counted case-insensitively under the first spelling, sorted by name.
+        assert_eq!(manager.tags(), [("email".to_string(), 1), ("important".to_string(), 2)]);
+    }
+}
@@ -0,0 +1,452 @@
+//! 1Password's 1PUX export: a zip archive holding `export.data`, a JSON
+//! document of accounts, vaults and items, and a `files/` folder of
+//! attachments.
+//!
+//! Each vault becomes a top-level folder. Items other than logins and
+//! passwords are tagged with their category (`credit card`, `server`, ...);
+//! their section fields become custom fields. Attachments and documents
+//! are not imported and are reported as unmapped.
+
+use std::io::{Cursor, Read};
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::entry::{CustomField, FieldKind, PasswordEntry, PreviousPassword};
+use crate::error::{Result, VaultError};
+use crate::import::{self, ImportedEntries, SkippedRecord, UnmappedField};
+
+const LOGIN: &str = "001";
+const PASSWORD: &str = "005";
+const DOCUMENT: &str = "006";
+
+// Category UUIDs of the item types 1Password defines.
+const CATEGORIES: [(&str, &str); 22] = [
+    ("001", "login"),
+    ("002", "credit card"),
+    ("003", "secure note"),
+    ("004", "identity"),
+    ("005", "password"),
+    ("006", "document"),
+    ("100", "software license"),
+    ("101", "bank account"),
+    ("102", "database"),
+    ("103", "driver license"),
+    ("104", "outdoor license"),
+    ("105", "membership"),
+    ("106", "passport"),
+    ("107", "reward program"),
+    ("108", "social security number"),
+    ("109", "wireless router"),
+    ("110", "server"),
+    ("111", "email account"),
+    ("112", "api credential"),
+    ("113", "medical record"),
+    ("114", "ssh key"),
+    ("115", "crypto wallet"),
+];
+
+#[derive(Deserialize)]
+struct Export {
+    #[serde(default)]
+    accounts: Vec<Account>,
+}
+
+#[derive(Deserialize)]
+struct Account {
+    #[serde(default)]
+    vaults: Vec<Vault>,
+}
+
+#[derive(Deserialize)]
+struct Vault {
+    attrs: VaultAttrs,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct VaultAttrs {
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    #[serde(default)]
+    fav_index: i64,
+    created_at: Option<i64>,
+    updated_at: Option<i64>,
+    #[serde(default)]
+    state: String,
+    #[serde(default)]
+    category_uuid: String,
+    #[serde(default)]
+    details: Details,
+    #[serde(default)]
+    overview: Overview,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Details {
+    #[serde(default)]
+    login_fields: Vec<LoginField>,
+    notes_plain: Option<String>,
+    #[serde(default)]
+    sections: Vec<Section>,
+    #[serde(default)]
+    password_history: Vec<OldPassword>,
+    password: Option<String>,
+    document_attributes: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginField {
+    #[serde(default)]
+    value: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    field_type: String,
+    designation: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Section {
+    #[serde(default)]
+    fields: Vec<SectionField>,
+}
+
+#[derive(Deserialize)]
+struct SectionField {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    value: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct OldPassword {
+    value: String,
+    time: i64,
+}
+
+#[derive(Default, Deserialize)]
+struct Overview {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    urls: Vec<OverviewUrl>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct OverviewUrl {
+    #[serde(default)]
+    url: String,
+}
+
+fn failed(reason: &str) -> VaultError {
+    VaultError::ImportFailed(reason.to_string())
+}
+
+/// Whether `data` looks like a zip archive, as 1PUX files are.
+pub fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04")
+}
+
+/// Reads a 1PUX export from the bytes of the archive.
+pub fn read_1pux(data: &[u8]) -> Result<ImportedEntries> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| failed(&format!("not a 1PUX archive: {}", e)))?;
+    let mut json = Vec::new();
+    archive
+        .by_name("export.data")
+        .map_err(|_| failed("the archive has no export.data; is it a 1PUX export?"))?
+        .read_to_end(&mut json)?;
+    let export: Export = serde_json::from_slice(&json).map_err(|e| failed(&format!("invalid export.data: {}", e)))?;
+
+    let mut imported = ImportedEntries::default();
+    for vault in export.accounts.iter().flat_map(|account| &account.vaults) {
+        let folder = vault.attrs.name.replace('/', "-");
+        if !folder.trim().is_empty() {
+            imported.folders.push(folder.clone());
+        }
+        for item in &vault.items {
+            match to_entry(item, &folder, &mut imported.unmapped) {
+                Ok(entry) => imported.entries.push(entry),
+                Err(reason) => imported.skipped.push(SkippedRecord::new(format!("item '{}'", item.overview.title), reason)),
+            }
+        }
+    }
+    Ok(imported)
+}
+
+fn time(seconds: i64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(seconds, 0).single()
+}
+
+fn to_entry(item: &Item, folder: &str, unmapped: &mut Vec<UnmappedField>) -> std::result::Result<PasswordEntry, String> {
+    if item.state == "deleted" {
+        return Err("in the trash".to_string());
+    }
+    let category = CATEGORIES.iter().find(|(uuid, _)| *uuid == item.category_uuid).map_or("unknown item type", |(_, name)| name);
+    let title = item.overview.title.trim();
+    let mut unmapped_field = |field: String| unmapped.push(UnmappedField::new(title, field));
+
+    let mut urls: Vec<String> = Vec::new();
+    for url in std::iter::once(&item.overview.url).chain(item.overview.urls.iter().map(|url| &url.url)) {
+        let url = url.trim();
+        if !url.is_empty() && !urls.iter().any(|known| known == url) {
+            urls.push(url.to_string());
+        }
+    }
+    let service = match title {
+        "" => urls.first().map(|url| import::host_of(url)).unwrap_or_default(),
+        title => title.to_string(),
+    };
+    if service.is_empty() {
+        return Err("no title or URL".to_string());
+    }
+
+    let details = &item.details;
+    let mut entry = PasswordEntry::new(service, String::new(), details.password.clone().unwrap_or_default());
+    entry.urls = urls;
+    entry.notes = details.notes_plain.clone().unwrap_or_default();
+    entry.folder = folder.to_string();
+    entry.tags = item.overview.tags.clone();
+    if item.category_uuid != LOGIN && item.category_uuid != PASSWORD {
+        entry.tags.push(category.to_string());
+    }
+    if item.fav_index > 0 {
+        entry.tags.push("favorite".to_string());
+    }
+    if item.state == "archived" {
+        entry.tags.push("archived".to_string());
+    }
+
+    for field in details.login_fields.iter().filter(|field| !field.value.is_empty()) {
+        match field.designation.as_deref() {
+            Some("username") => entry.username = field.value.clone(),
+            Some("password") => entry.password = field.value.clone(),
+            _ => {
+                let kind = match field.field_type.as_str() {
+                    "T" | "A" | "N" => FieldKind::Text,
+                    "E" => FieldKind::Email,
+                    "U" => FieldKind::Url,
+                    "P" => FieldKind::Hidden,
+                    // Checkboxes, radio buttons and the like only make
+                    // sense for filling in the web form.
+                    _ => {
+                        unmapped_field(format!("form field \"{}\"", field.name));
+                        continue;
+                    }
+                };
+                entry.fields.push(CustomField {
+                    name: field.name.clone(),
+                    kind,
+                    value: field.value.clone(),
+                });
+            }
+        }
+    }
+
+    for field in details.sections.iter().flat_map(|section| &section.fields) {
+        let name = if field.title.is_empty() { &field.id } else { &field.title };
+        let Some((key, value)) = field.value.iter().next() else {
+            continue;
+        };
+        match section_value(key, value) {
+            SectionValue::Empty => {}
+            SectionValue::Otp(secret) if entry.otp.is_none() => import::set_otp(&mut entry, &secret),
+            SectionValue::Otp(secret) => entry.fields.push(CustomField {
+                name: name.clone(),
+                kind: FieldKind::Hidden,
+                value: secret,
+            }),
+            SectionValue::Field(kind, value) => entry.fields.push(CustomField {
+                name: name.clone(),
+                kind,
+                value,
+            }),
+            SectionValue::Unmapped(what) => unmapped_field(format!("{} \"{}\"", what, name)),
+        }
+    }
+    if item.category_uuid == DOCUMENT || details.document_attributes.is_some() {
+        let file = details
+            .document_attributes
+            .as_ref()
+            .and_then(|attributes| attributes.get("fileName"))
+            .and_then(Value::as_str)
+            .unwrap_or("file");
+        unmapped_field(format!("document {}", file));
+    }
+
+    if let Some(created) = item.created_at.and_then(time) {
+        entry.created = created;
+        entry.modified = created;
+    }
+    if let Some(modified) = item.updated_at.and_then(time) {
+        entry.modified = modified;
+    }
+    // Each old password comes with when it was replaced.
+    entry.history = details
+        .password_history
+        .iter()
+        .filter_map(|old| {
+            Some(PreviousPassword {
+                password: old.value.clone(),
+                replaced: time(old.time)?,
+            })
+        })
+        .collect();
+    entry.history.sort_by_key(|old| std::cmp::Reverse(old.replaced));
+    Ok(entry)
+}
+
+enum SectionValue {
+    Empty,
+    Field(FieldKind, String),
+    Otp(String),
+    Unmapped(&'static str),
+}
+
+// A section field's value is an object with one key naming its type.
+fn section_value(key: &str, value: &Value) -> SectionValue {
+    let string = |value: &Value| value.as_str().unwrap_or("").to_string();
+    let mapped = match key {
+        "concealed" | "creditCardNumber" => SectionValue::Field(FieldKind::Hidden, string(value)),
+        "string" | "phone" | "menu" | "gender" | "creditCardType" => SectionValue::Field(FieldKind::Text, string(value)),
+        "url" => SectionValue::Field(FieldKind::Url, string(value)),
+        "email" => {
+            let address = value.get("email_address").map_or_else(|| string(value), string);
+            SectionValue::Field(FieldKind::Email, address)
+        }
+        "totp" => SectionValue::Otp(string(value)),
+        "date" => match value.as_i64().and_then(time) {
+            Some(date) => SectionValue::Field(FieldKind::Date, date.format("%Y-%m-%d").to_string()),
+            None => SectionValue::Empty,
+        },
+        // Stored as YYYYMM.
+        "monthYear" => match value.as_i64() {
+            Some(month_year) if month_year > 0 => SectionValue::Field(FieldKind::Text, format!("{:02}/{}", month_year % 100, month_year / 100)),
+            _ => SectionValue::Empty,
+        },
+        "address" => {
+            let parts: Vec<String> = ["street", "city", "state", "zip", "country"]
+                .iter()
+                .map(|part| value.get(*part).map_or(String::new(), string))
+                .filter(|part| !part.trim().is_empty())
+                .collect();
+            SectionValue::Field(FieldKind::Text, parts.join(", "))
+        }
+        "sshKey" => SectionValue::Field(FieldKind::Hidden, value.get("privateKey").map_or(String::new(), string)),
+        "file" => return SectionValue::Unmapped("attachment"),
+        _ => return SectionValue::Unmapped("field"),
+    };
+    match mapped {
+        SectionValue::Field(_, ref value) | SectionValue::Otp(ref value) if value.trim().is_empty() => SectionValue::Empty,
+        mapped => mapped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    const EXPORT: &str = r#"{
+        "accounts": [{
+            "vaults": [{
+                "attrs": {"name": "Personal/Home"},
+                "items": [
+                    {
+                        "favIndex": 1,
+                        "createdAt": 1700000000,
+                        "updatedAt": 1700000100,
+                        "state": "active",
+                        "categoryUuid": "001",
+                        "details": {
+                            "loginFields": [
+                                {"value": "octocat", "name": "username", "fieldType": "T", "designation": "username"},
+                                {"value": "hunter2", "name": "password", "fieldType": "P", "designation": "password"},
+                                {"value": "✓", "name": "remember", "fieldType": "C"}
+                            ],
+                            "notesPlain": "recovery codes in the safe",
+                            "sections": [{"fields": [
+                                {"title": "one-time password", "id": "otp", "value": {"totp": "JBSWY3DPEHPK3PXP"}},
+                                {"title": "PIN", "id": "pin", "value": {"concealed": "1234"}},
+                                {"title": "scan", "id": "scan", "value": {"file": {"fileName": "scan.pdf"}}}
+                            ]}],
+                            "passwordHistory": [{"value": "hunter1", "time": 1690000000}]
+                        },
+                        "overview": {"title": "GitHub", "url": "https://github.com", "tags": ["dev"]}
+                    },
+                    {
+                        "state": "active",
+                        "categoryUuid": "110",
+                        "details": {"sections": [{"fields": [{"title": "URL", "id": "url", "value": {"url": "ssh://example.org"}}]}]},
+                        "overview": {"title": "Server"}
+                    },
+                    {"state": "deleted", "categoryUuid": "001", "overview": {"title": "Old"}}
+                ]
+            }]
+        }]
+    }"#;
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn reads_1pux_archives() {
+        let data = archive(&[("export.attributes", "{}"), ("export.data", EXPORT)]);
+        assert!(is_zip(&data));
+        let imported = read_1pux(&data).unwrap();
+        assert_eq!(imported.folders, ["Personal-Home"]);
+        assert_eq!(imported.entries.len(), 2);
+        assert_eq!(imported.skipped.len(), 1);
+
+        let github = &imported.entries[0];
+        assert_eq!((github.service.as_str(), github.username.as_str(), github.password.as_str()), ("GitHub", "octocat", "hunter2"));
+        assert_eq!(github.urls, ["https://github.com"]);
+        assert_eq!(github.folder, "Personal-Home");
+        assert_eq!(github.tags, ["dev", "favorite"]);
+        assert_eq!(github.notes, "recovery codes in the safe");
+        assert!(github.otp.is_some());
+        assert_eq!(github.fields.len(), 1);
+        assert_eq!((github.fields[0].name.as_str(), github.fields[0].kind), ("PIN", FieldKind::Hidden));
+        assert_eq!(github.created, time(1700000000).unwrap());
+        assert_eq!(github.modified, time(1700000100).unwrap());
+        assert_eq!(github.history[0].password, "hunter1");
+        assert_eq!(imported.unmapped.len(), 2);
+
+        let server = &imported.entries[1];
+        assert_eq!(server.tags, ["server"]);
+        assert_eq!(server.fields[0].kind, FieldKind::Url);
+    }
+
+    #[test]
+    fn rejects_other_archives() {
+        assert!(!is_zip(b"{}"));
+        assert!(matches!(read_1pux(b"not a zip"), Err(VaultError::ImportFailed(_))));
+        let data = archive(&[("other.json", "{}")]);
+        assert!(matches!(read_1pux(&data), Err(VaultError::ImportFailed(_))));
+        let data = archive(&[("export.data", "not json")]);
+        assert!(matches!(read_1pux(&data), Err(VaultError::ImportFailed(_))));
+    }
+}
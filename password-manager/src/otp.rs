@@ -0,0 +1,379 @@
+//! One-time passwords: HOTP (RFC 4226) and TOTP (RFC 6238), set up from
+//! `otpauth://` URIs as shown in QR codes.
+
+use std::fmt;
+
+use crypto::digest::Digest;
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha1::Sha1;
+use crypto::sha2::{Sha256, Sha512};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, VaultError};
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// A shared secret for generating one-time codes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OtpSecret {
+    /// The key in base32, as it appears in `otpauth://` URIs.
+    pub secret: String,
+    pub algorithm: OtpAlgorithm,
+    pub digits: u32,
+    pub kind: OtpKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub enum OtpKind {
+    /// Time-based; a new code every `period` seconds.
+    Totp { period: u64 },
+    /// Counter-based; `counter` is the value for the next code.
+    Hotp { counter: u64 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// A generated code. `remaining` is how many seconds a TOTP code stays
+/// valid; HOTP codes do not expire.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OtpCode {
+    pub code: String,
+    pub remaining: Option<u64>,
+}
+
+impl OtpSecret {
+    /// A 6-digit, 30-second SHA-1 TOTP secret, the setup most sites use.
+    pub fn totp(secret: &str) -> Result<OtpSecret> {
+        let otp = OtpSecret {
+            secret: normalize_base32(secret),
+            algorithm: OtpAlgorithm::Sha1,
+            digits: 6,
+            kind: OtpKind::Totp { period: 30 },
+        };
+        otp.validate()?;
+        Ok(otp)
+    }
+
+    /// Parses `otpauth://totp/...` or `otpauth://hotp/...?counter=N`. Only
+    /// the parameters that affect the codes are kept.
+    pub fn from_uri(uri: &str) -> Result<OtpSecret> {
+        let rest = uri
+            .strip_prefix("otpauth://")
+            .ok_or_else(|| invalid("URI must start with otpauth://"))?;
+        let (kind, rest) = rest.split_once('/').ok_or_else(|| invalid("URI has no type"))?;
+        let query = rest.split_once('?').map_or("", |(_, query)| query);
+
+        let mut secret = None;
+        let mut algorithm = OtpAlgorithm::Sha1;
+        let mut digits = 6;
+        let mut period = 30;
+        let mut counter = None;
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            match name.to_ascii_lowercase().as_str() {
+                "secret" => secret = Some(normalize_base32(&value)),
+                "algorithm" => {
+                    algorithm = match value.to_ascii_uppercase().as_str() {
+                        "SHA1" => OtpAlgorithm::Sha1,
+                        "SHA256" => OtpAlgorithm::Sha256,
+                        "SHA512" => OtpAlgorithm::Sha512,
+                        _ => return Err(invalid(&format!("unsupported algorithm {}", value))),
+                    }
+                }
+                "digits" => digits = value.parse().map_err(|_| invalid("digits is not a number"))?,
+                "period" => period = value.parse().map_err(|_| invalid("period is not a number"))?,
+                "counter" => counter = Some(value.parse().map_err(|_| invalid("counter is not a number"))?),
+                _ => {}
+            }
+        }
+
+        let kind = match kind.to_ascii_lowercase().as_str() {
+            "totp" => OtpKind::Totp { period },
+            "hotp" => OtpKind::Hotp {
+                counter: counter.ok_or_else(|| invalid("HOTP URI has no counter"))?,
+            },
+            _ => return Err(invalid(&format!("unknown type {}", kind))),
+        };
+        let otp = OtpSecret {
+            secret: secret.ok_or_else(|| invalid("URI has no secret"))?,
+            algorithm,
+            digits,
+            kind,
+        };
+        otp.validate()?;
+        Ok(otp)
+    }
+
+    /// An `otpauth://` URI for this secret, labelled e.g. with the entry.
+    pub fn to_uri(&self, label: &str) -> String {
+        let (kind, extra) = match self.kind {
+            OtpKind::Totp { period } => ("totp", format!("period={}", period)),
+            OtpKind::Hotp { counter } => ("hotp", format!("counter={}", counter)),
+        };
+        format!(
+            "otpauth://{}/{}?secret={}&algorithm={}&digits={}&{}",
+            kind,
+            percent_encode(label),
+            self.secret,
+            self.algorithm,
+            self.digits,
+            extra
+        )
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        let key = decode_base32(&self.secret).ok_or_else(|| invalid("secret is not valid base32"))?;
+        if key.is_empty() {
+            return Err(invalid("secret is empty"));
+        }
+        if !(6..=8).contains(&self.digits) {
+            return Err(invalid("codes must have 6 to 8 digits"));
+        }
+        if self.kind == (OtpKind::Totp { period: 0 }) {
+            return Err(invalid("period must be positive"));
+        }
+        Ok(())
+    }
+
+    /// The code for `unix_time` (seconds). HOTP ignores the time and uses
+    /// up the current counter value. The fields are public and come from
+    /// vault files and imports, so they are checked again here.
+    pub fn generate(&mut self, unix_time: u64) -> Result<OtpCode> {
+        self.validate()?;
+        let key = decode_base32(&self.secret).ok_or_else(|| invalid("secret is not valid base32"))?;
+        match self.kind {
+            OtpKind::Totp { period } => Ok(OtpCode {
+                code: hotp(&key, unix_time / period, self.digits, self.algorithm),
+                remaining: Some(period - unix_time % period),
+            }),
+            OtpKind::Hotp { ref mut counter } => {
+                let code = hotp(&key, *counter, self.digits, self.algorithm);
+                *counter += 1;
+                Ok(OtpCode { code, remaining: None })
+            }
+        }
+    }
+}
+
+impl fmt::Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Sha512 => "SHA512",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn invalid(reason: &str) -> VaultError {
+    VaultError::InvalidOtp(reason.to_string())
+}
+
+// RFC 4226 section 5.3: HMAC the big-endian counter, then dynamic truncation.
+fn hotp(key: &[u8], counter: u64, digits: u32, algorithm: OtpAlgorithm) -> String {
+    let hash = match algorithm {
+        OtpAlgorithm::Sha1 => hmac(Sha1::new(), key, counter),
+        OtpAlgorithm::Sha256 => hmac(Sha256::new(), key, counter),
+        OtpAlgorithm::Sha512 => hmac(Sha512::new(), key, counter),
+    };
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]]) & 0x7fff_ffff;
+    format!("{:0width$}", binary % 10u32.pow(digits), width = digits as usize)
+}
+
+fn hmac<D: Digest>(digest: D, key: &[u8], counter: u64) -> Vec<u8> {
+    let mut mac = Hmac::new(digest, key);
+    mac.input(&counter.to_be_bytes());
+    mac.result().code().to_vec()
+}
+
+// Authenticator apps show secrets in groups, lower case or with padding.
+fn normalize_base32(secret: &str) -> String {
+    secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn decode_base32(secret: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(secret.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in secret.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+fn percent_decode(value: &str) -> Result<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value.get(i + 1..i + 3).ok_or_else(|| invalid("bad percent escape"))?;
+                decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid("bad percent escape"))?);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| invalid("URI is not valid UTF-8"))
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' | b':' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base32(key: &[u8]) -> String {
+        let mut encoded = String::new();
+        for chunk in key.chunks(5) {
+            let mut block = [0u8; 5];
+            block[..chunk.len()].copy_from_slice(chunk);
+            let value = block.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+            for i in 0..(chunk.len() * 8).div_ceil(5) {
+                encoded.push(BASE32_ALPHABET[((value >> (35 - 5 * i)) & 31) as usize] as char);
+            }
+        }
+        encoded
+    }
+
+    // RFC 4226 appendix D.
+    #[test]
+    fn hotp_rfc4226_vectors() {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489",
+        ];
+        let mut otp = OtpSecret {
+            secret: base32(b"12345678901234567890"),
+            algorithm: OtpAlgorithm::Sha1,
+            digits: 6,
+            kind: OtpKind::Hotp { counter: 0 },
+        };
+        for code in expected {
+            assert_eq!(otp.generate(0).unwrap(), OtpCode { code: code.to_string(), remaining: None });
+        }
+        assert_eq!(otp.kind, OtpKind::Hotp { counter: 10 });
+    }
+
+    // RFC 6238 appendix B.
+    #[test]
+    fn totp_rfc6238_vectors() {
+        let vectors: [(u64, [&str; 3]); 6] = [
+            (59, ["94287082", "46119246", "90693936"]),
+            (1111111109, ["07081804", "68084774", "25091201"]),
+            (1111111111, ["14050471", "67062674", "99943326"]),
+            (1234567890, ["89005924", "91819424", "93441116"]),
+            (2000000000, ["69279037", "90698825", "38618901"]),
+            (20000000000, ["65353130", "77737706", "47863826"]),
+        ];
+        let keys: [(OtpAlgorithm, &[u8]); 3] = [
+            (OtpAlgorithm::Sha1, b"12345678901234567890"),
+            (OtpAlgorithm::Sha256, b"12345678901234567890123456789012"),
+            (
+                OtpAlgorithm::Sha512,
+                b"1234567890123456789012345678901234567890123456789012345678901234",
+            ),
+        ];
+        for (time, codes) in vectors {
+            for ((algorithm, key), code) in keys.iter().zip(codes) {
+                let mut otp = OtpSecret {
+                    secret: base32(key),
+                    algorithm: *algorithm,
+                    digits: 8,
+                    kind: OtpKind::Totp { period: 30 },
+                };
+                assert_eq!(otp.generate(time).unwrap().code, code, "{} at {}", algorithm, time);
+            }
+        }
+    }
+
+    #[test]
+    fn totp_reports_seconds_remaining() {
+        let mut otp = OtpSecret::totp("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+        assert_eq!(otp.generate(59).unwrap().remaining, Some(1));
+        assert_eq!(otp.generate(60).unwrap().remaining, Some(30));
+    }
+
+    #[test]
+    fn refuses_to_generate_from_invalid_secrets() {
+        let mut otp = OtpSecret::totp("JBSWY3DP").unwrap();
+        otp.kind = OtpKind::Totp { period: 0 };
+        assert!(matches!(otp.generate(59), Err(VaultError::InvalidOtp(_))));
+
+        let mut otp = OtpSecret::totp("JBSWY3DP").unwrap();
+        otp.kind = OtpKind::Hotp { counter: 3 };
+        otp.digits = 10;
+        assert!(matches!(otp.generate(0), Err(VaultError::InvalidOtp(_))));
+        assert_eq!(otp.kind, OtpKind::Hotp { counter: 3 });
+    }
+
+    #[test]
+    fn parses_otpauth_uris() {
+        let otp = OtpSecret::from_uri(
+            "otpauth://totp/Example:alice%40example.com?secret=jbsw+y3dp&issuer=Example&algorithm=SHA256&digits=8&period=60",
+        )
+        .unwrap();
+        assert_eq!(otp.secret, "JBSWY3DP");
+        assert_eq!(otp.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!(otp.digits, 8);
+        assert_eq!(otp.kind, OtpKind::Totp { period: 60 });
+        assert_eq!(OtpSecret::from_uri(&otp.to_uri("alice@example.com")).unwrap(), otp);
+
+        let otp = OtpSecret::from_uri("otpauth://hotp/x?secret=JBSWY3DP&counter=7").unwrap();
+        assert_eq!(otp.kind, OtpKind::Hotp { counter: 7 });
+    }
+
+    #[test]
+    fn rejects_bad_uris() {
+        for uri in [
+            "https://example.com",
+            "otpauth://totp/x",
+            "otpauth://totp/x?secret=not*base32",
+            "otpauth://hotp/x?secret=JBSWY3DP",
+            "otpauth://totp/x?secret=JBSWY3DP&digits=4",
+            "otpauth://totp/x?secret=JBSWY3DP&period=0",
+            "otpauth://yotp/x?secret=JBSWY3DP",
+        ] {
+            assert!(matches!(OtpSecret::from_uri(uri), Err(VaultError::InvalidOtp(_))), "{}", uri);
+        }
+    }
+}
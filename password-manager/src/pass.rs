@@ -0,0 +1,580 @@
+//! Stores of `pass`, the standard Unix password manager: a directory tree
+//! with one GPG-encrypted file per entry and the recipients' key IDs in
+//! `.gpg-id`.
+//!
+//! A decrypted file holds the password on its first line, followed by
+//! `key: value` lines and free-form notes, which is how browser extensions
+//! for `pass` expect them:
+//!
+//! ```text
+//! correct horse battery staple
+//! login: octocat
+//! url: https://github.com/login
+//! otpauth://totp/GitHub:octocat?secret=JBSWY3DPEHPK3PXP
+//! Recovery codes: 1111 2222
+//! tags: work, dev
+//!
+//! Anything after the first blank line is notes.
+//! ```
+//!
+//! Custom field names that would be read back as something else (`url`,
+//! names with a colon, ...) are written as `\` and the name with `%`, `:`,
+//! `/` and whitespace percent-encoded, e.g. `\url%3A%20old: ...`. Values
+//! that are `|` or have surrounding whitespace use the multi-line form.
+//!
+//! An entry lives at `FOLDER/SERVICE.gpg`, or `FOLDER/USERNAME@SERVICE.gpg`
+//! when the folder has several entries for the service. Encryption and
+//! decryption go through a [`Gpg`] backend; [`GpgCommand`] runs the `gpg`
+//! program, optionally with its own keyring directory.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::{DateTime, Utc};
+
+use crate::entry::{CustomField, FieldKind, PasswordEntry};
+use crate::error::{Result, VaultError};
+use crate::folder;
+use crate::import::{self, ImportedEntries, SkippedRecord};
+
+const GPG_ID: &str = ".gpg-id";
+const EXTENSION: &str = "gpg";
+// Continuation lines of a multi-line value, after a `key: |` line.
+const INDENT: &str = "  ";
+// Starts a percent-encoded field name.
+const ESCAPE: char = '\\';
+// Keys `parse_entry` maps to the entry itself rather than a custom field.
+const RESERVED_KEYS: [&str; 8] = ["login", "username", "user", "email", "url", "website", "link", "tags"];
+
+/// Encrypts and decrypts the files of a store.
+pub trait Gpg {
+    /// Encrypts `plaintext` so that any of `recipients` (key IDs, emails or
+    /// fingerprints, as in `.gpg-id`) can decrypt it.
+    fn encrypt(&self, plaintext: &[u8], recipients: &[String]) -> io::Result<Vec<u8>>;
+
+    fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// The `gpg` program. Keys must already be in the keyring: nothing is
+/// looked up on key servers.
+#[derive(Clone, Debug)]
+pub struct GpgCommand {
+    pub program: PathBuf,
+    /// Keyring directory to use instead of `~/.gnupg`.
+    pub homedir: Option<PathBuf>,
+}
+
+impl Default for GpgCommand {
+    fn default() -> GpgCommand {
+        GpgCommand {
+            program: PathBuf::from("gpg"),
+            homedir: None,
+        }
+    }
+}
+
+impl GpgCommand {
+    fn run(&self, args: &[&str], input: &[u8]) -> io::Result<Vec<u8>> {
+        let mut command = Command::new(&self.program);
+        if let Some(ref homedir) = self.homedir {
+            command.arg("--homedir").arg(homedir);
+        }
+        let mut child = command
+            .args(["--batch", "--quiet", "--yes", "--auto-key-locate", "local"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("cannot run {}: {}", self.program.display(), e)))?;
+
+        // Fed from another thread so a full output pipe cannot stall gpg.
+        let mut stdin = child.stdin.take().expect("piped");
+        let output = std::thread::scope(|scope| {
+            scope.spawn(move || stdin.write_all(input));
+            child.wait_with_output()
+        })?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(io::Error::other(format!("gpg failed: {}", message)));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl Gpg for GpgCommand {
+    fn encrypt(&self, plaintext: &[u8], recipients: &[String]) -> io::Result<Vec<u8>> {
+        // Recipients are named explicitly, so their keys need no signatures.
+        let mut args = vec!["--trust-model", "always", "--encrypt"];
+        for recipient in recipients {
+            args.extend(["--recipient", recipient]);
+        }
+        self.run(&args, plaintext)
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+        self.run(&["--decrypt"], ciphertext)
+    }
+}
+
+/// Reads every entry of the store at `store`. Files that cannot be
+/// decrypted are skipped and reported.
+pub fn read_pass_store(store: &Path, gpg: &dyn Gpg) -> Result<ImportedEntries> {
+    if !store.is_dir() {
+        return Err(VaultError::ImportFailed(format!("no password store at {}", store.display())));
+    }
+    let mut imported = ImportedEntries::default();
+    read_dir(store, "", gpg, &mut imported)?;
+    Ok(imported)
+}
+
+fn read_dir(dir: &Path, path: &str, gpg: &dyn Gpg, imported: &mut ImportedEntries) -> Result<()> {
+    let mut children: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+    children.sort_by_key(|child| child.file_name());
+    for child in children {
+        let name = child.file_name().to_string_lossy().into_owned();
+        // .git, .gpg-id, .extensions and the like.
+        if name.starts_with('.') {
+            continue;
+        }
+        let child_path = if path.is_empty() { name.clone() } else { format!("{}/{}", path, name) };
+        if child.file_type()?.is_dir() {
+            imported.folders.push(child_path.clone());
+            read_dir(&child.path(), &child_path, gpg, imported)?;
+            continue;
+        }
+        let Some(service) = name.strip_suffix(".gpg") else {
+            continue;
+        };
+        let contents = match gpg.decrypt(&fs::read(child.path())?) {
+            Ok(contents) => contents,
+            Err(e) => {
+                imported.skipped.push(SkippedRecord::new(child_path, format!("cannot decrypt: {}", e)));
+                continue;
+            }
+        };
+        let Ok(text) = String::from_utf8(contents) else {
+            imported.skipped.push(SkippedRecord::new(child_path, "not a text file"));
+            continue;
+        };
+        let mut entry = parse_entry(service, &text);
+        entry.folder = path.to_string();
+        if let Ok(modified) = child.metadata().and_then(|metadata| metadata.modified()) {
+            entry.created = DateTime::<Utc>::from(modified);
+            entry.modified = entry.created;
+        }
+        imported.entries.push(entry);
+    }
+    Ok(())
+}
+
+fn parse_entry(name: &str, text: &str) -> PasswordEntry {
+    let mut lines = text.lines();
+    let password = lines.next().unwrap_or("").to_string();
+    let mut entry = PasswordEntry::new(name.to_string(), String::new(), password);
+    let mut notes = Vec::new();
+    let mut email = None;
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            notes.extend(lines.by_ref());
+            break;
+        }
+        if line.starts_with("otpauth://") {
+            import::set_otp(&mut entry, line);
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':').filter(|(key, _)| key.starts_with(ESCAPE) || is_key(key)) else {
+            notes.push(line);
+            continue;
+        };
+        let mut value = value.trim().to_string();
+        if value == "|" {
+            value.clear();
+            while let Some(continued) = lines.clone().next().and_then(|next| next.strip_prefix(INDENT)) {
+                lines.next();
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(continued);
+            }
+        }
+        if let Some(escaped) = key.trim_end().strip_prefix(ESCAPE) {
+            entry.fields.push(CustomField {
+                name: unescape_key(escaped),
+                kind: FieldKind::Text,
+                value,
+            });
+            continue;
+        }
+        match key.trim().to_lowercase().as_str() {
+            "login" | "username" | "user" if entry.username.is_empty() => entry.username = value,
+            "email" if email.is_none() => email = Some(value),
+            "url" | "website" | "link" => entry.urls.push(value),
+            "tags" => entry.tags.extend(value.split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(String::from)),
+            _ => entry.fields.push(CustomField {
+                name: key.trim().to_string(),
+                kind: FieldKind::Text,
+                value,
+            }),
+        }
+    }
+    match email {
+        Some(email) if entry.username.is_empty() => entry.username = email,
+        Some(email) => entry.fields.push(CustomField {
+            name: "email".to_string(),
+            kind: FieldKind::Email,
+            value: email,
+        }),
+        None => {}
+    }
+    // Undo the USERNAME@SERVICE naming used for entries sharing a service.
+    if let Some(service) = name.strip_prefix(&format!("{}@", entry.username)).filter(|_| !entry.username.is_empty()) {
+        entry.service = service.to_string();
+    }
+    entry.notes = notes.join("\n");
+    entry
+}
+
+// Whether the text before a colon looks like a field name rather than
+// the start of a note, or a URL on a line of its own.
+fn is_key(key: &str) -> bool {
+    let key = key.trim_end();
+    !key.is_empty() && key.len() <= 40 && !key.starts_with(char::is_whitespace) && !key.contains("//")
+}
+
+// The key a custom field is written under; see the module docs.
+fn field_key(name: &str) -> String {
+    let plain = is_key(name)
+        && name == name.trim_end()
+        && !name.contains(':')
+        && !name.starts_with(ESCAPE)
+        && !name.chars().any(char::is_control)
+        && !RESERVED_KEYS.contains(&name.to_lowercase().as_str());
+    if plain {
+        return name.to_string();
+    }
+    let mut key = ESCAPE.to_string();
+    for c in name.chars() {
+        if matches!(c, '%' | ':' | '/') || c.is_whitespace() || c.is_control() {
+            for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                key.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            key.push(c);
+        }
+    }
+    key
+}
+
+fn unescape_key(key: &str) -> String {
+    let bytes = key.as_bytes();
+    let mut name = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) if bytes[i] == b'%' => {
+                name.push(byte);
+                i += 3;
+            }
+            _ => {
+                name.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+/// Writes `entries` as a new store at `store`, encrypted for `recipients`.
+/// `folders` become directories even when empty. The store must not exist
+/// yet or be empty. Returns how many entries were written.
+pub fn write_pass_store(
+    entries: &[&PasswordEntry],
+    folders: &[&str],
+    store: &Path,
+    recipients: &[String],
+    gpg: &dyn Gpg,
+) -> Result<usize> {
+    if recipients.is_empty() {
+        return Err(VaultError::Io(io::Error::new(io::ErrorKind::InvalidInput, "no GPG recipients given")));
+    }
+    if store.exists() && fs::read_dir(store)?.next().is_some() {
+        let message = format!("{} is not empty", store.display());
+        return Err(VaultError::Io(io::Error::new(io::ErrorKind::AlreadyExists, message)));
+    }
+    fs::create_dir_all(store)?;
+    fs::write(store.join(GPG_ID), recipients.join("\n") + "\n")?;
+    for path in folders {
+        fs::create_dir_all(store.join(folder_dir(path)))?;
+    }
+
+    let mut taken = HashSet::new();
+    for entry in entries {
+        let dir = folder_dir(&entry.folder);
+        let shared = entries.iter().filter(|other| other.folder == entry.folder && file_name(&other.service) == file_name(&entry.service)).count() > 1;
+        let base = if shared && !entry.username.is_empty() { file_name(&entry.label()) } else { file_name(&entry.service) };
+        let mut path = dir.join(format!("{}.{}", base, EXTENSION));
+        for n in 2.. {
+            if taken.insert(path.clone()) {
+                break;
+            }
+            path = dir.join(format!("{} ({}).{}", base, n, EXTENSION));
+        }
+
+        let encrypted = gpg.encrypt(format_entry(entry).as_bytes(), recipients)?;
+        fs::create_dir_all(store.join(&dir))?;
+        fs::write(store.join(&path), encrypted)?;
+    }
+    Ok(entries.len())
+}
+
+fn folder_dir(path: &str) -> PathBuf {
+    folder::normalize(path).split(folder::SEPARATOR).filter(|part| !part.is_empty()).map(file_name).collect()
+}
+
+// Path separators and leading dots would put the file somewhere else.
+fn file_name(name: &str) -> String {
+    let name = name.trim().replace(['/', '\\', '\0'], "-");
+    if name.is_empty() || name.starts_with('.') {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+fn format_entry(entry: &PasswordEntry) -> String {
+    let mut text = format!("{}\n", entry.password);
+    let mut line = |key: &str, value: &str| {
+        // A lone `|` would start a multi-line value, and single-line values
+        // are trimmed when read.
+        if value.contains('\n') || value == "|" || value != value.trim() {
+            text.push_str(&format!("{}: |\n", key));
+            for part in value.lines() {
+                text.push_str(&format!("{}{}\n", INDENT, part));
+            }
+        } else {
+            text.push_str(&format!("{}: {}\n", key, value));
+        }
+    };
+    if !entry.username.is_empty() {
+        line("login", &entry.username);
+    }
+    for url in &entry.urls {
+        line("url", url);
+    }
+    for field in &entry.fields {
+        line(&field_key(&field.name), &field.value);
+    }
+    if !entry.tags.is_empty() {
+        line("tags", &entry.tags.join(", "));
+    }
+    if let Some(otp) = &entry.otp {
+        text.push_str(&format!("{}\n", otp.to_uri(&entry.label())));
+    }
+    if !entry.notes.is_empty() {
+        text.push_str(&format!("\n{}\n", entry.notes));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::otp::OtpSecret;
+
+    // Stands in for gpg: "encrypts" by prefixing the recipients.
+    struct FakeGpg;
+
+    impl Gpg for FakeGpg {
+        fn encrypt(&self, plaintext: &[u8], recipients: &[String]) -> io::Result<Vec<u8>> {
+            Ok([format!("{}\n", recipients.join(",")).as_bytes(), plaintext].concat())
+        }
+
+        fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+            let start = ciphertext.iter().position(|&b| b == b'\n').ok_or_else(|| io::Error::other("not ours"))?;
+            Ok(ciphertext[start + 1..].to_vec())
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pass-test-{}-{}", name, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn sample_entries() -> Vec<PasswordEntry> {
+        let mut github = PasswordEntry::new("github.com".into(), "octocat".into(), "hunter2: not a key".into());
+        github.urls = vec!["https://github.com/login".into(), "https://gist.github.com".into()];
+        github.folder = "Work/Code".into();
+        github.tags = vec!["work".into(), "dev".into()];
+        github.notes = "first line\nsecond: line".into();
+        github.otp = Some(OtpSecret::totp("JBSWY3DPEHPK3PXP").unwrap());
+        github.fields.push(CustomField {
+            name: "Recovery codes".into(),
+            kind: FieldKind::Text,
+            value: "1111 2222".into(),
+        });
+        github.fields.push(CustomField {
+            name: "Private key".into(),
+            kind: FieldKind::Text,
+            value: "-----BEGIN KEY-----\nabc\n-----END KEY-----".into(),
+        });
+        let mut bot = PasswordEntry::new("github.com".into(), "bot".into(), "b0t".into());
+        bot.folder = "Work/Code".into();
+        let odd = PasswordEntry::new("a/b ../c".into(), String::new(), "x".into());
+        vec![github, bot, odd]
+    }
+
+    #[test]
+    fn store_round_trips() {
+        let store = temp_dir("round-trip").join("store");
+        let entries = sample_entries();
+        let refs: Vec<&PasswordEntry> = entries.iter().collect();
+        assert_eq!(write_pass_store(&refs, &["Work/Code", "Archive"], &store, &["alice@example.com".into()], &FakeGpg).unwrap(), 3);
+        assert_eq!(fs::read_to_string(store.join(".gpg-id")).unwrap(), "alice@example.com\n");
+        assert!(store.join("Work/Code/octocat@github.com.gpg").exists());
+        assert!(store.join("Work/Code/bot@github.com.gpg").exists());
+        assert!(store.join("a-b ..-c.gpg").exists());
+
+        let imported = read_pass_store(&store, &FakeGpg).unwrap();
+        assert!(imported.skipped.is_empty());
+        assert_eq!(imported.folders, ["Archive", "Work", "Work/Code"]);
+        assert_eq!(imported.entries.len(), 3);
+        for original in &entries {
+            let back = imported.entries.iter().find(|entry| entry.username == original.username).unwrap();
+            assert_eq!(back.service, original.service.replace('/', "-"));
+            assert_eq!(back.password, original.password);
+            assert_eq!(back.folder, original.folder);
+            assert_eq!(back.urls, original.urls);
+            assert_eq!(back.tags, original.tags);
+            assert_eq!(back.notes, original.notes);
+            assert_eq!(back.otp, original.otp);
+            let fields = |entry: &PasswordEntry| -> Vec<(String, String)> {
+                entry.fields.iter().map(|field| (field.name.clone(), field.value.clone())).collect()
+            };
+            assert_eq!(fields(back), fields(original));
+        }
+        fs::remove_dir_all(store.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn awkward_field_names_and_values_round_trip() {
+        let mut entry = PasswordEntry::new("example.com".into(), "jo".into(), "s3cret".into());
+        for (name, value) in [
+            ("login", "second login"),
+            ("URL", "not a link"),
+            ("email", "jo@example.org"),
+            ("tags", "a, b"),
+            ("Security question: first pet", "Rex"),
+            ("https://example.com", "a URL as the name"),
+            ("\\backslash 100%", "|"),
+            ("", "  padded  "),
+            (" leading space", "x"),
+            ("a very long field name that is well over forty characters", "y"),
+            ("PIN", "1234"),
+        ] {
+            entry.fields.push(CustomField {
+                name: name.into(),
+                kind: FieldKind::Text,
+                value: value.into(),
+            });
+        }
+        let text = format_entry(&entry);
+        assert!(text.contains("\nPIN: 1234\n"), "{}", text);
+        assert!(text.contains("\n\\Security%20question%3A%20first%20pet: Rex\n"), "{}", text);
+
+        let back = parse_entry("example.com", &text);
+        assert_eq!((back.username.as_str(), back.password.as_str()), ("jo", "s3cret"));
+        assert!(back.urls.is_empty());
+        assert!(back.tags.is_empty());
+        assert!(back.notes.is_empty());
+        let fields = |entry: &PasswordEntry| -> Vec<(String, String)> {
+            entry.fields.iter().map(|field| (field.name.clone(), field.value.clone())).collect()
+        };
+        assert_eq!(fields(&back), fields(&entry));
+    }
+
+    #[test]
+    fn reads_files_written_by_hand() {
+        let store = temp_dir("by-hand");
+        fs::create_dir_all(store.join("Email")).unwrap();
+        fs::create_dir_all(store.join(".git")).unwrap();
+        let files = [
+            ("Email/example.com.gpg", "s3cret\nemail: jo@example.com\nWebsite: https://mail.example.com\nPIN: 1234\nremember to rotate"),
+            ("plain.gpg", "only a password\n"),
+            (".git/config.gpg", "ignored"),
+            ("README", "not an entry"),
+        ];
+        for (path, text) in files {
+            fs::write(store.join(path), FakeGpg.encrypt(text.as_bytes(), &[]).unwrap()).unwrap();
+        }
+        fs::write(store.join("broken.gpg"), "no newline").unwrap();
+
+        let imported = read_pass_store(&store, &FakeGpg).unwrap();
+        assert_eq!(imported.entries.len(), 2);
+        let mail = imported.entries.iter().find(|entry| entry.service == "example.com").unwrap();
+        assert_eq!((mail.folder.as_str(), mail.username.as_str(), mail.password.as_str()), ("Email", "jo@example.com", "s3cret"));
+        assert_eq!(mail.urls, ["https://mail.example.com"]);
+        assert_eq!((mail.fields[0].name.as_str(), mail.fields[0].value.as_str()), ("PIN", "1234"));
+        assert_eq!(mail.notes, "remember to rotate");
+        let plain = imported.entries.iter().find(|entry| entry.service == "plain").unwrap();
+        assert_eq!((plain.password.as_str(), plain.username.as_str()), ("only a password", ""));
+        assert_eq!(imported.skipped.len(), 1);
+        assert_eq!(imported.skipped[0].location, "broken.gpg");
+        fs::remove_dir_all(store).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_write_into_a_used_directory() {
+        let store = temp_dir("used");
+        fs::write(store.join("keep.gpg"), "x").unwrap();
+        let entries = sample_entries();
+        let refs: Vec<&PasswordEntry> = entries.iter().collect();
+        assert!(write_pass_store(&refs, &[], &store, &["alice".into()], &FakeGpg).is_err());
+        assert!(write_pass_store(&refs, &[], &store.join("new"), &[], &FakeGpg).is_err());
+        fs::remove_dir_all(store).unwrap();
+    }
+
+    // Uses a throwaway keyring, so it needs gpg but no network or agent
+    // state of the user; skipped when gpg is not installed.
+    #[test]
+    fn round_trips_through_real_gpg() {
+        let dir = temp_dir("gpg");
+        let gpg = GpgCommand {
+            homedir: Some(dir.join("gnupg")),
+            ..GpgCommand::default()
+        };
+        fs::create_dir_all(gpg.homedir.as_ref().unwrap()).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(gpg.homedir.as_ref().unwrap(), fs::Permissions::from_mode(0o700)).unwrap();
+        }
+        let generated = Command::new(&gpg.program)
+            .arg("--homedir")
+            .arg(gpg.homedir.as_ref().unwrap())
+            .args(["--batch", "--quiet", "--passphrase", "", "--quick-generate-key", "Test <test@example.com>", "future-default", "default", "never"])
+            .status();
+        if !generated.is_ok_and(|status| status.success()) {
+            eprintln!("gpg is not available; skipping");
+            fs::remove_dir_all(dir).unwrap();
+            return;
+        }
+
+        let entries = sample_entries();
+        let refs: Vec<&PasswordEntry> = entries.iter().collect();
+        let store = dir.join("store");
+        write_pass_store(&refs, &[], &store, &["test@example.com".into()], &gpg).unwrap();
+        let raw = fs::read(store.join("Work/Code/octocat@github.com.gpg")).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("hunter2"));
+        let imported = read_pass_store(&store, &gpg).unwrap();
+        assert!(imported.skipped.is_empty());
+        let github = imported.entries.iter().find(|entry| entry.username == "octocat").unwrap();
+        assert_eq!(github.password, "hunter2: not a key");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,205 @@
+//! Diceware-style passphrases: words drawn uniformly at random from a list.
+
+use std::fs;
+use std::path::Path;
+
+use rand::Rng;
+
+use crate::error::{Result, VaultError};
+use crate::generator::SYMBOLS;
+
+// The EFF large wordlist: 7776 words (12.9 bits each), one per line after
+// its five dice rolls. By the Electronic Frontier Foundation, licensed under
+// CC BY 3.0 US (https://creativecommons.org/licenses/by/3.0/us/); see
+// https://www.eff.org/dice.
+const DEFAULT_WORDLIST: &str = include_str!("../wordlists/eff_large_wordlist.txt");
+
+/// The words a passphrase is built from. Duplicates are dropped so the
+/// reported entropy matches what is actually drawn.
+#[derive(Clone, Debug)]
+pub struct Wordlist {
+    words: Vec<String>,
+}
+
+impl Wordlist {
+    /// The list that ships with the crate: the EFF large wordlist, 7776
+    /// words of 12.9 bits each. Other lists can be loaded with
+    /// [`from_file`](Self::from_file).
+    pub fn builtin() -> Wordlist {
+        Wordlist::parse(DEFAULT_WORDLIST).expect("built-in wordlist is valid")
+    }
+
+    /// Loads a list with one word per line. Dice-numbered lines as in the
+    /// EFF lists (`11111<TAB>abacus`) are accepted too; blank lines are
+    /// skipped.
+    pub fn from_file(path: &Path) -> Result<Wordlist> {
+        Wordlist::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(contents: &str) -> Result<Wordlist> {
+        let mut words: Vec<String> = contents
+            .lines()
+            .filter_map(|line| line.split_whitespace().last())
+            .map(str::to_string)
+            .collect();
+        words.sort();
+        words.dedup();
+        if words.len() < 2 {
+            return Err(VaultError::InvalidPolicy("the wordlist needs at least two distinct words".to_string()));
+        }
+        Ok(Wordlist { words })
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+/// How to build a passphrase.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassphrasePolicy {
+    pub words: usize,
+    pub separator: String,
+    /// Start every word with a capital letter.
+    pub capitalize: bool,
+    /// Append a random digit to a random word.
+    pub add_digit: bool,
+    /// Append a random symbol to a random word.
+    pub add_symbol: bool,
+}
+
+impl Default for PassphrasePolicy {
+    fn default() -> PassphrasePolicy {
+        PassphrasePolicy {
+            words: 6,
+            separator: "-".to_string(),
+            capitalize: false,
+            add_digit: false,
+            add_symbol: false,
+        }
+    }
+}
+
+/// A generated passphrase and the entropy of the process that produced it.
+#[derive(Clone, Debug)]
+pub struct Passphrase {
+    pub passphrase: String,
+    pub entropy_bits: f64,
+}
+
+impl PassphrasePolicy {
+    pub fn generate(&self, wordlist: &Wordlist) -> Result<Passphrase> {
+        if self.words == 0 {
+            return Err(VaultError::InvalidPolicy("a passphrase needs at least one word".to_string()));
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut words: Vec<String> = (0..self.words)
+            .map(|_| wordlist.words[rng.gen_range(0..wordlist.len())].clone())
+            .collect();
+        if self.capitalize {
+            words = words.iter().map(|word| capitalize(word)).collect();
+        }
+
+        let mut entropy_bits = self.words as f64 * (wordlist.len() as f64).log2();
+        // Which word gets the extra character adds to the entropy as well.
+        let position_bits = (self.words as f64).log2();
+        if self.add_digit {
+            let digit = char::from(b'0' + rng.gen_range(0..10u8));
+            words[rng.gen_range(0..self.words)].push(digit);
+            entropy_bits += 10f64.log2() + position_bits;
+        }
+        if self.add_symbol {
+            let symbol = SYMBOLS.as_bytes()[rng.gen_range(0..SYMBOLS.len())] as char;
+            words[rng.gen_range(0..self.words)].push(symbol);
+            entropy_bits += (SYMBOLS.len() as f64).log2() + position_bits;
+        }
+
+        Ok(Passphrase {
+            passphrase: words.join(&self.separator),
+            entropy_bits,
+        })
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_list_is_the_eff_large_list() {
+        let wordlist = Wordlist::builtin();
+        assert_eq!(wordlist.len(), 7776);
+        assert_eq!(wordlist.words.first().map(String::as_str), Some("abacus"));
+        assert_eq!(wordlist.words.last().map(String::as_str), Some("zoom"));
+        // A few EFF words such as "t-shirt" contain the default separator.
+        let policy = PassphrasePolicy {
+            separator: " ".to_string(),
+            ..PassphrasePolicy::default()
+        };
+        let passphrase = policy.generate(&wordlist).unwrap();
+        let words: Vec<&str> = passphrase.passphrase.split(' ').collect();
+        assert_eq!(words.len(), 6);
+        assert!(words.iter().all(|word| wordlist.words.binary_search(&word.to_string()).is_ok()));
+        // 6 * log2(7776) = 6 * 12.925 bits.
+        assert!((passphrase.entropy_bits - 6.0 * 7776f64.log2()).abs() < 1e-9);
+        assert!((passphrase.entropy_bits - 77.55).abs() < 0.01, "{}", passphrase.entropy_bits);
+    }
+
+    #[test]
+    fn reads_dice_numbered_lists() {
+        // Same layout as eff_large_wordlist.txt: 6^5 lines of five dice
+        // rolls, a tab and the word.
+        let mut contents = String::new();
+        for n in 0..7776u32 {
+            let rolls: String = (0..5).rev().map(|i| char::from(b'1' + (n / 6u32.pow(i) % 6) as u8)).collect();
+            contents.push_str(&format!("{}\tword{}\n", rolls, n));
+        }
+        let wordlist = Wordlist::parse(&contents).unwrap();
+        assert_eq!(wordlist.len(), 7776);
+        assert!(wordlist.words.iter().all(|word| word.starts_with("word")));
+        let policy = PassphrasePolicy {
+            words: 4,
+            ..PassphrasePolicy::default()
+        };
+        let entropy = policy.generate(&wordlist).unwrap().entropy_bits;
+        assert!((entropy / 4.0 - 12.925).abs() < 0.001, "{}", entropy);
+    }
+
+    #[test]
+    fn extras_add_their_entropy() {
+        let wordlist = Wordlist::parse("alpha\nbeta\ngamma\ndelta\nalpha\n").unwrap();
+        assert_eq!(wordlist.len(), 4);
+        let policy = PassphrasePolicy {
+            words: 4,
+            separator: " ".to_string(),
+            capitalize: true,
+            add_digit: true,
+            add_symbol: false,
+        };
+        let passphrase = policy.generate(&wordlist).unwrap();
+        // 2 bits per word, plus a digit placed after one of four words.
+        assert!((passphrase.entropy_bits - (8.0 + 10f64.log2() + 2.0)).abs() < 1e-9);
+        assert!(passphrase.passphrase.split(' ').all(|word| word.starts_with(char::is_uppercase)));
+        assert_eq!(passphrase.passphrase.chars().filter(char::is_ascii_digit).count(), 1);
+
+        assert!(Wordlist::parse("only\n\nonly\n").is_err());
+        let no_words = PassphrasePolicy {
+            words: 0,
+            ..PassphrasePolicy::default()
+        };
+        assert!(matches!(no_words.generate(&wordlist), Err(VaultError::InvalidPolicy(_))));
+    }
+}
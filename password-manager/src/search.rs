@@ -0,0 +1,269 @@
+//! Ranked, case-insensitive search over entries and sorted listings.
+//!
+//! Each word of a query must match some field of an entry (service,
+//! username, URLs, tags, folder or notes), either as a substring or, for
+//! short fields, as a fuzzy subsequence (`gthb` finds `github`). Exact and
+//! prefix matches rank above substrings, which rank above fuzzy matches,
+//! and a hit in the service name counts for more than one in the notes.
+
+use std::cmp::Reverse;
+use std::fmt;
+
+use crate::entry::PasswordEntry;
+
+/// Where a query word was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SearchField {
+    Service,
+    Username,
+    Url,
+    Tag,
+    Folder,
+    Notes,
+}
+
+impl SearchField {
+    // How much a match in this field is worth relative to the others.
+    fn weight(self) -> u32 {
+        match self {
+            SearchField::Service => 3,
+            SearchField::Username | SearchField::Url | SearchField::Tag => 2,
+            SearchField::Folder | SearchField::Notes => 1,
+        }
+    }
+
+    // Notes are free text, where almost any query is a subsequence.
+    fn allows_fuzzy(self) -> bool {
+        self != SearchField::Notes
+    }
+}
+
+impl fmt::Display for SearchField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SearchField::Service => "service",
+            SearchField::Username => "username",
+            SearchField::Url => "url",
+            SearchField::Tag => "tag",
+            SearchField::Folder => "folder",
+            SearchField::Notes => "notes",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One entry found by [`PasswordManager::search`](crate::PasswordManager::search).
+#[derive(Clone)]
+pub struct SearchResult<'a> {
+    pub entry: &'a PasswordEntry,
+    /// Higher is a better match; only meaningful relative to other results.
+    pub score: u32,
+    /// The fields the query matched in, without repeats.
+    pub fields: Vec<SearchField>,
+}
+
+/// Orders for [`PasswordManager::list_sorted`](crate::PasswordManager::list_sorted).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// By service, then username, ignoring case.
+    #[default]
+    Name,
+    /// Most recently used first; never used entries last.
+    LastUsed,
+    /// Most recently modified first.
+    Modified,
+}
+
+// Scores for one query word against one field, before the field weight.
+const EXACT: u32 = 100;
+const PREFIX: u32 = 80;
+const WORD_PREFIX: u32 = 60;
+const SUBSTRING: u32 = 40;
+// Fuzzy matches are clamped below this so they never beat a substring.
+const FUZZY_MAX: u32 = 39;
+
+pub(crate) fn search<'a>(entries: &[&'a PasswordEntry], query: &str) -> Vec<SearchResult<'a>> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+
+    let mut results: Vec<SearchResult> = entries
+        .iter()
+        .filter_map(|&entry| {
+            let haystacks = fields(entry);
+            let mut score = 0;
+            let mut matched = Vec::new();
+            for word in &words {
+                let (best, field) = haystacks
+                    .iter()
+                    .filter_map(|(field, text)| match_word(word, text, field.allows_fuzzy()).map(|s| (s * field.weight(), *field)))
+                    .max()?;
+                score += best;
+                matched.push(field);
+            }
+            matched.sort();
+            matched.dedup();
+            Some(SearchResult {
+                entry,
+                score,
+                fields: matched,
+            })
+        })
+        .collect();
+    results.sort_by(|a, b| {
+        Reverse(a.score)
+            .cmp(&Reverse(b.score))
+            .then_with(|| name_key(a.entry).cmp(&name_key(b.entry)))
+    });
+    results
+}
+
+pub(crate) fn sort(entries: &mut [&PasswordEntry], order: SortOrder) {
+    match order {
+        SortOrder::Name => entries.sort_by_cached_key(|entry| name_key(entry)),
+        SortOrder::LastUsed => entries.sort_by(|a, b| b.last_used.cmp(&a.last_used).then_with(|| name_key(a).cmp(&name_key(b)))),
+        SortOrder::Modified => entries.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| name_key(a).cmp(&name_key(b)))),
+    }
+}
+
+fn name_key(entry: &PasswordEntry) -> (String, String) {
+    (entry.service.to_lowercase(), entry.username.to_lowercase())
+}
+
+fn fields(entry: &PasswordEntry) -> Vec<(SearchField, String)> {
+    let mut fields = vec![
+        (SearchField::Service, entry.service.to_lowercase()),
+        (SearchField::Username, entry.username.to_lowercase()),
+    ];
+    fields.extend(entry.urls.iter().map(|url| (SearchField::Url, url.to_lowercase())));
+    fields.extend(entry.tags.iter().map(|tag| (SearchField::Tag, tag.to_lowercase())));
+    fields.push((SearchField::Folder, entry.folder.to_lowercase()));
+    fields.push((SearchField::Notes, entry.notes.to_lowercase()));
+    fields
+}
+
+// `word` and `text` are already lowercase.
+fn match_word(word: &str, text: &str, fuzzy: bool) -> Option<u32> {
+    if text.is_empty() {
+        return None;
+    }
+    if text == word {
+        return Some(EXACT);
+    }
+    if text.starts_with(word) {
+        return Some(PREFIX);
+    }
+    if let Some(start) = text.find(word) {
+        let at_word_start = text[..start].chars().next_back().is_none_or(|c| !c.is_alphanumeric());
+        return Some(if at_word_start { WORD_PREFIX } else { SUBSTRING });
+    }
+    if fuzzy {
+        fuzzy_score(word, text)
+    } else {
+        None
+    }
+}
+
+// Scores `word` as a subsequence of `text`, trying every start position:
+// consecutive characters and characters at word starts earn a bonus, and
+// every skipped character between the first and last match costs a point.
+fn fuzzy_score(word: &str, text: &str) -> Option<u32> {
+    let word: Vec<char> = word.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let starts_word = |i: usize| i == 0 || !text[i - 1].is_alphanumeric();
+
+    let mut best: Option<i64> = None;
+    for start in (0..text.len()).filter(|&i| text[i] == word[0]) {
+        let mut score = if starts_word(start) { 13 } else { 10 };
+        let mut last = start;
+        let mut complete = true;
+        for &c in &word[1..] {
+            let Some(offset) = text[last + 1..].iter().position(|&t| t == c) else {
+                complete = false;
+                break;
+            };
+            let i = last + 1 + offset;
+            if i == last + 1 {
+                score += 2;
+            } else {
+                score -= offset as i64;
+            }
+            if starts_word(i) {
+                score += 3;
+            }
+            last = i;
+        }
+        if complete {
+            best = best.max(Some(score));
+        }
+    }
+    best.filter(|&score| score > 0).map(|score| (score as u32).min(FUZZY_MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(service: &str, username: &str) -> PasswordEntry {
+        PasswordEntry::new(service.to_string(), username.to_string(), "pw".to_string())
+    }
+
+    fn services<'a>(results: &[SearchResult<'a>]) -> Vec<&'a str> {
+        results.iter().map(|result| result.entry.service.as_str()).collect()
+    }
+
+    #[test]
+    fn ranks_exact_then_prefix_then_substring_then_fuzzy() {
+        let entries: Vec<PasswordEntry> =
+            ["legit", "gait", "github", "old-git", "git", "mail"].iter().map(|service| entry(service, "")).collect();
+        let refs: Vec<&PasswordEntry> = entries.iter().collect();
+        let results = search(&refs, "git");
+        assert_eq!(services(&results), ["git", "github", "old-git", "legit", "gait"]);
+        let scores: Vec<u32> = results.iter().map(|result| result.score).collect();
+        assert!(scores.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", scores);
+        assert!(results.iter().all(|result| result.fields == [SearchField::Service]));
+    }
+
+    #[test]
+    fn ignores_case() {
+        let entries = [entry("GitHub", "OctoCat")];
+        let refs: Vec<&PasswordEntry> = entries.iter().collect();
+        let results = search(&refs, "GITHUB octocat");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].score, EXACT * SearchField::Service.weight() + EXACT * SearchField::Username.weight());
+        assert_eq!(results[0].fields, [SearchField::Service, SearchField::Username]);
+    }
+
+    #[test]
+    fn matches_urls_tags_and_notes() {
+        let mut bank = entry("bank", "me");
+        bank.urls.push("https://online.examplebank.com".to_string());
+        bank.tags.push("Finance".to_string());
+        bank.notes = "Security questions: first pet was Rex".to_string();
+        bank.folder = "Personal".to_string();
+        let entries = [bank, entry("mail", "bob")];
+        let refs: Vec<&PasswordEntry> = entries.iter().collect();
+
+        let found = |query: &str| search(&refs, query).iter().map(|result| result.fields.clone()).collect::<Vec<_>>();
+        assert_eq!(found("examplebank"), [vec![SearchField::Url]]);
+        assert_eq!(found("finance"), [vec![SearchField::Tag]]);
+        assert_eq!(found("pet"), [vec![SearchField::Notes]]);
+        assert_eq!(found("personal"), [vec![SearchField::Folder]]);
+        // Every word has to match somewhere.
+        assert_eq!(found("finance rex"), [vec![SearchField::Tag, SearchField::Notes]]);
+        assert!(found("finance bob").is_empty());
+        // Notes only match as substrings, never fuzzily.
+        assert!(found("sqfp").is_empty());
+        assert!(found("  ").is_empty());
+    }
+
+    #[test]
+    fn service_matches_outrank_notes_matches() {
+        let mut noted = entry("mail", "bob");
+        noted.notes = "github recovery codes".to_string();
+        let entries = [noted, entry("github", "octocat")];
+        let refs: Vec<&PasswordEntry> = entries.iter().collect();
+        assert_eq!(services(&search(&refs, "github")), ["github", "mail"]);
+    }
+}
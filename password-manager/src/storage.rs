@@ -0,0 +1,130 @@
+// Crash-safe writes of the vault file and its rotating backups.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+// Writes to a sibling temp file, flushes it to disk and renames it over
+// `path`, so readers only ever see the old or the new contents.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp_path, path)?;
+
+    let parent = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(parent) {
+        dir.sync_all().ok();
+    }
+    Ok(())
+}
+
+pub(crate) fn backup_dir(vault: &Path) -> PathBuf {
+    let mut name = vault.as_os_str().to_owned();
+    name.push(".backups");
+    PathBuf::from(name)
+}
+
+// Backups are named after the time they were taken, so sorting by name
+// sorts them by age. Newest first.
+pub(crate) fn list_backups(vault: &Path) -> Result<Vec<PathBuf>> {
+    let mut backups = Vec::new();
+    match fs::read_dir(backup_dir(vault)) {
+        Ok(dir) => {
+            for item in dir {
+                let path = item?.path();
+                if path.extension().is_some_and(|ext| ext == "bak") {
+                    backups.push(path);
+                }
+            }
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+// Copies the vault as it is on disk into the backup directory and drops the
+// oldest backups beyond `keep`.
+pub(crate) fn rotate_backups(vault: &Path, keep: usize) -> Result<()> {
+    if keep == 0 || !vault.exists() {
+        return Ok(());
+    }
+
+    let dir = backup_dir(vault);
+    fs::create_dir_all(&dir)?;
+    let stamp = chrono::Utc::now().format(BACKUP_TIMESTAMP_FORMAT);
+    fs::copy(vault, dir.join(format!("{}.bak", stamp)))?;
+
+    for old in list_backups(vault)?.iter().skip(keep) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    fn temp_vault(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("storage-test-{}-{}", name, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("passwords.dat")
+    }
+
+    #[test]
+    fn writes_replace_the_file_and_leave_no_temp_file() {
+        let vault = temp_vault("write");
+        write_atomically(&vault, b"first").unwrap();
+        write_atomically(&vault, b"second").unwrap();
+        assert_eq!(fs::read(&vault).unwrap(), b"second");
+        let names: Vec<_> = fs::read_dir(vault.parent().unwrap()).unwrap().map(|item| item.unwrap().file_name()).collect();
+        assert_eq!(names, ["passwords.dat"]);
+    }
+
+    #[test]
+    fn rotation_keeps_the_newest_backups() {
+        let vault = temp_vault("rotate");
+        rotate_backups(&vault, 3).unwrap();
+        assert!(!backup_dir(&vault).exists());
+
+        for generation in 0..5 {
+            write_atomically(&vault, format!("generation {}", generation).as_bytes()).unwrap();
+            rotate_backups(&vault, 3).unwrap();
+            // Backups are told apart by the millisecond they were taken.
+            sleep(Duration::from_millis(5));
+        }
+        let backups = list_backups(&vault).unwrap();
+        let contents: Vec<String> = backups.iter().map(|backup| fs::read_to_string(backup).unwrap()).collect();
+        assert_eq!(contents, ["generation 4", "generation 3", "generation 2"]);
+
+        fs::write(backup_dir(&vault).join("notes.txt"), "not a backup").unwrap();
+        assert_eq!(list_backups(&vault).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn zero_backups_disables_rotation() {
+        let vault = temp_vault("disabled");
+        write_atomically(&vault, b"contents").unwrap();
+        rotate_backups(&vault, 0).unwrap();
+        assert!(!backup_dir(&vault).exists());
+        assert!(list_backups(&vault).unwrap().is_empty());
+    }
+}
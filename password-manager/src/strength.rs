@@ -0,0 +1,758 @@
+//! Password strength estimation in the style of zxcvbn.
+//!
+//! A password is split into the cheapest sequence of guessable pieces:
+//! dictionary words (also reversed, capitalized or in l33t speak), keyboard
+//! walks, repeats, sequences, dates and plain brute force. The estimated
+//! number of guesses for the whole password is turned into a 0-4 score and
+//! the weakest parts into human-readable feedback.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use chrono::{Datelike, Utc};
+
+const COMMON_PASSWORDS: &str = include_str!("../wordlists/common-passwords.txt");
+const ENGLISH_WORDS: &str = include_str!("../wordlists/default.txt");
+
+// Longer passwords are only analysed up to here; the rest counts as brute
+// force, which only makes the estimate more conservative for the prefix.
+const MAX_ANALYSED: usize = 128;
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_GUESSES_MULTI_CHAR: f64 = 50.0;
+// Penalty for every additional piece, so one long match beats many small
+// ones of the same total.
+const MIN_GUESSES_PER_EXTRA_MATCH: f64 = 10_000.0;
+const MIN_YEAR_SPACE: f64 = 20.0;
+
+const KEYBOARD_ROWS: [&str; 4] = ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./"];
+const SHIFTED_ROWS: [&str; 4] = ["~!@#$%^&*()_+", "QWERTYUIOP{}|", "ASDFGHJKL:\"", "ZXCVBNM<>?"];
+const KEYBOARD_STARTS: f64 = 94.0;
+const KEYBOARD_AVERAGE_DEGREE: f64 = 4.6;
+
+const L33T: [(char, &str); 12] = [
+    ('4', "a"),
+    ('@', "a"),
+    ('8', "b"),
+    ('3', "e"),
+    ('9', "g"),
+    ('1', "il"),
+    ('!', "i"),
+    ('0', "o"),
+    ('5', "s"),
+    ('$', "s"),
+    ('7', "t"),
+    ('+', "t"),
+];
+
+/// The result of [`estimate`].
+#[derive(Clone, Debug)]
+pub struct Strength {
+    /// 0 (too guessable) to 4 (very unguessable).
+    pub score: u8,
+    /// Estimated guesses an attacker needs.
+    pub guesses: f64,
+    /// `log2(guesses)`, the effective entropy.
+    pub entropy_bits: f64,
+    /// What makes the password weak, if anything.
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+impl Strength {
+    /// "very weak" to "very strong".
+    pub fn label(&self) -> &'static str {
+        match self.score {
+            0 => "very weak",
+            1 => "weak",
+            2 => "fair",
+            3 => "strong",
+            _ => "very strong",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    Dictionary {
+        rank: usize,
+        source: Source,
+        reversed: bool,
+        l33t: bool,
+        token: String,
+    },
+    Spatial {
+        turns: usize,
+    },
+    Repeat {
+        unit_len: usize,
+    },
+    Sequence,
+    Date,
+    Bruteforce,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    CommonPasswords,
+    English,
+    UserInputs,
+}
+
+#[derive(Clone, Debug)]
+struct Match {
+    start: usize,
+    end: usize,
+    guesses: f64,
+    pattern: Pattern,
+}
+
+/// Estimates how hard `password` is to guess. `user_inputs` are strings
+/// an attacker would try first, such as the service and username.
+pub fn estimate(password: &str, user_inputs: &[&str]) -> Strength {
+    let chars: Vec<char> = password.chars().take(MAX_ANALYSED).collect();
+    let extra = password.chars().count() - chars.len();
+    if chars.is_empty() {
+        return Strength {
+            score: 0,
+            guesses: 1.0,
+            entropy_bits: 0.0,
+            warning: Some("The password is empty".to_string()),
+            suggestions: vec!["Use a few words, avoid common phrases".to_string()],
+        };
+    }
+
+    let matches = find_matches(&chars, user_inputs);
+    let (log_guesses, sequence) = cheapest_sequence(&chars, &matches);
+    let log_guesses = log_guesses + extra as f64 * BRUTEFORCE_CARDINALITY.log10();
+    let guesses = 10f64.powf(log_guesses);
+    let score = score(log_guesses);
+    let (warning, suggestions) = feedback(score, &sequence, &chars);
+    Strength {
+        score,
+        guesses,
+        entropy_bits: log_guesses / 2f64.log10(),
+        warning,
+        suggestions,
+    }
+}
+
+fn score(log_guesses: f64) -> u8 {
+    // zxcvbn's thresholds: 10^3, 10^6, 10^8 and 10^10 guesses.
+    match log_guesses {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+fn dictionaries() -> &'static [(Source, HashMap<&'static str, usize>); 2] {
+    static DICTIONARIES: OnceLock<[(Source, HashMap<&'static str, usize>); 2]> = OnceLock::new();
+    DICTIONARIES.get_or_init(|| {
+        let ranked = |list: &'static str| list.lines().enumerate().map(|(i, word)| (word, i + 1)).collect();
+        // The English list is alphabetical, not by frequency, so every word
+        // counts as equally likely.
+        let size = ENGLISH_WORDS.lines().count();
+        let english = ENGLISH_WORDS.lines().map(|word| (word, size)).collect();
+        [(Source::CommonPasswords, ranked(COMMON_PASSWORDS)), (Source::English, english)]
+    })
+}
+
+fn find_matches(chars: &[char], user_inputs: &[&str]) -> Vec<Match> {
+    let lower: Vec<char> = chars.iter().flat_map(|c| c.to_lowercase()).collect();
+    // Lowercasing can change the length for a few non-ASCII letters; skip
+    // the matchers that need positions to line up in that case.
+    let lower = if lower.len() == chars.len() { lower } else { chars.to_vec() };
+    let user_inputs: HashMap<String, usize> = user_inputs
+        .iter()
+        .filter(|input| !input.is_empty())
+        .enumerate()
+        .map(|(i, input)| (input.to_lowercase(), i + 1))
+        .collect();
+
+    let mut matches = Vec::new();
+    dictionary_matches(chars, &lower, &user_inputs, &mut matches);
+    spatial_matches(chars, &mut matches);
+    repeat_matches(&lower, &mut matches);
+    sequence_matches(chars, &mut matches);
+    date_matches(chars, &mut matches);
+    matches
+}
+
+fn dictionary_matches(chars: &[char], lower: &[char], user_inputs: &HashMap<String, usize>, matches: &mut Vec<Match>) {
+    let variants = l33t_variants(lower);
+    let n = chars.len();
+    for start in 0..n {
+        for end in start + 3..=n {
+            let original: String = lower[start..end].iter().collect();
+            for (variant, l33t) in &variants {
+                let token: String = variant[start..end].iter().collect();
+                if *l33t && token == original {
+                    continue;
+                }
+                let reversed_token: String = token.chars().rev().collect();
+                for (reversed, candidate) in [(false, &token), (true, &reversed_token)] {
+                    if reversed && candidate == &token {
+                        continue;
+                    }
+                    if let Some((source, rank)) = lookup(candidate, user_inputs) {
+                        let mut guesses = rank as f64 * uppercase_variations(&chars[start..end]);
+                        if reversed {
+                            guesses *= 2.0;
+                        }
+                        if *l33t {
+                            guesses *= l33t_variations(&lower[start..end], &variant[start..end]);
+                        }
+                        matches.push(Match {
+                            start,
+                            end,
+                            guesses,
+                            pattern: Pattern::Dictionary {
+                                rank,
+                                source,
+                                reversed,
+                                l33t: *l33t,
+                                token: candidate.clone(),
+                            },
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn lookup(word: &str, user_inputs: &HashMap<String, usize>) -> Option<(Source, usize)> {
+    if let Some(&rank) = user_inputs.get(word) {
+        return Some((Source::UserInputs, rank));
+    }
+    dictionaries()
+        .iter()
+        .filter_map(|(source, words)| words.get(word).map(|&rank| (*source, rank)))
+        .min_by_key(|&(_, rank)| rank)
+}
+
+// The lowercase password itself plus up to two de-l33ted readings ('1' is
+// tried both as 'i' and as 'l').
+fn l33t_variants(lower: &[char]) -> Vec<(Vec<char>, bool)> {
+    let mut variants = vec![(lower.to_vec(), false)];
+    if !lower.iter().any(|c| L33T.iter().any(|(l33t, _)| l33t == c)) {
+        return variants;
+    }
+    for choice in 0..2 {
+        let variant = lower
+            .iter()
+            .map(|&c| match L33T.iter().find(|(l33t, _)| *l33t == c) {
+                Some((_, letters)) => letters.chars().nth(choice).or_else(|| letters.chars().next()).unwrap_or(c),
+                None => c,
+            })
+            .collect();
+        variants.push((variant, true));
+    }
+    variants
+}
+
+fn uppercase_variations(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_only = token[0].is_uppercase() && upper == 1;
+    let last_only = token[token.len() - 1].is_uppercase() && upper == 1;
+    if first_only || last_only || lower == 0 {
+        return 2.0;
+    }
+    (1..=upper.min(lower)).map(|i| binomial(upper + lower, i)).sum()
+}
+
+fn l33t_variations(original: &[char], unl33ted: &[char]) -> f64 {
+    let substituted = original.iter().zip(unl33ted).filter(|(a, b)| a != b).count();
+    let unchanged = original.len() - substituted;
+    let variations: f64 = (1..=substituted.min(unchanged.max(1)))
+        .map(|i| binomial(substituted + unchanged, i))
+        .sum();
+    variations.max(2.0)
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+fn key_position(c: char) -> Option<(i32, i32, bool)> {
+    for (row, (plain, shifted)) in KEYBOARD_ROWS.iter().zip(SHIFTED_ROWS).enumerate() {
+        if let Some(col) = plain.chars().position(|k| k == c) {
+            return Some((row as i32, col as i32, false));
+        }
+        if let Some(col) = shifted.chars().position(|k| k == c) {
+            return Some((row as i32, col as i32, true));
+        }
+    }
+    None
+}
+
+// Runs of three or more keys that are next to each other on a QWERTY
+// keyboard, including the rows above and below.
+fn spatial_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let positions: Vec<_> = chars.iter().map(|&c| key_position(c)).collect();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = start + 1;
+        let mut turns = 0;
+        let mut shifted = 0;
+        let mut direction = None;
+        while end < chars.len() {
+            let (Some((r1, c1, _)), Some((r2, c2, s2))) = (positions[end - 1], positions[end]) else {
+                break;
+            };
+            // Rows are staggered, so a key touches the two above and below.
+            let step = (r2 - r1, c2 - c1);
+            let adjacent = match step.0 {
+                0 => step.1.abs() == 1,
+                -1 => step.1 == 0 || step.1 == 1,
+                1 => step.1 == 0 || step.1 == -1,
+                _ => false,
+            };
+            if !adjacent {
+                break;
+            }
+            if direction != Some(step) {
+                turns += 1;
+                direction = Some(step);
+            }
+            if s2 {
+                shifted += 1;
+            }
+            end += 1;
+        }
+        let len = end - start;
+        if len >= 3 {
+            let mut guesses = 0.0;
+            for i in 2..=len {
+                for j in 1..=turns.min(i - 1) {
+                    guesses += binomial(i - 1, j - 1) * KEYBOARD_STARTS * KEYBOARD_AVERAGE_DEGREE.powi(j as i32);
+                }
+            }
+            if shifted > 0 {
+                let unshifted = len - shifted;
+                guesses *= if unshifted == 0 {
+                    2.0
+                } else {
+                    (1..=shifted.min(unshifted)).map(|i| binomial(len, i)).sum()
+                };
+            }
+            matches.push(Match {
+                start,
+                end,
+                guesses,
+                pattern: Pattern::Spatial { turns },
+            });
+        }
+        start = end.max(start + 1);
+    }
+}
+
+// "aaaa" or "abcabc": a unit repeated back to back.
+fn repeat_matches(lower: &[char], matches: &mut Vec<Match>) {
+    let n = lower.len();
+    for start in 0..n {
+        for unit_len in 1..=(n - start) / 2 {
+            let mut end = start + unit_len;
+            while end < n && lower[end] == lower[end - unit_len] {
+                end += 1;
+            }
+            let repeats = (end - start) / unit_len;
+            if repeats < 2 || end - start < 3 {
+                continue;
+            }
+            let end = start + repeats * unit_len;
+            let unit: String = lower[start..start + unit_len].iter().collect();
+            let unit_guesses = match lookup(&unit, &HashMap::new()) {
+                Some((_, rank)) if unit_len >= 3 => rank as f64,
+                _ => BRUTEFORCE_CARDINALITY.powi(unit_len as i32),
+            };
+            matches.push(Match {
+                start,
+                end,
+                guesses: unit_guesses * repeats as f64,
+                pattern: Pattern::Repeat { unit_len },
+            });
+        }
+    }
+}
+
+// "abcd", "9876", "acegi": same-class characters with a constant small step.
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let class = |c: char| {
+        if c.is_ascii_lowercase() {
+            1
+        } else if c.is_ascii_uppercase() {
+            2
+        } else if c.is_ascii_digit() {
+            3
+        } else {
+            0
+        }
+    };
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let delta = chars[start + 1] as i32 - chars[start] as i32;
+        let same_class = class(chars[start]) != 0 && class(chars[start]) == class(chars[start + 1]);
+        if !same_class || delta == 0 || delta.abs() > 2 {
+            start += 1;
+            continue;
+        }
+        let mut end = start + 2;
+        while end < chars.len() && class(chars[end]) == class(chars[start]) && chars[end] as i32 - chars[end - 1] as i32 == delta {
+            end += 1;
+        }
+        if end - start >= 3 {
+            let first = chars[start];
+            let base = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let direction = if delta < 0 { 2.0 } else { 1.0 };
+            matches.push(Match {
+                start,
+                end,
+                guesses: base * direction * (end - start) as f64,
+                pattern: Pattern::Sequence,
+            });
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+// Years ("1987") and day-month-year dates in any order, with or without
+// separators ("12.04.1987", "870412").
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let n = chars.len();
+    for start in 0..n {
+        for end in start + 4..=(start + 10).min(n) {
+            let token: String = chars[start..end].iter().collect();
+            if let Some(guesses) = date_guesses(&token) {
+                matches.push(Match {
+                    start,
+                    end,
+                    guesses,
+                    pattern: Pattern::Date,
+                });
+            }
+        }
+    }
+}
+
+fn date_guesses(token: &str) -> Option<f64> {
+    // Years near the present are the likeliest.
+    let this_year = Utc::now().year();
+    let year_space = |year: i32| ((year - this_year).abs() as f64).max(MIN_YEAR_SPACE);
+
+    if token.len() == 4 && token.chars().all(|c| c.is_ascii_digit()) {
+        let year: i32 = token.parse().ok()?;
+        if (1900..=2099).contains(&year) {
+            return Some(year_space(year));
+        }
+    }
+
+    let separators: Vec<char> = token.chars().filter(|c| !c.is_ascii_digit()).collect();
+    let (parts, separated): (Vec<&str>, bool) = match separators.as_slice() {
+        [] => (Vec::new(), false),
+        [a, b] if a == b && "/-._ ".contains(*a) => (token.split(*a).collect(), true),
+        _ => return None,
+    };
+
+    let candidates: Vec<(u32, u32, u32, usize)> = if separated {
+        if parts.len() != 3 || parts.iter().any(|p| p.is_empty()) {
+            return None;
+        }
+        let numbers: Vec<u32> = parts.iter().map(|p| p.parse().ok()).collect::<Option<_>>()?;
+        let lens: Vec<usize> = parts.iter().map(|p| p.len()).collect();
+        vec![
+            (numbers[0], numbers[1], numbers[2], lens[2]),
+            (numbers[2], numbers[1], numbers[0], lens[0]),
+            (numbers[1], numbers[0], numbers[2], lens[2]),
+        ]
+    } else {
+        let digits = token;
+        let mut candidates = Vec::new();
+        for year_len in [2, 4] {
+            if digits.len() <= year_len || digits.len() - year_len > 4 {
+                continue;
+            }
+            let rest_len = digits.len() - year_len;
+            for split in 1..rest_len {
+                if split > 2 || rest_len - split > 2 {
+                    continue;
+                }
+                // Year last: d m y / m d y.
+                let (rest, year) = digits.split_at(rest_len);
+                let (a, b) = rest.split_at(split);
+                let (a, b, year) = (a.parse().ok()?, b.parse().ok()?, year.parse().ok()?);
+                candidates.push((a, b, year, year_len));
+                candidates.push((b, a, year, year_len));
+                // Year first: y m d.
+                let (year, rest) = digits.split_at(year_len);
+                let (a, b) = rest.split_at(split);
+                let (a, b, year) = (a.parse().ok()?, b.parse().ok()?, year.parse().ok()?);
+                candidates.push((b, a, year, year_len));
+            }
+        }
+        candidates
+    };
+
+    candidates
+        .into_iter()
+        .filter_map(|(day, month, year, year_len)| {
+            let year = match year_len {
+                2 if year > 50 => 1900 + year as i32,
+                2 => 2000 + year as i32,
+                4 if (1900..=2099).contains(&year) => year as i32,
+                _ => return None,
+            };
+            ((1..=31).contains(&day) && (1..=12).contains(&month)).then_some(year)
+        })
+        .map(|year| 365.0 * year_space(year) * if separated { 4.0 } else { 1.0 })
+        .reduce(f64::min)
+}
+
+// Dynamic programming over prefixes, as in zxcvbn: for every prefix and
+// number of pieces keep the cheapest cover, then charge the number of ways
+// to order the pieces.
+fn cheapest_sequence(chars: &[char], matches: &[Match]) -> (f64, Vec<Match>) {
+    let n = chars.len();
+    let mut by_end: Vec<Vec<&Match>> = vec![Vec::new(); n + 1];
+    for m in matches {
+        by_end[m.end].push(m);
+    }
+
+    // best[k][j]: lowest sum of log10(guesses) covering chars[..j] with k
+    // pieces, and the piece that ends there.
+    let mut best: Vec<Vec<Option<(f64, Match)>>> = vec![vec![None; n + 1]; n + 1];
+    let bruteforce = |start: usize, end: usize| Match {
+        start,
+        end,
+        guesses: BRUTEFORCE_CARDINALITY.powi((end - start) as i32).max(MIN_GUESSES_SINGLE_CHAR + 1.0),
+        pattern: Pattern::Bruteforce,
+    };
+    for end in 1..=n {
+        let mut candidates: Vec<Match> = by_end[end].iter().map(|m| (*m).clone()).collect();
+        candidates.extend((0..end).map(|start| bruteforce(start, end)));
+        for candidate in candidates {
+            let min = if candidate.end - candidate.start == 1 {
+                MIN_GUESSES_SINGLE_CHAR
+            } else {
+                MIN_GUESSES_MULTI_CHAR
+            };
+            let log = if candidate.start == 0 && candidate.end == n {
+                candidate.guesses.log10()
+            } else {
+                candidate.guesses.max(min).log10()
+            };
+            for k in 1..=end {
+                let previous = if candidate.start == 0 {
+                    if k == 1 {
+                        Some(0.0)
+                    } else {
+                        None
+                    }
+                } else {
+                    best[k - 1][candidate.start].as_ref().map(|(sum, _)| *sum)
+                };
+                let Some(previous) = previous else { continue };
+                // Back-to-back brute force pieces are never cheaper than one.
+                if matches!(candidate.pattern, Pattern::Bruteforce) {
+                    if let Some((_, prev)) = &best[k - 1][candidate.start] {
+                        if matches!(prev.pattern, Pattern::Bruteforce) {
+                            continue;
+                        }
+                    }
+                }
+                let total = previous + log;
+                if best[k][end].as_ref().is_none_or(|(sum, _)| total < *sum) {
+                    best[k][end] = Some((total, candidate.clone()));
+                }
+            }
+        }
+    }
+
+    let (k, log_guesses) = (1..=n)
+        .filter_map(|k| best[k][n].as_ref().map(|(sum, _)| (k, sequence_log_guesses(k, *sum))))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("brute force always covers the password");
+
+    let mut sequence = Vec::with_capacity(k);
+    let (mut k, mut end) = (k, n);
+    while k > 0 {
+        let (_, m) = best[k][end].clone().expect("back-pointer exists");
+        end = m.start;
+        k -= 1;
+        sequence.push(m);
+    }
+    sequence.reverse();
+    (log_guesses, sequence)
+}
+
+// log10(k! * product + MIN_GUESSES_PER_EXTRA_MATCH^(k-1)).
+fn sequence_log_guesses(k: usize, log_product: f64) -> f64 {
+    let log_factorial: f64 = (2..=k).map(|i| (i as f64).log10()).sum();
+    let a = log_factorial + log_product;
+    let b = (k as f64 - 1.0) * MIN_GUESSES_PER_EXTRA_MATCH.log10();
+    let (high, low) = if a > b { (a, b) } else { (b, a) };
+    high + (1.0 + 10f64.powf(low - high)).log10()
+}
+
+fn feedback(score: u8, sequence: &[Match], chars: &[char]) -> (Option<String>, Vec<String>) {
+    if score > 2 {
+        return (None, Vec::new());
+    }
+    let longest = sequence
+        .iter()
+        .max_by_key(|m| m.end - m.start)
+        .expect("sequence is never empty");
+    let mut suggestions = vec!["Add another word or two; uncommon words are better".to_string()];
+    let whole = sequence.len() == 1;
+
+    let warning = match &longest.pattern {
+        Pattern::Dictionary {
+            rank,
+            source,
+            reversed,
+            l33t,
+            token,
+        } => {
+            let token_chars = &chars[longest.start..longest.end];
+            if token_chars.iter().all(|c| c.is_uppercase()) && token_chars.iter().any(|c| c.is_alphabetic()) {
+                suggestions.push("All-uppercase is almost as easy to guess as all-lowercase".to_string());
+            } else if token_chars[0].is_uppercase() {
+                suggestions.push("Capitalization doesn't help very much".to_string());
+            }
+            if *reversed && token.len() >= 4 {
+                suggestions.push("Reversed words aren't much harder to guess".to_string());
+            }
+            if *l33t {
+                suggestions.push("Predictable substitutions like '@' instead of 'a' don't help very much".to_string());
+            }
+            Some(match source {
+                Source::CommonPasswords if whole && *rank <= 10 => "This is a top-10 common password".to_string(),
+                Source::CommonPasswords if whole && *rank <= 100 => "This is a top-100 common password".to_string(),
+                Source::CommonPasswords => "This is similar to a commonly used password".to_string(),
+                Source::English if whole => "A word by itself is easy to guess".to_string(),
+                Source::English => "Common words are easy to guess".to_string(),
+                Source::UserInputs => "The service or account name is easy to guess".to_string(),
+            })
+        }
+        Pattern::Spatial { turns } => {
+            suggestions.push("Use a longer keyboard pattern with more turns".to_string());
+            Some(if *turns == 1 {
+                "Straight rows of keys are easy to guess".to_string()
+            } else {
+                "Short keyboard patterns are easy to guess".to_string()
+            })
+        }
+        Pattern::Repeat { unit_len } => {
+            suggestions.push("Avoid repeated words and characters".to_string());
+            Some(if *unit_len == 1 {
+                "Repeats like \"aaa\" are easy to guess".to_string()
+            } else {
+                "Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"".to_string()
+            })
+        }
+        Pattern::Sequence => {
+            suggestions.push("Avoid sequences".to_string());
+            Some("Sequences like abc or 6543 are easy to guess".to_string())
+        }
+        Pattern::Date => {
+            suggestions.push("Avoid dates and years that are associated with you".to_string());
+            Some("Dates are often easy to guess".to_string())
+        }
+        Pattern::Bruteforce => None,
+    };
+
+    if warning.is_none() && chars.len() < 12 {
+        suggestions.push("Use a longer password".to_string());
+    }
+    (warning, suggestions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The pieces of the cheapest guess for `password`.
+    fn pieces(password: &str, user_inputs: &[&str]) -> Vec<(String, Pattern)> {
+        let chars: Vec<char> = password.chars().collect();
+        let matches = find_matches(&chars, user_inputs);
+        let (_, sequence) = cheapest_sequence(&chars, &matches);
+        sequence.into_iter().map(|m| (chars[m.start..m.end].iter().collect(), m.pattern)).collect()
+    }
+
+    #[test]
+    fn finds_dictionary_words() {
+        let strength = estimate("password", &[]);
+        assert_eq!(strength.score, 0);
+        assert_eq!(strength.warning.as_deref(), Some("This is a top-10 common password"));
+
+        let found = pieces("drowssap", &[]);
+        assert!(matches!(found.as_slice(), [(_, Pattern::Dictionary { reversed: true, .. })]), "{:?}", found);
+        let found = pieces("octocat", &["github", "octocat"]);
+        assert!(matches!(found.as_slice(), [(_, Pattern::Dictionary { source: Source::UserInputs, .. })]), "{:?}", found);
+    }
+
+    #[test]
+    fn finds_l33t_spellings() {
+        let found = pieces("p@ssw0rd", &[]);
+        match found.as_slice() {
+            [(_, Pattern::Dictionary { l33t: true, token, .. })] => assert_eq!(token, "password"),
+            other => panic!("{:?}", other),
+        }
+        assert!(estimate("p@ssw0rd", &[]).suggestions.iter().any(|s| s.contains("substitutions")));
+    }
+
+    #[test]
+    fn finds_keyboard_walks() {
+        let found = pieces("kjhgfd", &[]);
+        assert!(matches!(found.as_slice(), [(_, Pattern::Spatial { turns: 1 })]), "{:?}", found);
+        let found = pieces("qwedcxz", &[]);
+        assert!(matches!(found.as_slice(), [(_, Pattern::Spatial { turns: 3 })]), "{:?}", found);
+    }
+
+    #[test]
+    fn finds_repeats() {
+        let found = pieces("zzzzzzzz", &[]);
+        assert!(matches!(found.as_slice(), [(_, Pattern::Repeat { unit_len: 1 })]), "{:?}", found);
+        let found = pieces("xkcdxkcdxkcd", &[]);
+        assert!(matches!(found.as_slice(), [(_, Pattern::Repeat { unit_len: 4 })]), "{:?}", found);
+    }
+
+    #[test]
+    fn finds_dates() {
+        for date in ["12.04.1987", "1987-04-12", "120487"] {
+            let found = pieces(date, &[]);
+            assert!(matches!(found.as_slice(), [(_, Pattern::Date)]), "{}: {:?}", date, found);
+        }
+        assert_eq!(date_guesses("13.13.1987"), None);
+
+        // Years are weighed by their distance from the current one.
+        let this_year = Utc::now().year();
+        assert_eq!(date_guesses(&this_year.to_string()), Some(MIN_YEAR_SPACE));
+        assert_eq!(date_guesses(&(this_year - 50).to_string()), Some(50.0));
+    }
+
+    #[test]
+    fn random_passwords_score_high() {
+        assert_eq!(estimate("", &[]).score, 0);
+        let strength = estimate("correct horse battery staple", &[]);
+        assert!(strength.score >= 3, "{:?}", strength);
+        let strength = estimate("Vq7#mZ2!pR9x@Lw4", &[]);
+        assert_eq!(strength.score, 4);
+        assert_eq!(strength.warning, None);
+    }
+}
@@ -0,0 +1,361 @@
+#!/usr/bin/env python3
+"""Writes the KDBX 4 fixtures used by the tests in src/kdbx.rs.
+
+The files are built here from the KeePass format description rather than
+with our own writer, so the tests read something we did not produce. Needs
+the `cryptography` package. Run from this directory:
+
+    python3 make_kdbx.py
+
+Both databases use the password "fixture-password" and hold the same
+entries; they differ in key derivation, cipher and inner stream:
+
+    argon2id-chacha20.kdbx  Argon2id, ChaCha20, ChaCha20 inner stream
+    aeskdf-aes256.kdbx      AES-KDF, AES-256-CBC, Salsa20 inner stream
+"""
+
+import base64
+import gzip
+import hashlib
+import hmac
+import os
+import re
+import struct
+import uuid
+from datetime import datetime, timezone
+
+from cryptography.hazmat.primitives import padding
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+from cryptography.hazmat.primitives.kdf.argon2 import Argon2id
+
+PASSWORD = b"fixture-password"
+SIGNATURE = bytes.fromhex("03d9a29a67fb4bb5")
+CIPHER_AES256 = uuid.UUID("31c1f2e6-bf71-4350-be58-05216afc5aff").bytes
+CIPHER_CHACHA20 = uuid.UUID("d6038a2b-8b6f-4cb5-a524-339a31dbb59a").bytes
+KDF_AES = uuid.UUID("c9d9f39a-628a-4460-bf74-0d08c18a4fea").bytes
+KDF_ARGON2ID = uuid.UUID("9e298b19-56db-4773-b23d-fc3ec6f0a1e6").bytes
+SALSA20_NONCE = bytes.fromhex("e830094b97205d2a")
+
+# Fixed so that regenerating gives the same files.
+SEED = hashlib.sha256(b"kdbx fixtures").digest()
+
+
+def fixed_bytes(label, length):
+    return hashlib.sha512(SEED + label.encode()).digest()[:length]
+
+
+def kdbx_time(year, month, day):
+    epoch = datetime(1, 1, 1, tzinfo=timezone.utc)
+    seconds = int((datetime(year, month, day, 12, tzinfo=timezone.utc) - epoch).total_seconds())
+    return base64.b64encode(struct.pack("<q", seconds)).decode()
+
+
+def b64uuid(text):
+    return base64.b64encode(uuid.UUID(text).bytes).decode()
+
+
+# ---- Salsa20 (not in `cryptography`) ----
+
+def _rotl(value, shift):
+    return ((value << shift) | (value >> (32 - shift))) & 0xFFFFFFFF
+
+
+def _salsa20_block(key, nonce, counter):
+    constants = struct.unpack("<4I", b"expand 32-byte k")
+    k = struct.unpack("<8I", key)
+    n = struct.unpack("<2I", nonce)
+    state = [
+        constants[0], k[0], k[1], k[2],
+        k[3], constants[1], n[0], n[1],
+        counter & 0xFFFFFFFF, counter >> 32, constants[2], k[4],
+        k[5], k[6], k[7], constants[3],
+    ]
+    x = list(state)
+
+    def quarter(a, b, c, d):
+        x[b] ^= _rotl((x[a] + x[d]) & 0xFFFFFFFF, 7)
+        x[c] ^= _rotl((x[b] + x[a]) & 0xFFFFFFFF, 9)
+        x[d] ^= _rotl((x[c] + x[b]) & 0xFFFFFFFF, 13)
+        x[a] ^= _rotl((x[d] + x[c]) & 0xFFFFFFFF, 18)
+
+    for _ in range(10):
+        quarter(0, 4, 8, 12)
+        quarter(5, 9, 13, 1)
+        quarter(10, 14, 2, 6)
+        quarter(15, 3, 7, 11)
+        quarter(0, 1, 2, 3)
+        quarter(5, 6, 7, 4)
+        quarter(10, 11, 8, 9)
+        quarter(15, 12, 13, 14)
+    return struct.pack("<16I", *[(a + b) & 0xFFFFFFFF for a, b in zip(x, state)])
+
+
+class Salsa20:
+    def __init__(self, key, nonce):
+        self.key, self.nonce, self.counter, self.buffer = key, nonce, 0, b""
+
+    def update(self, data):
+        while len(self.buffer) < len(data):
+            self.buffer += _salsa20_block(self.key, self.nonce, self.counter)
+            self.counter += 1
+        stream, self.buffer = self.buffer[: len(data)], self.buffer[len(data):]
+        return bytes(a ^ b for a, b in zip(data, stream))
+
+
+def chacha20(key, nonce12):
+    # `cryptography` takes a 4-byte little-endian counter before the nonce.
+    return Cipher(algorithms.ChaCha20(key, b"\0\0\0\0" + nonce12), mode=None).encryptor()
+
+
+# ---- Database content ----
+
+# Protected values are written as {{P:...}} and encrypted in document order.
+XML = """<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<KeePassFile>
+\t<Meta>
+\t\t<Generator>KeePassXC</Generator>
+\t\t<DatabaseName>Fixture</DatabaseName>
+\t\t<RecycleBinEnabled>True</RecycleBinEnabled>
+\t\t<RecycleBinUUID>{bin}</RecycleBinUUID>
+\t</Meta>
+\t<Root>
+\t\t<Group>
+\t\t\t<UUID>{root}</UUID>
+\t\t\t<Name>Root</Name>
+\t\t\t<Entry>
+\t\t\t\t<UUID>{github}</UUID>
+\t\t\t\t<Tags>work;dev</Tags>
+\t\t\t\t<Times>
+\t\t\t\t\t<CreationTime>{t2020}</CreationTime>
+\t\t\t\t\t<LastModificationTime>{t2023}</LastModificationTime>
+\t\t\t\t\t<LastAccessTime>{t2024}</LastAccessTime>
+\t\t\t\t</Times>
+\t\t\t\t<String><Key>Title</Key><Value>GitHub</Value></String>
+\t\t\t\t<String><Key>UserName</Key><Value>octocat</Value></String>
+\t\t\t\t<String><Key>Password</Key><Value Protected="True">{{P:current &lt;Pa55&gt;}}</Value></String>
+\t\t\t\t<String><Key>URL</Key><Value>https://github.com/login</Value></String>
+\t\t\t\t<String><Key>Notes</Key><Value>Main account
+with 2FA</Value></String>
+\t\t\t\t<String><Key>otp</Key><Value Protected="True">{{P:otpauth://totp/GitHub:octocat?secret=JBSWY3DPEHPK3PXP&amp;issuer=GitHub&amp;digits=8&amp;period=30}}</Value></String>
+\t\t\t\t<String><Key>Recovery codes</Key><Value Protected="True">{{P:1111 2222 3333}}</Value></String>
+\t\t\t\t<String><Key>Account ID</Key><Value>42</Value></String>
+\t\t\t\t<String><Key>Birthday</Key><Value>2000-01-01</Value></String>
+\t\t\t\t<CustomData>
+\t\t\t\t\t<Item><Key>PasswordManager.FieldKind.Birthday</Key><Value>date</Value></Item>
+\t\t\t\t</CustomData>
+\t\t\t\t<History>
+\t\t\t\t\t<Entry>
+\t\t\t\t\t\t<UUID>{github}</UUID>
+\t\t\t\t\t\t<Times><LastModificationTime>{t2020}</LastModificationTime></Times>
+\t\t\t\t\t\t<String><Key>Title</Key><Value>GitHub</Value></String>
+\t\t\t\t\t\t<String><Key>Password</Key><Value Protected="True">{{P:oldest-pw}}</Value></String>
+\t\t\t\t\t</Entry>
+\t\t\t\t\t<Entry>
+\t\t\t\t\t\t<UUID>{github}</UUID>
+\t\t\t\t\t\t<Times><LastModificationTime>{t2021}</LastModificationTime></Times>
+\t\t\t\t\t\t<String><Key>Title</Key><Value>GitHub</Value></String>
+\t\t\t\t\t\t<String><Key>Password</Key><Value Protected="True">{{P:older-pw}}</Value></String>
+\t\t\t\t\t</Entry>
+\t\t\t\t\t<Entry>
+\t\t\t\t\t\t<UUID>{github}</UUID>
+\t\t\t\t\t\t<Times><LastModificationTime>{t2022}</LastModificationTime></Times>
+\t\t\t\t\t\t<String><Key>Title</Key><Value>GitHub</Value></String>
+\t\t\t\t\t\t<String><Key>Password</Key><Value Protected="True">{{P:older-pw}}</Value></String>
+\t\t\t\t\t\t<String><Key>Notes</Key><Value>only the notes changed</Value></String>
+\t\t\t\t\t</Entry>
+\t\t\t\t</History>
+\t\t\t</Entry>
+\t\t\t<Entry>
+\t\t\t\t<UUID>{untitled}</UUID>
+\t\t\t\t<String><Key>Title</Key><Value></Value></String>
+\t\t\t\t<String><Key>UserName</Key><Value>anon</Value></String>
+\t\t\t\t<String><Key>Password</Key><Value Protected="True">{{P:x}}</Value></String>
+\t\t\t\t<String><Key>URL</Key><Value>https://www.example.org/signin</Value></String>
+\t\t\t</Entry>
+\t\t\t<Entry>
+\t\t\t\t<UUID>{nameless}</UUID>
+\t\t\t\t<String><Key>Title</Key><Value/></String>
+\t\t\t\t<String><Key>Password</Key><Value Protected="True">{{P:lost}}</Value></String>
+\t\t\t</Entry>
+\t\t\t<Group>
+\t\t\t\t<UUID>{work}</UUID>
+\t\t\t\t<Name>Work</Name>
+\t\t\t\t<Group>
+\t\t\t\t\t<UUID>{email}</UUID>
+\t\t\t\t\t<Name>Email</Name>
+\t\t\t\t\t<Entry>
+\t\t\t\t\t\t<UUID>{mail}</UUID>
+\t\t\t\t\t\t<String><Key>Title</Key><Value>Mail</Value></String>
+\t\t\t\t\t\t<String><Key>UserName</Key><Value>me@example.com</Value></String>
+\t\t\t\t\t\t<String><Key>Password</Key><Value Protected="True">{{P:  spaced out  }}</Value></String>
+\t\t\t\t\t\t<String><Key>URL</Key><Value>https://webmail.example.com</Value></String>
+\t\t\t\t\t\t<String><Key>KP2A_URL_1</Key><Value>https://mail.example.com</Value></String>
+\t\t\t\t\t</Entry>
+\t\t\t\t</Group>
+\t\t\t\t<Group>
+\t\t\t\t\t<UUID>{empty}</UUID>
+\t\t\t\t\t<Name>Empty</Name>
+\t\t\t\t</Group>
+\t\t\t</Group>
+\t\t\t<Group>
+\t\t\t\t<UUID>{legacy}</UUID>
+\t\t\t\t<Name>Legacy</Name>
+\t\t\t\t<Entry>
+\t\t\t\t\t<UUID>{oldtotp}</UUID>
+\t\t\t\t\t<String><Key>Title</Key><Value>Old TOTP</Value></String>
+\t\t\t\t\t<String><Key>Password</Key><Value Protected="True">{{P:pw}}</Value></String>
+\t\t\t\t\t<String><Key>TOTP Seed</Key><Value Protected="True">{{P:GEZDGNBVGY3TQOJQ}}</Value></String>
+\t\t\t\t\t<String><Key>TOTP Settings</Key><Value>60;8</Value></String>
+\t\t\t\t</Entry>
+\t\t\t</Group>
+\t\t\t<Group>
+\t\t\t\t<UUID>{bin}</UUID>
+\t\t\t\t<Name>Recycle Bin</Name>
+\t\t\t\t<Entry>
+\t\t\t\t\t<UUID>{deleted}</UUID>
+\t\t\t\t\t<String><Key>Title</Key><Value>Deleted</Value></String>
+\t\t\t\t\t<String><Key>Password</Key><Value Protected="True">{{P:gone}}</Value></String>
+\t\t\t\t</Entry>
+\t\t\t</Group>
+\t\t</Group>
+\t\t<DeletedObjects/>
+\t</Root>
+</KeePassFile>
+"""
+
+IDS = {
+    "root": "11111111-0000-4000-8000-000000000001",
+    "github": "6f0a0d4e-3c1e-4c9a-9a57-2f3c1d7e8b01",
+    "untitled": "6f0a0d4e-3c1e-4c9a-9a57-2f3c1d7e8b02",
+    "nameless": "6f0a0d4e-3c1e-4c9a-9a57-2f3c1d7e8b03",
+    "work": "11111111-0000-4000-8000-000000000002",
+    "email": "11111111-0000-4000-8000-000000000003",
+    "mail": "6f0a0d4e-3c1e-4c9a-9a57-2f3c1d7e8b04",
+    "empty": "11111111-0000-4000-8000-000000000004",
+    "legacy": "11111111-0000-4000-8000-000000000005",
+    "oldtotp": "6f0a0d4e-3c1e-4c9a-9a57-2f3c1d7e8b05",
+    "bin": "11111111-0000-4000-8000-000000000006",
+    "deleted": "6f0a0d4e-3c1e-4c9a-9a57-2f3c1d7e8b06",
+}
+
+
+def unescape(text):
+    return text.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+
+
+def document(stream):
+    xml = XML.replace("{{", "\0").replace("}}", "\1")
+    xml = xml.format(
+        t2020=kdbx_time(2020, 1, 1),
+        t2021=kdbx_time(2021, 3, 1),
+        t2022=kdbx_time(2022, 2, 1),
+        t2023=kdbx_time(2023, 5, 1),
+        t2024=kdbx_time(2024, 1, 2),
+        **{name: b64uuid(value) for name, value in IDS.items()},
+    )
+    xml = xml.replace("\0", "{{").replace("\1", "}}")
+
+    def protect(match):
+        plain = unescape(match.group(1)).encode()
+        return base64.b64encode(stream.update(plain)).decode()
+
+    return re.sub(r"\{\{P:(.*?)\}\}", protect, xml, flags=re.S).encode()
+
+
+# ---- Container ----
+
+def field(field_id, data):
+    return struct.pack("<BI", field_id, len(data)) + data
+
+
+def variant_dictionary(items):
+    out = struct.pack("<H", 0x0100)
+    for kind, key, value in items:
+        out += struct.pack("<BI", kind, len(key)) + key.encode() + struct.pack("<I", len(value)) + value
+    return out + b"\0"
+
+
+def aes_kdf(composite, seed, rounds):
+    encryptor = Cipher(algorithms.AES(seed), modes.ECB()).encryptor()
+    key = composite
+    for _ in range(rounds):
+        key = encryptor.update(key)
+    return hashlib.sha256(key).digest()
+
+
+def build(name, cipher, kdf):
+    master_seed = fixed_bytes(name + " master seed", 32)
+    composite = hashlib.sha256(hashlib.sha256(PASSWORD).digest()).digest()
+    if kdf == "argon2id":
+        salt = fixed_bytes(name + " salt", 32)
+        memory_kib, iterations, lanes = 256, 2, 2
+        kdf_parameters = variant_dictionary([
+            (0x42, "$UUID", KDF_ARGON2ID),
+            (0x42, "S", salt),
+            (0x04, "P", struct.pack("<I", lanes)),
+            (0x05, "M", struct.pack("<Q", memory_kib * 1024)),
+            (0x05, "I", struct.pack("<Q", iterations)),
+            (0x04, "V", struct.pack("<I", 0x13)),
+        ])
+        transformed = Argon2id(
+            salt=salt, length=32, iterations=iterations, lanes=lanes, memory_cost=memory_kib
+        ).derive(composite)
+    else:
+        seed, rounds = fixed_bytes(name + " seed", 32), 1000
+        kdf_parameters = variant_dictionary([
+            (0x42, "$UUID", KDF_AES),
+            (0x05, "R", struct.pack("<Q", rounds)),
+            (0x42, "S", seed),
+        ])
+        transformed = aes_kdf(composite, seed, rounds)
+
+    iv = fixed_bytes(name + " iv", 12 if cipher == "chacha20" else 16)
+    header = SIGNATURE + struct.pack("<HH", 1, 4)
+    header += field(2, CIPHER_CHACHA20 if cipher == "chacha20" else CIPHER_AES256)
+    header += field(3, struct.pack("<I", 1))
+    header += field(4, master_seed)
+    header += field(7, iv)
+    header += field(11, kdf_parameters)
+    header += field(0, b"\r\n\r\n")
+
+    cipher_key = hashlib.sha256(master_seed + transformed).digest()
+    hmac_base = hashlib.sha512(master_seed + transformed + b"\x01").digest()
+
+    def block_key(index):
+        return hashlib.sha512(struct.pack("<Q", index) + hmac_base).digest()
+
+    out = header + hashlib.sha256(header).digest()
+    out += hmac.new(block_key(2**64 - 1), header, hashlib.sha256).digest()
+
+    stream_key = fixed_bytes(name + " stream key", 64 if cipher == "chacha20" else 32)
+    if cipher == "chacha20":
+        stream_hash = hashlib.sha512(stream_key).digest()
+        inner = field(1, struct.pack("<I", 3)) + field(2, stream_key)
+        stream = chacha20(stream_hash[:32], stream_hash[32:44])
+    else:
+        inner = field(1, struct.pack("<I", 2)) + field(2, stream_key)
+        stream = Salsa20(hashlib.sha256(stream_key).digest(), SALSA20_NONCE)
+    inner += field(0, b"")
+    payload = gzip.compress(inner + document(stream), mtime=0)
+
+    if cipher == "chacha20":
+        encrypted = chacha20(cipher_key, iv).update(payload)
+    else:
+        padder = padding.PKCS7(128).padder()
+        padded = padder.update(payload) + padder.finalize()
+        encryptor = Cipher(algorithms.AES(cipher_key), modes.CBC(iv)).encryptor()
+        encrypted = encryptor.update(padded) + encryptor.finalize()
+
+    for index, block in enumerate([encrypted, b""]):
+        size = struct.pack("<I", len(block))
+        mac = hmac.new(block_key(index), struct.pack("<Q", index) + size + block, hashlib.sha256).digest()
+        out += mac + size + block
+
+    with open(name, "wb") as file:
+        file.write(out)
+
+
+if __name__ == "__main__":
+    os.chdir(os.path.dirname(os.path.abspath(__file__)))
+    build("argon2id-chacha20.kdbx", "chacha20", "argon2id")
+    build("aeskdf-aes256.kdbx", "aes256", "aes")